@@ -0,0 +1,89 @@
+use crate::{
+    page,
+    solana::{AccountEvent, SignedUsdcTransactionsBySlot},
+};
+
+use serde::{Deserialize, Serialize};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Query parameters of /addresses/{address}/events.  Slot bounds are inclusive, and type is that of the event, e.g.
+// close_account
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct AccountEventsQuery {
+    pub symbol: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<String>,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+    // Maximum number of events to return
+    pub limit: Option<usize>,
+}
+
+impl AccountEventsQuery {
+    pub fn get_limit(&self) -> usize {
+        page::get_limit(self.limit)
+    }
+
+    pub fn matches(&self, address: &str, slot: u64, account_event: &AccountEvent) -> bool {
+        account_event.event.get_account() == address
+            && self.symbol.as_ref().is_none_or(|symbol| account_event.symbol.eq_ignore_ascii_case(symbol))
+            && self.kind.as_ref().is_none_or(|kind| account_event.event.get_type() == kind)
+            && self.from_slot.is_none_or(|from_slot| slot >= from_slot)
+            && self.to_slot.is_none_or(|to_slot| slot <= to_slot)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AddressEvent {
+    pub slot: u64,
+    #[serde(flatten)]
+    pub event: AccountEvent,
+}
+
+// The earliest events acting upon an address, in execution order.  If there are more than the limit, narrow the slot
+// range to fetch the rest
+#[derive(Clone, Debug, Serialize)]
+pub struct AddressEvents {
+    pub address: String,
+    pub events: Vec<AddressEvent>,
+    pub has_more: bool,
+}
+
+impl AddressEvents {
+    // Build the response from up to one more than the limit of matching events
+    pub fn new(address: &str, query: &AccountEventsQuery, events: Vec<AddressEvent>) -> Self {
+        let (events, has_more) = page::take_page(events, query.get_limit());
+
+        AddressEvents {
+            address: address.to_string(),
+            events,
+            has_more,
+        }
+    }
+}
+
+pub fn get_address_events<'a>(
+    address: &str,
+    query: &AccountEventsQuery,
+    slots: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>,
+) -> AddressEvents {
+    let events = slots
+        .flat_map(|txns_by_slot| {
+            txns_by_slot
+                .account_events
+                .iter()
+                .filter(|account_event| query.matches(address, txns_by_slot.slot, account_event))
+                .map(|account_event| AddressEvent {
+                    slot: txns_by_slot.slot,
+                    event: account_event.clone(),
+                })
+        })
+        .take(page::get_fetch_limit(query.get_limit()))
+        .collect();
+
+    AddressEvents::new(address, query, events)
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+pub mod unit_tests;
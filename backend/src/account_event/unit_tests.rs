@@ -0,0 +1,80 @@
+use crate::{
+    account_event::{get_address_events, AccountEventsQuery},
+    solana::{AccountEvent, SignedUsdcTransactionsBySlot, UsdcEvent},
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+pub fn get_account_event(account: &str, event: fn(String) -> UsdcEvent) -> AccountEvent {
+    AccountEvent {
+        signature: format!("{}Signature", account),
+        mint: "USDCMint".to_string(),
+        symbol: "USDC".to_string(),
+        event: event(account.to_string()),
+        instruction_index: 0,
+        stack_height: 1,
+        block_time: None,
+    }
+}
+
+pub fn revoke(account: String) -> UsdcEvent {
+    UsdcEvent::Revoke {
+        account,
+        owner: "Owner".to_string(),
+    }
+}
+
+pub fn freeze(account: String) -> UsdcEvent {
+    UsdcEvent::FreezeAccount {
+        account,
+        freeze_authority: "Issuer".to_string(),
+    }
+}
+
+// Treasury is frozen in slot 1, and has a delegation revoked in slot 2 and 3, alongside events on other accounts
+fn get_slots() -> Vec<SignedUsdcTransactionsBySlot> {
+    [
+        (1, vec![get_account_event("Treasury", freeze), get_account_event("Customer", freeze)]),
+        (2, vec![get_account_event("Customer", revoke), get_account_event("Treasury", revoke)]),
+        (3, vec![get_account_event("Treasury", revoke)]),
+    ]
+    .into_iter()
+    .map(|(slot, account_events)| SignedUsdcTransactionsBySlot {
+        slot,
+        account_events,
+        ..Default::default()
+    })
+    .collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_list_events_acting_upon_address() -> Result<(), String> {
+    let slots = get_slots();
+    let all = get_address_events("Treasury", &AccountEventsQuery::default(), slots.iter());
+    let got: Vec<(u64, &str)> = all.events.iter().map(|event| (event.slot, event.event.event.get_type())).collect();
+
+    if got == [(1, "freeze_account"), (2, "revoke"), (3, "revoke")] && !all.has_more {
+        Ok(())
+    } else {
+        Err(format!("Wrong events {:?}", got))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_filter_and_limit_events() -> Result<(), String> {
+    let slots = get_slots();
+    let query = AccountEventsQuery {
+        kind: Some("revoke".to_string()),
+        limit: Some(1),
+        ..AccountEventsQuery::default()
+    };
+    let limited = get_address_events("Treasury", &query, slots.iter());
+    let got: Vec<u64> = limited.events.iter().map(|event| event.slot).collect();
+
+    if got == [2] && limited.has_more {
+        Ok(())
+    } else {
+        Err(format!("Wrong events {:?} (has_more: {})", got, limited.has_more))
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    query::{TransactionsPage, TransactionsQuery},
+    solana::{SignedUsdcTransaction, SignedUsdcTransactionsBySlot, TransferRecord},
+};
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Inbound,
+    Outbound,
+    // From the address to itself, which counts as both inbound and outbound, so nets to zero
+    #[serde(rename = "self")]
+    SelfTransfer,
+}
+
+impl Direction {
+    pub fn get(address: &str, transfer: &TransferRecord) -> Direction {
+        match (transfer.txn.from == address, transfer.txn.to == address) {
+            (true, true) => Direction::SelfTransfer,
+            (true, false) => Direction::Outbound,
+            _ => Direction::Inbound,
+        }
+    }
+}
+
+// Flow of one token through an address, in the token's base units
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FlowTotals {
+    pub mint: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub transfer_count: u64,
+    pub inbound: u128,
+    pub outbound: u128,
+    pub net: i128,
+}
+
+impl FlowTotals {
+    pub fn new(mint: String, symbol: String, decimals: u8, transfer_count: u64, inbound: u128, outbound: u128) -> Self {
+        FlowTotals {
+            mint,
+            symbol,
+            decimals,
+            transfer_count,
+            inbound,
+            outbound,
+            net: inbound as i128 - outbound as i128,
+        }
+    }
+}
+
+// Sum the flows of the transfers matching the query, by mint
+pub fn sum_flow_totals<'a>(
+    address: &str,
+    query: &TransactionsQuery,
+    slots: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>,
+) -> Vec<FlowTotals> {
+    let mut totals: BTreeMap<String, FlowTotals> = BTreeMap::new();
+
+    for txns_by_slot in slots.filter_map(|txns_by_slot| query.filter_slot(txns_by_slot)) {
+        for (_, transfer) in txns_by_slot.transfers() {
+            let flow = totals.entry(transfer.mint.clone()).or_insert_with(|| FlowTotals {
+                mint: transfer.mint.clone(),
+                symbol: transfer.symbol.clone(),
+                decimals: transfer.txn.decimals,
+                ..FlowTotals::default()
+            });
+            let amount = transfer.txn.amount as u128;
+
+            flow.transfer_count += 1;
+
+            if transfer.txn.to == address {
+                flow.inbound += amount;
+                flow.net += amount as i128;
+            }
+
+            if transfer.txn.from == address {
+                flow.outbound += amount;
+                flow.net -= amount as i128;
+            }
+        }
+    }
+
+    totals.into_values().collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[derive(Clone, Debug, Serialize)]
+pub struct AddressTransfer {
+    pub slot: u64,
+    pub direction: Direction,
+    // Net flow of the transfer's token through the address, from the start of the queried slot range up to and
+    // including this transfer
+    pub running_net: i128,
+    pub transaction: SignedUsdcTransaction,
+}
+
+// One page of the transfers from or to an address, in slot order.  The totals cover every matching transfer in the
+// store, not only those of this page, and the cursor works as for /transactions
+#[derive(Clone, Debug, Serialize)]
+pub struct AddressHistory {
+    pub address: String,
+    pub totals: Vec<FlowTotals>,
+    pub transfers: Vec<AddressTransfer>,
+    pub next_cursor: Option<u64>,
+    pub has_more: bool,
+}
+
+// Build the history from a page of the query (whose address must be set), reading flow totals over a slot range with
+// read_totals
+pub fn get_history<E>(
+    address: &str,
+    query: &TransactionsQuery,
+    page: TransactionsPage,
+    mut read_totals: impl FnMut(&TransactionsQuery) -> Result<Vec<FlowTotals>, E>,
+) -> Result<AddressHistory, E> {
+    let totals = read_totals(&TransactionsQuery {
+        after_slot: None,
+        ..query.clone()
+    })?;
+
+    // The running net of this page continues from the net flow up to and including the cursor
+    let mut running_nets: HashMap<String, i128> = match query.after_slot {
+        Some(after_slot) => read_totals(&TransactionsQuery {
+            to_slot: Some(query.to_slot.map_or(after_slot, |to_slot| to_slot.min(after_slot))),
+            after_slot: None,
+            ..query.clone()
+        })?
+        .into_iter()
+        .map(|flow| (flow.mint, flow.net))
+        .collect(),
+        None => HashMap::new(),
+    };
+    let mut transfers = Vec::new();
+
+    for txns_by_slot in page.slots {
+        for (transaction, transfer) in txns_by_slot.transfers() {
+            let direction = Direction::get(address, transfer);
+            let running_net = running_nets.entry(transfer.mint.clone()).or_default();
+
+            match direction {
+                Direction::Inbound => *running_net += transfer.txn.amount as i128,
+                Direction::Outbound => *running_net -= transfer.txn.amount as i128,
+                Direction::SelfTransfer => (),
+            }
+
+            transfers.push(AddressTransfer {
+                slot: txns_by_slot.slot,
+                direction,
+                running_net: *running_net,
+                transaction: SignedUsdcTransaction::new(transaction, transfer),
+            });
+        }
+    }
+
+    Ok(AddressHistory {
+        address: address.to_string(),
+        totals,
+        transfers,
+        next_cursor: page.next_cursor,
+        has_more: page.has_more,
+    })
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,97 @@
+use crate::{
+    address::{get_history, sum_flow_totals, Direction, FlowTotals},
+    config::RetentionPolicy,
+    query::TransactionsQuery,
+    retention::TransactionBuffer,
+    test_data::{get_slot, get_transaction},
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Treasury receives 100 and 50 USDC, sends 30 USDC and 7 USDT, and moves 5 USDC to itself
+fn get_buffer() -> TransactionBuffer {
+    let mut buffer = TransactionBuffer::new(RetentionPolicy::default());
+    let slots = [
+        (1, vec![get_transaction("USDC", "Customer", "Treasury", 100)]),
+        (2, vec![get_transaction("USDC", "Customer", "Supplier", 1000)]),
+        (
+            3,
+            vec![
+                get_transaction("USDC", "Treasury", "Supplier", 30),
+                get_transaction("USDT", "Treasury", "Supplier", 7),
+            ],
+        ),
+        (4, vec![get_transaction("USDC", "Treasury", "Treasury", 5)]),
+        (5, vec![get_transaction("USDC", "Customer", "Treasury", 50)]),
+    ];
+
+    for (slot, txns) in slots {
+        buffer.insert(get_slot(slot, txns));
+    }
+
+    buffer
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_sum_flows_by_mint() -> Result<(), String> {
+    let buffer = get_buffer();
+    let query = TransactionsQuery {
+        address: Some("Treasury".to_string()),
+        ..TransactionsQuery::default()
+    };
+    let totals: Vec<(String, u64, u128, u128, i128)> =
+        sum_flow_totals("Treasury", &query, buffer.iter_address("Treasury"))
+            .into_iter()
+            .map(|flow| (flow.symbol, flow.transfer_count, flow.inbound, flow.outbound, flow.net))
+            .collect();
+
+    if totals == vec![("USDC".to_string(), 4, 155, 35, 120), ("USDT".to_string(), 1, 0, 7, -7)] {
+        Ok(())
+    } else {
+        Err(format!("Wrong totals {:?}", totals))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_continue_running_net_across_pages() -> Result<(), String> {
+    let buffer = get_buffer();
+    let mut query = TransactionsQuery {
+        address: Some("Treasury".to_string()),
+        symbol: Some("USDC".to_string()),
+        limit: Some(2),
+        ..TransactionsQuery::default()
+    };
+    let mut transfers: Vec<(u64, Direction, i128)> = Vec::new();
+    let mut totals: Vec<Vec<FlowTotals>> = Vec::new();
+
+    for _ in 0..2 {
+        let page = query.paginate(buffer.iter_address("Treasury"));
+        let history = get_history("Treasury", &query, page, |query| {
+            Ok::<_, String>(sum_flow_totals("Treasury", query, buffer.iter_address("Treasury")))
+        })?;
+
+        query.after_slot = history.next_cursor;
+        totals.push(history.totals);
+        transfers.extend(
+            history
+                .transfers
+                .iter()
+                .map(|transfer| (transfer.slot, transfer.direction, transfer.running_net)),
+        );
+    }
+
+    // Slot 2 does not involve the treasury, and every page reports the totals of the whole range
+    let expected = vec![
+        (1, Direction::Inbound, 100),
+        (3, Direction::Outbound, 70),
+        (4, Direction::SelfTransfer, 70),
+        (5, Direction::Inbound, 120),
+    ];
+
+    if transfers == expected && totals[0] == totals[1] && totals[0].len() == 1 && totals[0][0].net == 120 {
+        Ok(())
+    } else {
+        Err(format!("Wrong history {:?}, totals {:?}", transfers, totals))
+    }
+}
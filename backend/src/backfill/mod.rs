@@ -0,0 +1,206 @@
+use crate::{
+    checkpoint::write_atomically,
+    config::RetryPolicy,
+    slot::{get_retry_delay, process_slots},
+    state::AppState,
+};
+
+use futures::StreamExt;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use std::{fs, io, path::Path, pin::pin};
+use tokio::time::sleep;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Inclusive range of historical slots to ingest
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+pub struct BackfillRequest {
+    pub start_slot: u64,
+    pub end_slot: u64,
+}
+
+impl BackfillRequest {
+    // Only slots up to the current tip (of the RPC node) can be backfilled.  A range reaching beyond it would leave the
+    // backfill walking through empty chunks until the chain caught up
+    pub fn validate(&self, tip: u64) -> Result<(), String> {
+        if self.end_slot < self.start_slot {
+            Err("end_slot must not be less than start_slot".to_string())
+        } else if self.end_slot > tip {
+            Err(format!("end_slot must not be after the current slot {}", tip))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillStatus {
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct BackfillProgress {
+    pub start_slot: u64,
+    pub end_slot: u64,
+    // Every slot before this one has been processed
+    pub next_slot: u64,
+    // Number of blocks produced in the range so far processed.  Slots in which no block was produced are not counted
+    pub blocks_processed: u64,
+    // Number of slots whose block could not be fetched, even after retrying.  These are listed in /dead_letters
+    #[serde(default)]
+    pub failed_slots: u64,
+    pub status: BackfillStatus,
+    pub error: Option<String>,
+}
+
+impl BackfillProgress {
+    // Continue an unfinished backfill of the same range from where it stopped, otherwise start from the beginning
+    pub fn new(request: BackfillRequest, previous: Option<&BackfillProgress>) -> Self {
+        match previous {
+            Some(previous)
+                if previous.start_slot == request.start_slot
+                    && previous.end_slot == request.end_slot
+                    && previous.status != BackfillStatus::Completed =>
+            {
+                BackfillProgress {
+                    status: BackfillStatus::Running,
+                    error: None,
+                    ..previous.clone()
+                }
+            }
+            _ => BackfillProgress {
+                start_slot: request.start_slot,
+                end_slot: request.end_slot,
+                next_slot: request.start_slot,
+                blocks_processed: 0,
+                failed_slots: 0,
+                status: BackfillStatus::Running,
+                error: None,
+            },
+        }
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+pub fn read_progress(path: &Path) -> Option<BackfillProgress> {
+    let contents = fs::read_to_string(path).ok()?;
+
+    serde_json::from_str(&contents)
+        .inspect_err(|e| warn!("Ignoring backfill progress file {}: {}", path.display(), e))
+        .ok()
+}
+
+pub fn write_progress(path: &Path, progress: &BackfillProgress) -> io::Result<()> {
+    write_atomically(path, &serde_json::to_vec(progress)?)
+}
+
+// Last slot of the chunk starting at next_slot
+pub fn get_chunk_end(next_slot: u64, end_slot: u64, chunk_slots: u64) -> u64 {
+    next_slot.saturating_add(chunk_slots.max(1) - 1).min(end_slot)
+}
+
+async fn get_blocks(
+    rpc_client: &RpcClient,
+    start_slot: u64,
+    end_slot: u64,
+    retry: &RetryPolicy,
+) -> Result<Vec<u64>, ClientError> {
+    let mut attempt: u32 = 1;
+
+    loop {
+        match rpc_client.get_blocks(start_slot, Some(end_slot)).await {
+            Ok(slots) => return Ok(slots),
+            Err(e) if attempt >= retry.max_attempts => return Err(e),
+            Err(e) => {
+                let delay = get_retry_delay(retry, attempt);
+
+                warn!(
+                    "Backfill: getBlocks {}..={} attempt {} failed ({}). Retrying in {:.3?}",
+                    start_slot, end_slot, attempt, e, delay
+                );
+                sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+async fn save_progress(state: &AppState, progress: &BackfillProgress) {
+    *state.backfill.lock().await = Some(progress.clone());
+
+    if let Err(e) = write_progress(&state.config.backfill_path, progress) {
+        error!(
+            "Unable to write backfill progress {}: {}",
+            state.config.backfill_path.display(),
+            e
+        );
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Walk the requested range one chunk at a time, storing the results alongside those of the monitor.  Progress is saved
+// after each chunk, so an interrupted backfill resumes from the start of the chunk it was working on
+pub async fn run_backfill(state: AppState, mut progress: BackfillProgress) {
+    let config = &state.config;
+
+    info!(
+        "Backfill: slots {} to {}, starting from slot {}",
+        progress.start_slot, progress.end_slot, progress.next_slot
+    );
+    save_progress(&state, &progress).await;
+
+    while progress.next_slot <= progress.end_slot {
+        let chunk_end = get_chunk_end(progress.next_slot, progress.end_slot, config.backfill_chunk_slots);
+        let slots = match get_blocks(&state.rpc_client, progress.next_slot, chunk_end, &config.retry).await {
+            Ok(slots) => slots,
+            Err(e) => {
+                error!("Backfill: giving up at slot {}: {}", progress.next_slot, e);
+                progress.status = BackfillStatus::Failed;
+                progress.error = Some(e.to_string());
+                save_progress(&state, &progress).await;
+                return;
+            }
+        };
+        let mut processed_slots = pin!(process_slots(&state.rpc_client, &state.owners, &slots, config));
+
+        while let Some(result) = processed_slots.next().await {
+            let fetched = result.is_ok();
+
+            // A block that was fetched but could not be stored is a dead letter too
+            match state.record_slot(result).await {
+                Ok(_) if fetched => progress.blocks_processed += 1,
+                Ok(_) => (),
+                Err(_) => progress.failed_slots += 1,
+            }
+        }
+
+        info!(
+            "Backfill: processed slots {} to {} of {} ({} blocks, {} failed)",
+            progress.start_slot,
+            chunk_end,
+            progress.end_slot,
+            progress.blocks_processed,
+            progress.failed_slots
+        );
+
+        // The range may end at the very last slot, after which there is nothing left to do
+        let Some(next_slot) = chunk_end.checked_add(1) else {
+            break;
+        };
+
+        progress.next_slot = next_slot;
+        save_progress(&state, &progress).await;
+    }
+
+    progress.status = BackfillStatus::Completed;
+    info!("Backfill: completed slots {} to {}", progress.start_slot, progress.end_slot);
+    save_progress(&state, &progress).await;
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,235 @@
+use crate::{
+    backfill::{get_chunk_end, read_progress, run_backfill, BackfillProgress, BackfillRequest, BackfillStatus},
+    config::{Config, RetryPolicy},
+    slot::unit_tests::{create_mock_client, get_block_responses, MockRpcSender},
+    solana::SignedUsdcTransactionsBySlot,
+    state::AppState,
+};
+
+use serde_json::json;
+use solana_client::rpc_request::RpcRequest;
+use std::{env, fs, path::Path, time::Duration};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn get_backfill_config(test_name: &str) -> Config {
+    Config {
+        backfill_path: env::temp_dir().join(format!(
+            "solana_usdc_monitor_{}_{}.backfill",
+            test_name,
+            std::process::id()
+        )),
+        retry: RetryPolicy {
+            max_attempts: 2,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(2),
+        },
+        ..Config::default()
+    }
+}
+
+fn get_request() -> BackfillRequest {
+    BackfillRequest {
+        start_slot: 10,
+        end_slot: 12,
+    }
+}
+
+fn remove_progress(path: &Path) {
+    let _ = fs::remove_file(path);
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_split_range_into_chunks() -> Result<(), String> {
+    let cases = [(10, 100, 25, 34), (90, 100, 25, 100), (10, 10, 25, 10), (10, 100, 0, 10)];
+
+    for (next_slot, end_slot, chunk_slots, expected) in cases {
+        let chunk_end = get_chunk_end(next_slot, end_slot, chunk_slots);
+
+        if chunk_end != expected {
+            return Err(format!(
+                "Chunk of {} slots from {} to {} should end at {}, got {}",
+                chunk_slots, next_slot, end_slot, expected, chunk_end
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_resume_only_unfinished_backfill_of_same_range() -> Result<(), String> {
+    let failed = BackfillProgress {
+        next_slot: 11,
+        blocks_processed: 1,
+        status: BackfillStatus::Failed,
+        error: Some("timed out".to_string()),
+        ..BackfillProgress::new(get_request(), None)
+    };
+    let resumed = BackfillProgress::new(get_request(), Some(&failed));
+
+    if resumed.next_slot != 11 || resumed.status != BackfillStatus::Running || resumed.error.is_some() {
+        return Err(format!("Should have resumed from slot 11, got {:?}", resumed));
+    }
+
+    let completed = BackfillProgress {
+        status: BackfillStatus::Completed,
+        ..failed.clone()
+    };
+    let other_range = BackfillRequest {
+        start_slot: 5,
+        ..get_request()
+    };
+
+    match (
+        BackfillProgress::new(get_request(), Some(&completed)).next_slot,
+        BackfillProgress::new(other_range, Some(&failed)).next_slot,
+    ) {
+        (10, 5) => Ok(()),
+        other => Err(format!("Should have restarted from the beginning of the range, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_03_should_store_backfilled_slots_in_order() -> Result<(), String> {
+    let _ = env_logger::builder()
+        .is_test(true)
+        .format_timestamp_millis()
+        .try_init();
+
+    let config = get_backfill_config("in_order");
+    let backfill_path = config.backfill_path.clone();
+    let mut responses = get_block_responses();
+
+    responses.insert(RpcRequest::GetBlocks, json!([10, 11, 12]));
+
+    let state = AppState::new(config, create_mock_client(MockRpcSender::new(responses)), None);
+
+    // A slot already recorded by the monitor
+    state.txns.lock().await.insert(SignedUsdcTransactionsBySlot {
+        slot: 20,
+        ..Default::default()
+    });
+
+    run_backfill(state.clone(), BackfillProgress::new(get_request(), None)).await;
+    let persisted = read_progress(&backfill_path);
+    remove_progress(&backfill_path);
+
+    let slots: Vec<u64> = state.txns.lock().await.iter().map(|txns_by_slot| txns_by_slot.slot).collect();
+
+    if slots != vec![10, 11, 12, 20] {
+        return Err(format!("Backfilled slots stored out of order: {:?}", slots));
+    }
+
+    match persisted {
+        Some(BackfillProgress {
+            next_slot: 13,
+            blocks_processed: 3,
+            status: BackfillStatus::Completed,
+            ..
+        }) => Ok(()),
+        other => Err(format!("Wrong backfill progress {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_04_should_record_failure_for_resumption() -> Result<(), String> {
+    let config = get_backfill_config("failure");
+    let backfill_path = config.backfill_path.clone();
+
+    // No response to getBlocks has been configured, so every request fails
+    let state = AppState::new(config, create_mock_client(MockRpcSender::new(get_block_responses())), None);
+
+    run_backfill(state.clone(), BackfillProgress::new(get_request(), None)).await;
+    let persisted = read_progress(&backfill_path);
+    remove_progress(&backfill_path);
+
+    match persisted {
+        Some(progress) if progress.status == BackfillStatus::Failed && progress.next_slot == 10 => {
+            if Some(&progress) == state.backfill.lock().await.as_ref() {
+                Ok(())
+            } else {
+                Err("Reported progress differs from persisted progress".to_string())
+            }
+        }
+        other => Err(format!("Should have recorded a failed backfill, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_reject_range_beyond_current_slot() -> Result<(), String> {
+    let reversed = BackfillRequest {
+        start_slot: 12,
+        end_slot: 10,
+    };
+    let beyond_tip = BackfillRequest {
+        end_slot: u64::MAX,
+        ..get_request()
+    };
+
+    match (get_request().validate(12), reversed.validate(12), beyond_tip.validate(12)) {
+        (Ok(()), Err(_), Err(_)) => Ok(()),
+        other => Err(format!("Wrong validation {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_06_should_finish_range_ending_at_last_slot() -> Result<(), String> {
+    let config = get_backfill_config("last_slot");
+    let backfill_path = config.backfill_path.clone();
+    let mut responses = get_block_responses();
+
+    responses.insert(RpcRequest::GetBlocks, json!([]));
+
+    let state = AppState::new(config, create_mock_client(MockRpcSender::new(responses)), None);
+    let request = BackfillRequest {
+        start_slot: u64::MAX - 1,
+        end_slot: u64::MAX,
+    };
+
+    run_backfill(state.clone(), BackfillProgress::new(request, None)).await;
+    let persisted = read_progress(&backfill_path);
+    remove_progress(&backfill_path);
+
+    match persisted {
+        Some(progress) if progress.status == BackfillStatus::Completed => Ok(()),
+        other => Err(format!("Should have completed the backfill, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_07_should_count_failed_slots_separately() -> Result<(), String> {
+    let config = get_backfill_config("failed_slots");
+    let backfill_path = config.backfill_path.clone();
+    let mut responses = get_block_responses();
+
+    responses.insert(RpcRequest::GetBlocks, json!([10, 11, 12]));
+
+    // Every getBlock request fails
+    let sender = MockRpcSender {
+        failure_code: -32004,
+        failures: u32::MAX,
+        ..MockRpcSender::new(responses)
+    };
+    let state = AppState::new(config, create_mock_client(sender), None);
+
+    run_backfill(state.clone(), BackfillProgress::new(get_request(), None)).await;
+    let persisted = read_progress(&backfill_path);
+    remove_progress(&backfill_path);
+
+    match persisted {
+        Some(BackfillProgress {
+            blocks_processed: 0,
+            failed_slots: 3,
+            status: BackfillStatus::Completed,
+            ..
+        }) if state.dead_letters.lock().await.slots().count() == 3 => Ok(()),
+        other => Err(format!("Wrong backfill progress {:?}", other)),
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{
+    config::Config,
+    solana::{SupplyChangeKind, UsdcTransfer},
+    token_account::get_account_keys,
+    transaction::ProcessedTransaction,
+    MAX_RECONCILIATION_DISAGREEMENTS,
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedTransaction, EncodedTransactionWithStatusMeta, UiTransaction,
+    UiTransactionTokenBalance,
+};
+use std::collections::{BTreeMap, VecDeque};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Change in the balance of one token account over a transaction, in the token's base units
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct BalanceDelta {
+    pub account: String,
+    pub mint: String,
+    pub symbol: String,
+    pub delta: i128,
+}
+
+// Token account and mint
+type BalanceKey = (String, String);
+
+fn add_token_balances(
+    balances: &mut BTreeMap<BalanceKey, i128>,
+    account_keys: &[String],
+    token_balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+    config: &Config,
+    sign: i128,
+) {
+    if let OptionSerializer::Some(token_balances) = token_balances {
+        for balance in token_balances.iter().filter(|balance| config.get_mint(&balance.mint).is_some()) {
+            // An unparseable amount is left out, so it shows up as a disagreement rather than being hidden
+            if let (Some(account), Ok(amount)) = (
+                account_keys.get(balance.account_index as usize),
+                balance.ui_token_amount.amount.parse::<u64>(),
+            ) {
+                *balances.entry((account.clone(), balance.mint.clone())).or_default() += sign * amount as i128;
+            }
+        }
+    }
+}
+
+// Detect movements of the monitored tokens without parsing any instructions, by comparing the post token balances of a
+// transaction with its pre token balances.  An account missing from either list (because the transaction created or
+// closed it) had a balance of zero.  Only accounts whose balance changed are returned, ordered by account and mint
+pub fn get_balance_deltas(txn: &EncodedTransactionWithStatusMeta, config: &Config) -> Vec<BalanceDelta> {
+    let (Some(meta), EncodedTransaction::Json(UiTransaction { message, .. })) = (&txn.meta, &txn.transaction) else {
+        return vec![];
+    };
+    let account_keys = get_account_keys(message, Some(meta));
+    let mut balances: BTreeMap<BalanceKey, i128> = BTreeMap::new();
+
+    add_token_balances(&mut balances, &account_keys, &meta.pre_token_balances, config, -1);
+    add_token_balances(&mut balances, &account_keys, &meta.post_token_balances, config, 1);
+
+    balances
+        .into_iter()
+        .filter(|(_, delta)| *delta != 0)
+        .filter_map(|((account, mint), delta)| {
+            config.get_mint(&mint).map(|monitored_mint| BalanceDelta {
+                account,
+                symbol: monitored_mint.symbol.clone(),
+                mint,
+                delta,
+            })
+        })
+        .collect()
+}
+
+// The changes in balance implied by the transfers, mints and burns decoded from the instructions.  A Token-2022
+// transfer fee is withheld from the amount received
+fn get_instruction_deltas(processed: &ProcessedTransaction) -> BTreeMap<BalanceKey, i128> {
+    let mut deltas: BTreeMap<BalanceKey, i128> = BTreeMap::new();
+    let mut add = |account: &str, mint: &str, delta: i128| {
+        *deltas.entry((account.to_string(), mint.to_string())).or_default() += delta;
+    };
+
+    for transfer in processed.transaction.transfers.iter() {
+        let UsdcTransfer { from, to, amount, fee, .. } = &transfer.txn;
+
+        add(from, &transfer.mint, -(*amount as i128));
+        add(to, &transfer.mint, *amount as i128 - fee.unwrap_or(0) as i128);
+    }
+
+    for supply_change in processed.supply_changes.iter() {
+        let amount = supply_change.amount as i128;
+
+        match supply_change.kind {
+            SupplyChangeKind::Mint => add(&supply_change.account, &supply_change.mint, amount),
+            SupplyChangeKind::Burn => add(&supply_change.account, &supply_change.mint, -amount),
+        }
+    }
+
+    deltas
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// An account whose change in balance differs from that implied by the decoded instructions
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Discrepancy {
+    pub account: String,
+    pub mint: String,
+    pub balance_delta: i128,
+    pub instruction_delta: i128,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Disagreement {
+    pub signature: String,
+    pub slot: u64,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+// Compare the balance changes of a transaction with the results of process_transaction.  Returns None if neither
+// detector saw the monitored tokens move, so there was nothing to check
+pub fn reconcile(
+    txn: &EncodedTransactionWithStatusMeta,
+    processed: &ProcessedTransaction,
+    config: &Config,
+) -> Option<Vec<Discrepancy>> {
+    let mut deltas: BTreeMap<BalanceKey, (i128, i128)> = BTreeMap::new();
+
+    for balance_delta in get_balance_deltas(txn, config) {
+        deltas.entry((balance_delta.account, balance_delta.mint)).or_default().0 = balance_delta.delta;
+    }
+
+    for (key, delta) in get_instruction_deltas(processed) {
+        deltas.entry(key).or_default().1 = delta;
+    }
+
+    // Transfers from an account to itself, or that net to zero, leave no trace in either
+    deltas.retain(|_, (balance_delta, instruction_delta)| *balance_delta != 0 || *instruction_delta != 0);
+
+    (!deltas.is_empty()).then(|| {
+        deltas
+            .into_iter()
+            .filter(|(_, (balance_delta, instruction_delta))| balance_delta != instruction_delta)
+            .map(|((account, mint), (balance_delta, instruction_delta))| Discrepancy {
+                account,
+                mint,
+                balance_delta,
+                instruction_delta,
+            })
+            .collect()
+    })
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Outcome of cross-checking every transaction that moved the monitored tokens.  Only the most recent disagreements are
+// kept
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ReconciliationReport {
+    pub checked: u64,
+    pub agreed: u64,
+    pub disagreed: u64,
+    pub disagreements: VecDeque<Disagreement>,
+}
+
+impl ReconciliationReport {
+    // Reconcile a transaction of this slot, and record the outcome
+    pub fn check(
+        &mut self,
+        slot: u64,
+        txn: &EncodedTransactionWithStatusMeta,
+        processed: &ProcessedTransaction,
+        config: &Config,
+    ) {
+        let Some(discrepancies) = reconcile(txn, processed, config) else {
+            return;
+        };
+
+        self.checked += 1;
+
+        if discrepancies.is_empty() {
+            self.agreed += 1;
+            return;
+        }
+
+        let signature = match &txn.transaction {
+            EncodedTransaction::Json(UiTransaction { signatures, .. }) => signatures.first().cloned(),
+            _ => None,
+        };
+
+        warn!(
+            "{}: token balances disagree with the instructions for {} accounts",
+            signature.as_deref().unwrap_or("Unsigned transaction"),
+            discrepancies.len()
+        );
+        self.disagreed += 1;
+        self.push(Disagreement {
+            signature: signature.unwrap_or_default(),
+            slot,
+            discrepancies,
+        });
+    }
+
+    pub fn merge(&mut self, other: &ReconciliationReport) {
+        self.checked += other.checked;
+        self.agreed += other.agreed;
+        self.disagreed += other.disagreed;
+
+        for disagreement in other.disagreements.iter() {
+            self.push(disagreement.clone());
+        }
+    }
+
+    fn push(&mut self, disagreement: Disagreement) {
+        if self.disagreements.len() >= MAX_RECONCILIATION_DISAGREEMENTS {
+            self.disagreements.pop_front();
+        }
+
+        self.disagreements.push_back(disagreement);
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
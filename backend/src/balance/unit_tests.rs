@@ -0,0 +1,186 @@
+use crate::{
+    balance::{get_balance_deltas, reconcile, BalanceDelta, Discrepancy, ReconciliationReport},
+    config::Config,
+    instruction::test_data::{get_parsed_ui_instruction_for_test, DESTINATION, SOURCE},
+    transaction::{
+        process_transaction,
+        unit_tests::{get_ui_txn_status_meta, get_v0_txn, V0_DESTINATION, V0_SOURCE},
+        ProcessedTransaction,
+    },
+    MAX_RECONCILIATION_DISAGREEMENTS, USDC_MINT,
+};
+
+use serde_json::json;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer,
+    parse_accounts::{ParsedAccount, ParsedAccountSource},
+    EncodedTransaction, EncodedTransactionWithStatusMeta, UiInstruction, UiMessage, UiParsedMessage, UiTransaction,
+    UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+const OTHER_MINT: &str = "Some unmonitored mint";
+
+fn get_token_balance(account_index: u8, mint: &str, amount: u64) -> UiTransactionTokenBalance {
+    serde_json::from_value(json!({
+        "accountIndex": account_index,
+        "mint": mint,
+        "uiTokenAmount": {
+            "amount": amount.to_string(),
+            "decimals": 6,
+            "uiAmount": null,
+            "uiAmountString": ""
+        }
+    }))
+    .unwrap()
+}
+
+// The sender moves 1.47 USDC into the receiver's new account, while an unmonitored token moves too.  Pass no
+// instructions to simulate a program whose transfers are not parsed
+fn get_txn(instructions: Vec<UiInstruction>) -> EncodedTransactionWithStatusMeta {
+    EncodedTransactionWithStatusMeta {
+        transaction: EncodedTransaction::Json(UiTransaction {
+            signatures: vec!["Signature".to_string()],
+            message: UiMessage::Parsed(UiParsedMessage {
+                account_keys: ["FeePayer", SOURCE, DESTINATION, "Treasury"]
+                    .iter()
+                    .map(|pubkey| ParsedAccount {
+                        pubkey: pubkey.to_string(),
+                        signer: false,
+                        source: Some(ParsedAccountSource::Transaction),
+                        writable: true,
+                    })
+                    .collect(),
+                recent_blockhash: "".to_string(),
+                instructions,
+                address_table_lookups: None,
+            }),
+        }),
+        meta: Some(UiTransactionStatusMeta {
+            pre_token_balances: OptionSerializer::Some(vec![
+                get_token_balance(1, USDC_MINT, 5_000_000),
+                get_token_balance(3, OTHER_MINT, 10),
+            ]),
+            post_token_balances: OptionSerializer::Some(vec![
+                get_token_balance(1, USDC_MINT, 3_530_000),
+                get_token_balance(2, USDC_MINT, 1_470_000),
+                get_token_balance(3, OTHER_MINT, 20),
+            ]),
+            ..get_ui_txn_status_meta(false)
+        }),
+        version: None,
+    }
+}
+
+fn get_processed(txn: &EncodedTransactionWithStatusMeta) -> Result<ProcessedTransaction, String> {
+    process_transaction(txn, &Config::default()).map_err(|err| err.to_string())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_detect_balance_changes_of_monitored_mints() -> Result<(), String> {
+    let deltas = get_balance_deltas(&get_txn(vec![]), &Config::default());
+    let get_delta = |account: &str, delta| BalanceDelta {
+        account: account.to_string(),
+        mint: USDC_MINT.to_string(),
+        symbol: "USDC".to_string(),
+        delta,
+    };
+
+    if deltas == [get_delta(DESTINATION, 1_470_000), get_delta(SOURCE, -1_470_000)] {
+        Ok(())
+    } else {
+        Err(format!("Wrong balance deltas {:?}", deltas))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_agree_with_decoded_transfer() -> Result<(), String> {
+    let txn = get_txn(vec![get_parsed_ui_instruction_for_test(2, false)]);
+    let processed = get_processed(&txn)?;
+    let mut report = ReconciliationReport::default();
+
+    report.check(1, &txn, &processed, &Config::default());
+
+    if report.checked == 1 && report.agreed == 1 && report.disagreements.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Expected agreement, got {:?}", report))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_flag_transfer_missed_by_instructions() -> Result<(), String> {
+    let txn = get_txn(vec![]);
+    let processed = get_processed(&txn)?;
+    let mut report = ReconciliationReport::default();
+
+    for slot in 0..=MAX_RECONCILIATION_DISAGREEMENTS as u64 {
+        report.check(slot, &txn, &processed, &Config::default());
+    }
+
+    // Only the most recent disagreements are kept
+    match report.disagreements.front() {
+        Some(disagreement)
+            if disagreement.slot == 1
+                && disagreement.signature == "Signature"
+                && report.disagreed == MAX_RECONCILIATION_DISAGREEMENTS as u64 + 1
+                && report.disagreements.len() == MAX_RECONCILIATION_DISAGREEMENTS =>
+        {
+            let expected = [(DESTINATION, 1_470_000), (SOURCE, -1_470_000)].map(|(account, balance_delta)| {
+                Discrepancy {
+                    account: account.to_string(),
+                    mint: USDC_MINT.to_string(),
+                    balance_delta,
+                    instruction_delta: 0,
+                }
+            });
+
+            if disagreement.discrepancies == expected {
+                Ok(())
+            } else {
+                Err(format!("Wrong discrepancies {:?}", disagreement.discrepancies))
+            }
+        }
+        other => Err(format!("Wrong disagreements {:?} in {:?}", other, report)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_not_check_transaction_without_monitored_tokens() -> Result<(), String> {
+    let txn = EncodedTransactionWithStatusMeta {
+        meta: Some(get_ui_txn_status_meta(false)),
+        ..get_txn(vec![])
+    };
+
+    match reconcile(&txn, &ProcessedTransaction::default(), &Config::default()) {
+        None => Ok(()),
+        Some(discrepancies) => Err(format!("Expected nothing to check, got {:?}", discrepancies)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_find_accounts_loaded_from_lookup_tables() -> Result<(), String> {
+    let config = Config::default();
+    // The compiled message lists only its own keys, so the destination is only found among the loaded addresses
+    let txn = get_v0_txn(false);
+    let processed = process_transaction(&txn, &config).map_err(|err| err.to_string())?;
+    let got: Vec<(String, i128)> = get_balance_deltas(&txn, &config)
+        .into_iter()
+        .map(|balance_delta| (balance_delta.account, balance_delta.delta))
+        .collect();
+
+    match reconcile(&txn, &processed, &config) {
+        Some(discrepancies)
+            if discrepancies.is_empty()
+                && got == [(V0_SOURCE.to_string(), -1_250_000), (V0_DESTINATION.to_string(), 1_250_000)] =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Wrong reconciliation {:?} of balance deltas {:?}", other, got)),
+    }
+}
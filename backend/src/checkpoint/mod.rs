@@ -0,0 +1,53 @@
+use log::warn;
+use std::{fs, io, path::Path};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// The checkpoint file holds the number of the last slot that was fully processed
+pub fn read_checkpoint(path: &Path) -> Option<u64> {
+    let contents = fs::read_to_string(path).ok()?;
+
+    match contents.trim().parse::<u64>() {
+        Ok(slot) => Some(slot),
+        Err(_) => {
+            warn!("Ignoring checkpoint file {}: invalid contents '{}'", path.display(), contents.trim());
+            None
+        }
+    }
+}
+
+pub fn write_checkpoint(path: &Path, slot: u64) -> io::Result<()> {
+    write_atomically(path, slot.to_string().as_bytes())
+}
+
+// Write to a temporary file first, then rename it, so a crash part way through cannot leave the file truncated
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+// The slot to record once every slot up to and including this one has been processed.  A slot whose block could not be
+// fetched (from first_slot onwards, as any earlier dead letter is not the monitor's) has not been accounted for, so the
+// checkpoint stays just before the oldest such slot, and a restart fetches it again.  None if that is slot 0
+pub fn get_checkpoint(slot: u64, first_slot: u64, failed_slots: impl Iterator<Item = u64>) -> Option<u64> {
+    match failed_slots.filter(|failed_slot| (first_slot..=slot).contains(failed_slot)).min() {
+        Some(failed_slot) => failed_slot.checked_sub(1),
+        None => Some(slot),
+    }
+}
+
+// Resume from the slot after the checkpoint, unless that would leave us more than max_catch_up slots behind the tip
+pub fn get_start_slot(checkpoint: u64, tip: u64, max_catch_up: u64) -> u64 {
+    let next_slot = checkpoint + 1;
+
+    if tip.saturating_sub(next_slot) > max_catch_up {
+        tip - max_catch_up
+    } else {
+        next_slot
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,73 @@
+use crate::checkpoint::{get_checkpoint, get_start_slot, read_checkpoint, write_checkpoint};
+
+use std::{env, fs, path::PathBuf};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn get_checkpoint_path(test_name: &str) -> PathBuf {
+    env::temp_dir().join(format!("solana_usdc_monitor_{}_{}.checkpoint", test_name, std::process::id()))
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_read_back_written_checkpoint() -> Result<(), String> {
+    let path = get_checkpoint_path("read_back");
+
+    write_checkpoint(&path, 321659973).map_err(|e| e.to_string())?;
+    write_checkpoint(&path, 321659976).map_err(|e| e.to_string())?;
+    let checkpoint = read_checkpoint(&path);
+    let _ = fs::remove_file(&path);
+
+    match checkpoint {
+        Some(321659976) => Ok(()),
+        other => Err(format!("Expected checkpoint 321659976, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_ignore_missing_or_invalid_checkpoint() -> Result<(), String> {
+    let path = get_checkpoint_path("invalid");
+    let missing = read_checkpoint(&path);
+
+    fs::write(&path, "not a slot").map_err(|e| e.to_string())?;
+    let invalid = read_checkpoint(&path);
+    let _ = fs::remove_file(&path);
+
+    match (missing, invalid) {
+        (None, None) => Ok(()),
+        other => Err(format!("Expected no checkpoint, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_resume_after_checkpoint() -> Result<(), String> {
+    match get_start_slot(1000, 1500, 1000) {
+        1001 => Ok(()),
+        slot => Err(format!("Expected to resume from slot 1001, got {}", slot)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_limit_catch_up_distance() -> Result<(), String> {
+    match get_start_slot(1000, 5000, 1000) {
+        4000 => Ok(()),
+        slot => Err(format!("Expected to resume from slot 4000, got {}", slot)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_not_move_checkpoint_past_failed_slot() -> Result<(), String> {
+    // Slot 990 was dead-lettered by a backfill, before the monitor started at slot 1000
+    match (
+        get_checkpoint(1005, 1000, [990, 1003, 1004].into_iter()),
+        get_checkpoint(1005, 1000, [1000].into_iter()),
+        get_checkpoint(1005, 1000, [990].into_iter()),
+        get_checkpoint(1002, 1000, [1003].into_iter()),
+    ) {
+        (Some(1002), Some(999), Some(1005), Some(1002)) => Ok(()),
+        other => Err(format!("Wrong checkpoints {:?}", other)),
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{
+    DEFAULT_BACKFILL_CHUNK_SLOTS, DEFAULT_BACKFILL_PATH, DEFAULT_CHECKPOINT_PATH, DEFAULT_MAX_CATCH_UP_SLOTS,
+    DEFAULT_MAX_CONCURRENT_SLOTS, DEFAULT_RETENTION_MAX_TRANSFERS, DEFAULT_RETRY_BASE_DELAY, DEFAULT_RETRY_MAX_ATTEMPTS,
+    DEFAULT_RETRY_MAX_DELAY, DEFAULT_SOLANA_RPC_URL, PYUSD_MINT, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID, USDC_MINT,
+    USDT_MINT,
+};
+
+use log::error;
+use std::{env, path::PathBuf, str::FromStr, time::Duration};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonitoredMint {
+    pub symbol: String,
+    pub mint: String,
+    // Used only when neither the instruction nor the token balances state the number of decimals
+    pub decimals: u8,
+}
+
+// How often, and how patiently, a failed getBlock request is retried
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    // Includes the first attempt
+    pub max_attempts: u32,
+    // Delay before the first retry.  This doubles on each subsequent retry, up to max_delay
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+// How much of the transfer history is kept in memory.  A limit of None means unlimited
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RetentionPolicy {
+    pub max_slots: Option<usize>,
+    pub max_transfers: Option<usize>,
+    // Measured from the time at which the slot was processed
+    pub max_age: Option<Duration>,
+}
+
+// Runtime configuration.  Each value can be overridden by an environment variable
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub solana_rpc_url: String,
+    // Transfers are only recognised when performed by one of these programs
+    pub token_program_ids: Vec<String>,
+    // and only when they move one of these tokens
+    pub mints: Vec<MonitoredMint>,
+    // Maximum number of getBlock requests in flight at any one time
+    pub max_concurrent_slots: usize,
+    pub retry: RetryPolicy,
+    // File holding the last fully processed slot
+    pub checkpoint_path: PathBuf,
+    // On restart, never resume from further behind the tip than this
+    pub max_catch_up_slots: u64,
+    // File holding the progress of the current, or most recent, backfill
+    pub backfill_path: PathBuf,
+    // Number of slots requested from getBlocks at a time when backfilling
+    pub backfill_chunk_slots: u64,
+    // SQLite database in which detected transfers are stored.  Without one, they are held only in memory
+    pub database_path: Option<PathBuf>,
+    pub retention: RetentionPolicy,
+    // File to which slots evicted from memory are appended.  Without one, they are discarded
+    pub eviction_path: Option<PathBuf>,
+    // Whether a signature that is not in the store is looked up on the RPC node
+    pub on_demand_lookup: bool,
+    // Whether owners missing from a transaction's token balances are looked up on the RPC node
+    pub owner_lookup: bool,
+    // Whether failed transactions that attempted to transfer the monitored tokens are kept, rather than discarded
+    pub record_failed_transactions: bool,
+}
+
+fn monitored_mint(symbol: &str, mint: &str, decimals: u8) -> MonitoredMint {
+    MonitoredMint {
+        symbol: symbol.to_string(),
+        mint: mint.to_string(),
+        decimals,
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            solana_rpc_url: DEFAULT_SOLANA_RPC_URL.to_string(),
+            token_program_ids: vec![TOKEN_PROGRAM_ID.to_string(), TOKEN_2022_PROGRAM_ID.to_string()],
+            mints: vec![
+                monitored_mint("USDC", USDC_MINT, 6),
+                monitored_mint("USDT", USDT_MINT, 6),
+                monitored_mint("PYUSD", PYUSD_MINT, 6),
+            ],
+            max_concurrent_slots: DEFAULT_MAX_CONCURRENT_SLOTS,
+            retry: RetryPolicy {
+                max_attempts: DEFAULT_RETRY_MAX_ATTEMPTS,
+                base_delay: DEFAULT_RETRY_BASE_DELAY,
+                max_delay: DEFAULT_RETRY_MAX_DELAY,
+            },
+            checkpoint_path: PathBuf::from(DEFAULT_CHECKPOINT_PATH),
+            max_catch_up_slots: DEFAULT_MAX_CATCH_UP_SLOTS,
+            backfill_path: PathBuf::from(DEFAULT_BACKFILL_PATH),
+            backfill_chunk_slots: DEFAULT_BACKFILL_CHUNK_SLOTS,
+            database_path: None,
+            retention: RetentionPolicy {
+                max_transfers: Some(DEFAULT_RETENTION_MAX_TRANSFERS),
+                ..RetentionPolicy::default()
+            },
+            eviction_path: None,
+            on_demand_lookup: true,
+            owner_lookup: true,
+            record_failed_transactions: false,
+        }
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Split a comma separated list, ignoring any empty entries
+fn parse_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+        .collect()
+}
+
+// Each mint is described as SYMBOL:MINT:DECIMALS.  Malformed entries are reported and skipped
+fn parse_mints(value: &str) -> Vec<MonitoredMint> {
+    parse_list(value)
+        .iter()
+        .filter_map(|entry| match entry.split(':').collect::<Vec<&str>>().as_slice() {
+            [symbol, mint, decimals] if !symbol.is_empty() && !mint.is_empty() => match decimals.parse::<u8>() {
+                Ok(decimals) => Some(monitored_mint(symbol, mint, decimals)),
+                Err(_) => {
+                    error!("Ignoring monitored mint '{}': invalid decimals", entry);
+                    None
+                }
+            },
+            _ => {
+                error!("Ignoring monitored mint '{}': expected SYMBOL:MINT:DECIMALS", entry);
+                None
+            }
+        })
+        .collect()
+}
+
+// Read a numeric value, falling back to the default if the variable is either missing or invalid
+fn parse_env<T: FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value.trim().parse::<T>().unwrap_or_else(|_| {
+            error!("Ignoring {}: invalid value '{}'", name, value);
+            default
+        }),
+        Err(_) => default,
+    }
+}
+
+// As parse_env, except that a value of zero means there is no limit
+fn parse_limit<T: FromStr + Default + PartialEq + Copy>(name: &str, default: Option<T>) -> Option<T> {
+    let limit = parse_env(name, default.unwrap_or_default());
+
+    (limit != T::default()).then_some(limit)
+}
+
+impl Config {
+    pub fn from_env() -> Self {
+        let default = Config::default();
+
+        Config {
+            solana_rpc_url: env::var("SOLANA_RPC_URL").unwrap_or(default.solana_rpc_url),
+            token_program_ids: env::var("TOKEN_PROGRAM_IDS")
+                .map(|ids| parse_list(&ids))
+                .unwrap_or(default.token_program_ids),
+            mints: env::var("MONITORED_MINTS")
+                .map(|mints| parse_mints(&mints))
+                .unwrap_or(default.mints),
+            // A value of zero would mean no block is ever fetched
+            max_concurrent_slots: parse_env("MAX_CONCURRENT_SLOTS", default.max_concurrent_slots).max(1),
+            retry: RetryPolicy {
+                max_attempts: parse_env("RETRY_MAX_ATTEMPTS", default.retry.max_attempts).max(1),
+                base_delay: Duration::from_millis(parse_env(
+                    "RETRY_BASE_DELAY_MILLIS",
+                    default.retry.base_delay.as_millis() as u64,
+                )),
+                max_delay: Duration::from_millis(parse_env(
+                    "RETRY_MAX_DELAY_MILLIS",
+                    default.retry.max_delay.as_millis() as u64,
+                )),
+            },
+            checkpoint_path: env::var("CHECKPOINT_PATH")
+                .map(PathBuf::from)
+                .unwrap_or(default.checkpoint_path),
+            max_catch_up_slots: parse_env("MAX_CATCH_UP_SLOTS", default.max_catch_up_slots),
+            backfill_path: env::var("BACKFILL_PATH")
+                .map(PathBuf::from)
+                .unwrap_or(default.backfill_path),
+            backfill_chunk_slots: parse_env("BACKFILL_CHUNK_SLOTS", default.backfill_chunk_slots).max(1),
+            database_path: env::var("DATABASE_PATH").ok().map(PathBuf::from).or(default.database_path),
+            retention: RetentionPolicy {
+                max_slots: parse_limit("RETENTION_MAX_SLOTS", default.retention.max_slots),
+                max_transfers: parse_limit("RETENTION_MAX_TRANSFERS", default.retention.max_transfers),
+                max_age: parse_limit("RETENTION_MAX_AGE_SECS", default.retention.max_age.map(|age| age.as_secs()))
+                    .map(Duration::from_secs),
+            },
+            eviction_path: env::var("EVICTION_PATH").ok().map(PathBuf::from).or(default.eviction_path),
+            on_demand_lookup: parse_env("ON_DEMAND_LOOKUP", default.on_demand_lookup),
+            owner_lookup: parse_env("OWNER_LOOKUP", default.owner_lookup),
+            record_failed_transactions: parse_env("RECORD_FAILED_TRANSACTIONS", default.record_failed_transactions),
+        }
+    }
+
+    pub fn is_token_program(&self, program_id: &str) -> bool {
+        self.token_program_ids.iter().any(|id| id == program_id)
+    }
+
+    pub fn get_mint(&self, mint: &str) -> Option<&MonitoredMint> {
+        self.mints.iter().find(|monitored| monitored.mint == mint)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,74 @@
+use crate::{
+    config::{parse_limit, parse_list, parse_mints, Config, MonitoredMint},
+    USDC_MINT,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_ignore_empty_list_entries() -> Result<(), String> {
+    let list = parse_list(" a, ,b,");
+
+    if list == vec!["a".to_string(), "b".to_string()] {
+        Ok(())
+    } else {
+        Err(format!("Wrong list entries {:?}", list))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_parse_monitored_mints() -> Result<(), String> {
+    let mints = parse_mints("USDC:EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v:6, EURC:HzwqbKZw8HxMN6bF2yFZNrht3c2iXXzpKcFu7uBEDKtr:6");
+
+    match mints.as_slice() {
+        [usdc, eurc] if usdc.symbol == "USDC" && usdc.mint == USDC_MINT && eurc.symbol == "EURC" => Ok(()),
+        _ => Err(format!("Wrong monitored mints {:?}", mints)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_skip_malformed_monitored_mints() -> Result<(), String> {
+    let mints = parse_mints("USDC:EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v,BAD::6,WORSE:mint:lots,OK:mint:2");
+
+    if mints
+        == vec![MonitoredMint {
+            symbol: "OK".to_string(),
+            mint: "mint".to_string(),
+            decimals: 2,
+        }]
+    {
+        Ok(())
+    } else {
+        Err(format!("Should have skipped malformed entries, got {:?}", mints))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_find_monitored_mint() -> Result<(), String> {
+    let config = Config::default();
+
+    match (config.get_mint(USDC_MINT), config.get_mint("unknown mint")) {
+        (Some(usdc), None) if usdc.symbol == "USDC" => Ok(()),
+        other => Err(format!("Wrong monitored mint lookup {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_treat_zero_limit_as_unlimited() -> Result<(), String> {
+    let name = "SOLANA_USDC_MONITOR_TEST_LIMIT";
+    let missing = parse_limit(name, Some(100_usize));
+
+    std::env::set_var(name, "0");
+    let zero = parse_limit(name, Some(100_usize));
+    std::env::set_var(name, "25");
+    let set = parse_limit(name, None::<usize>);
+    std::env::remove_var(name);
+
+    match (missing, zero, set) {
+        (Some(100), None, Some(25)) => Ok(()),
+        other => Err(format!("Wrong limits {:?}", other)),
+    }
+}
@@ -0,0 +1,885 @@
+use crate::{
+    account_event::{AccountEventsQuery, AddressEvent},
+    address::FlowTotals,
+    failed::FailedTransactionsQuery,
+    page,
+    query::{TransactionRecordsPage, TransactionsPage, TransactionsQuery},
+    solana::{
+        AccountEvent, FailedTransaction, SignedUsdcTransactionsBySlot, TransactionStatus, TransferRecord,
+        UsdcTransaction, UsdcTransfer,
+    },
+    supply::{SupplyQuery, SupplyTotals},
+};
+
+use log::error;
+use rusqlite::{params, types::Type, Connection, OpenFlags, Row, ToSql};
+use solana_transaction::versioned::{Legacy, TransactionVersion};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Only slots containing at least one transfer (or other event) are stored.  Deleting a slot removes its transactions,
+// their signatures and their transfers
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS slots (
+        slot INTEGER PRIMARY KEY
+    );
+    CREATE TABLE IF NOT EXISTS transfers (
+        id INTEGER PRIMARY KEY,
+        slot INTEGER NOT NULL REFERENCES slots (slot) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        mint TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        source TEXT NOT NULL,
+        destination TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        decimals INTEGER NOT NULL,
+        ui_amount TEXT NOT NULL,
+        fee INTEGER,
+        instruction_index INTEGER NOT NULL,
+        stack_height INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS signatures (
+        transfer_id INTEGER NOT NULL REFERENCES transfers (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        PRIMARY KEY (transfer_id, position)
+    );
+    CREATE INDEX IF NOT EXISTS transfers_slot ON transfers (slot, position);
+    CREATE INDEX IF NOT EXISTS transfers_source ON transfers (source);
+    CREATE INDEX IF NOT EXISTS transfers_destination ON transfers (destination);
+    CREATE INDEX IF NOT EXISTS signatures_signature ON signatures (signature);
+";
+
+// Changes to the schema since it was first released.  A database records (as its user_version) how many of these it
+// has had applied, and any others are applied when it is opened
+const MIGRATIONS: &[&str] = &["
+    ALTER TABLE transfers ADD COLUMN source_owner TEXT;
+    ALTER TABLE transfers ADD COLUMN destination_owner TEXT;
+    CREATE INDEX transfers_source_owner ON transfers (source_owner);
+    CREATE INDEX transfers_destination_owner ON transfers (destination_owner);
+", "
+    ALTER TABLE transfers ADD COLUMN block_time INTEGER;
+    ALTER TABLE transfers ADD COLUMN fee_payer TEXT NOT NULL DEFAULT '';
+    ALTER TABLE transfers ADD COLUMN transaction_fee INTEGER;
+    ALTER TABLE transfers ADD COLUMN compute_units_consumed INTEGER;
+", "
+    CREATE TABLE supply_changes (
+        id INTEGER PRIMARY KEY,
+        slot INTEGER NOT NULL REFERENCES slots (slot) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        type TEXT NOT NULL,
+        signature TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        account TEXT NOT NULL,
+        authority TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        decimals INTEGER NOT NULL,
+        ui_amount TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        stack_height INTEGER NOT NULL,
+        block_time INTEGER
+    );
+    CREATE INDEX supply_changes_slot ON supply_changes (slot, position);
+    CREATE INDEX supply_changes_block_time ON supply_changes (block_time);
+", "
+    CREATE TABLE account_events (
+        id INTEGER PRIMARY KEY,
+        slot INTEGER NOT NULL REFERENCES slots (slot) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        type TEXT NOT NULL,
+        account TEXT NOT NULL,
+        signature TEXT NOT NULL,
+        mint TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        instruction_index INTEGER NOT NULL,
+        stack_height INTEGER NOT NULL,
+        block_time INTEGER,
+        -- The fields specific to the type of event, as JSON
+        event TEXT NOT NULL
+    );
+    CREATE INDEX account_events_account ON account_events (account, slot, position);
+", "
+    CREATE TABLE failed_transactions (
+        id INTEGER PRIMARY KEY,
+        slot INTEGER NOT NULL REFERENCES slots (slot) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        token_error TEXT,
+        -- The whole transaction, including the transfers it attempted, as JSON
+        record TEXT NOT NULL
+    );
+    CREATE INDEX failed_transactions_slot ON failed_transactions (slot, position);
+", "
+    ALTER TABLE transfers ADD COLUMN version TEXT;
+", "
+    CREATE TABLE transactions (
+        id INTEGER PRIMARY KEY,
+        slot INTEGER NOT NULL REFERENCES slots (slot) ON DELETE CASCADE,
+        -- Index of the transaction within its block
+        position INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        status TEXT NOT NULL,
+        block_time INTEGER,
+        fee_payer TEXT NOT NULL,
+        transaction_fee INTEGER,
+        compute_units_consumed INTEGER,
+        version TEXT
+    );
+    CREATE INDEX transactions_slot ON transactions (slot, position);
+    -- Only successful transactions were stored, each as a run of transfers sharing their signatures.  The position of
+    -- a transaction within its block was not stored, so it is numbered by the position of its first transfer instead
+    INSERT INTO transactions (slot, position, signature, status, block_time, fee_payer, transaction_fee,
+                              compute_units_consumed, version)
+    SELECT transfers.slot, MIN(transfers.position), signatures.signature, 'success', block_time, fee_payer,
+           transaction_fee, compute_units_consumed, version
+    FROM transfers JOIN signatures ON signatures.transfer_id = transfers.id AND signatures.position = 0
+    GROUP BY transfers.slot, signatures.signature;
+    ALTER TABLE transfers ADD COLUMN transaction_id INTEGER REFERENCES transactions (id) ON DELETE CASCADE;
+    UPDATE transfers SET transaction_id = (
+        SELECT transactions.id
+        FROM transactions JOIN signatures ON signatures.signature = transactions.signature
+        WHERE transactions.slot = transfers.slot AND signatures.transfer_id = transfers.id AND signatures.position = 0
+    );
+    CREATE INDEX transfers_transaction ON transfers (transaction_id);
+    -- Signatures belong to the transaction, rather than to each of its transfers
+    CREATE TABLE transaction_signatures (
+        transaction_id INTEGER NOT NULL REFERENCES transactions (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        signature TEXT NOT NULL,
+        PRIMARY KEY (transaction_id, position)
+    );
+    INSERT INTO transaction_signatures (transaction_id, position, signature)
+    SELECT transfers.transaction_id, signatures.position, MIN(signatures.signature)
+    FROM signatures JOIN transfers ON transfers.id = signatures.transfer_id
+    GROUP BY transfers.transaction_id, signatures.position;
+    DROP TABLE signatures;
+    ALTER TABLE transaction_signatures RENAME TO signatures;
+    CREATE INDEX signatures_signature ON signatures (signature);
+    ALTER TABLE transfers DROP COLUMN block_time;
+    ALTER TABLE transfers DROP COLUMN fee_payer;
+    ALTER TABLE transfers DROP COLUMN transaction_fee;
+    ALTER TABLE transfers DROP COLUMN compute_units_consumed;
+    ALTER TABLE transfers DROP COLUMN version;
+", "
+    -- The transfers a failed transaction attempted, which were held only within its JSON record.  The record now holds
+    -- the rest of the transaction.  A transfer whose amount cannot be stored is left out, as for successful transfers
+    CREATE TABLE failed_transfers (
+        failed_transaction_id INTEGER NOT NULL REFERENCES failed_transactions (id) ON DELETE CASCADE,
+        -- Index of the transfer within the transaction
+        position INTEGER NOT NULL,
+        mint TEXT NOT NULL,
+        symbol TEXT NOT NULL,
+        source TEXT NOT NULL,
+        destination TEXT NOT NULL,
+        amount INTEGER NOT NULL,
+        decimals INTEGER NOT NULL,
+        ui_amount TEXT NOT NULL,
+        fee INTEGER,
+        instruction_index INTEGER NOT NULL,
+        stack_height INTEGER NOT NULL,
+        source_owner TEXT,
+        destination_owner TEXT,
+        PRIMARY KEY (failed_transaction_id, position)
+    );
+    CREATE INDEX failed_transfers_source ON failed_transfers (source);
+    CREATE INDEX failed_transfers_destination ON failed_transfers (destination);
+    INSERT INTO failed_transfers (failed_transaction_id, position, mint, symbol, source, destination, amount, decimals,
+                                  ui_amount, fee, instruction_index, stack_height, source_owner, destination_owner)
+    SELECT failed_transactions.id, transfer.key, transfer.value ->> 'mint', transfer.value ->> 'symbol',
+           transfer.value ->> 'from', transfer.value ->> 'to', transfer.value ->> 'amount',
+           transfer.value ->> 'decimals', transfer.value ->> 'ui_amount', transfer.value ->> 'fee',
+           transfer.value ->> 'instruction_index', transfer.value ->> 'stack_height', transfer.value ->> 'from_owner',
+           transfer.value ->> 'to_owner'
+    FROM failed_transactions, json_each(failed_transactions.record, '$.transfers') AS transfer
+    WHERE json_type(transfer.value, '$.amount') = 'integer';
+    UPDATE failed_transactions SET record = json_set(record, '$.transfers', json('[]'));
+"];
+
+// A transaction version is stored as either "legacy" or its number
+fn get_version_text(version: &TransactionVersion) -> String {
+    match version {
+        TransactionVersion::Legacy(Legacy::Legacy) => "legacy".to_string(),
+        TransactionVersion::Number(number) => number.to_string(),
+    }
+}
+
+fn get_version(text: &str) -> Option<TransactionVersion> {
+    match text {
+        "legacy" => Some(TransactionVersion::LEGACY),
+        number => number.parse::<u8>().ok().map(TransactionVersion::Number),
+    }
+}
+
+fn get_status(text: &str) -> TransactionStatus {
+    match text {
+        "failed" => TransactionStatus::Failed,
+        _ => TransactionStatus::Success,
+    }
+}
+
+// The transfer held in the columns mint, symbol, source, destination, amount, decimals, ui_amount, fee,
+// instruction_index, stack_height, source_owner and destination_owner, in that order, starting at this one
+fn get_transfer(row: &Row, first: usize) -> rusqlite::Result<TransferRecord> {
+    Ok(TransferRecord {
+        mint: row.get(first)?,
+        symbol: row.get(first + 1)?,
+        txn: UsdcTransfer {
+            from: row.get(first + 2)?,
+            to: row.get(first + 3)?,
+            from_owner: row.get(first + 10)?,
+            to_owner: row.get(first + 11)?,
+            amount: row.get(first + 4)?,
+            decimals: row.get(first + 5)?,
+            ui_amount: row.get(first + 6)?,
+            fee: row.get(first + 7)?,
+            instruction_index: row.get(first + 8)?,
+            stack_height: row.get(first + 9)?,
+        },
+    })
+}
+
+// SUM of a column of amounts would overflow SQLite's 64 bit integers, so the upper and lower 32 bits of the amounts are
+// summed separately instead.  Neither sum can overflow before 2^31 amounts have been added up
+fn get_split_sum(amount: &str) -> String {
+    format!("SUM(({amount}) >> 32), SUM(({amount}) & 4294967295)")
+}
+
+// The total of the two sums selected by get_split_sum, starting at this column
+fn read_split_sum(row: &Row, first: usize) -> rusqlite::Result<u128> {
+    let upper: u64 = row.get(first)?;
+    let lower: u64 = row.get(first + 1)?;
+
+    Ok(((upper as u128) << 32) + lower as u128)
+}
+
+// A filter of a query: the condition it adds, along with the named parameter holding its value, which is None if the
+// filter is not set
+type Filter<'a> = (&'static str, &'static str, Option<&'a dyn ToSql>);
+
+// Add the condition and parameter of every filter that is set
+fn add_filters<'a>(
+    conditions: &mut Vec<&'static str>,
+    params: &mut Vec<(&'static str, &'a dyn ToSql)>,
+    filters: impl IntoIterator<Item = Filter<'a>>,
+) {
+    for (condition, name, value) in filters {
+        if let Some(value) = value {
+            conditions.push(condition);
+            params.push((name, value));
+        }
+    }
+}
+
+// SQLite integers are signed, so an amount larger than i64::MAX cannot be stored.  Rather than reject the whole slot,
+// leave out only the row holding it
+fn skip_unstorable<T>(slot: u64, row: &str, result: rusqlite::Result<T>) -> rusqlite::Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(rusqlite::Error::ToSqlConversionFailure(e)) => {
+            error!("Slot {}: not storing {}: {}", slot, row, e);
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Embedded SQLite store of detected transfers.  All writes go through a single connection, whereas each read opens
+// its own, so readers never wait for the writer (or each other) to release a lock
+pub struct Database {
+    path: PathBuf,
+    writer: Mutex<Connection>,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let mut writer = Connection::open(path)?;
+
+        // Write-ahead logging allows readers to proceed while a slot is being written
+        writer.pragma_update(None, "journal_mode", "WAL")?;
+        writer.pragma_update(None, "foreign_keys", "ON")?;
+        writer.execute_batch(SCHEMA)?;
+        Database::migrate(&mut writer)?;
+
+        Ok(Database {
+            path: path.to_path_buf(),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    fn migrate(writer: &mut Connection) -> rusqlite::Result<()> {
+        let version: usize = writer.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        for (idx, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let db_txn = writer.transaction()?;
+
+            db_txn.execute_batch(migration)?;
+            db_txn.pragma_update(None, "user_version", idx + 1)?;
+            db_txn.commit()?;
+        }
+
+        Ok(())
+    }
+
+    fn reader(&self) -> rusqlite::Result<Connection> {
+        Connection::open_with_flags(
+            &self.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )
+    }
+
+    // Replace anything previously stored for this slot.  A transfer or supply change of more than i64::MAX is left out
+    pub fn write_slot(&self, txns_by_slot: &SignedUsdcTransactionsBySlot) -> rusqlite::Result<()> {
+        let mut writer = self.writer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let db_txn = writer.transaction()?;
+
+        db_txn.execute("DELETE FROM slots WHERE slot = ?1", params![txns_by_slot.slot])?;
+        db_txn.execute("INSERT INTO slots (slot) VALUES (?1)", params![txns_by_slot.slot])?;
+
+        {
+            let mut insert_transaction = db_txn.prepare(
+                "INSERT INTO transactions (slot, position, signature, status, block_time, fee_payer, transaction_fee,
+                                           compute_units_consumed, version)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            )?;
+            let mut insert_signature =
+                db_txn.prepare("INSERT INTO signatures (transaction_id, position, signature) VALUES (?1, ?2, ?3)")?;
+            let mut insert_transfer = db_txn.prepare(
+                "INSERT INTO transfers (slot, position, transaction_id, mint, symbol, source, destination, amount,
+                                        decimals, ui_amount, fee, instruction_index, stack_height, source_owner,
+                                        destination_owner)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+            )?;
+            // Transfers are numbered across the whole slot, in execution order
+            let mut transfer_position = 0;
+
+            for transaction in txns_by_slot.transactions.iter() {
+                let transaction_id = insert_transaction.insert(params![
+                    transaction.slot,
+                    transaction.position,
+                    transaction.signature,
+                    transaction.status.as_str(),
+                    transaction.block_time,
+                    transaction.fee_payer,
+                    transaction.transaction_fee,
+                    transaction.compute_units_consumed,
+                    transaction.version.as_ref().map(get_version_text),
+                ])?;
+
+                for (position, signature) in transaction.signatures.iter().enumerate() {
+                    insert_signature.execute(params![transaction_id, position, signature])?;
+                }
+
+                for transfer in transaction.transfers.iter() {
+                    let txn = &transfer.txn;
+                    let inserted = insert_transfer.execute(params![
+                        txns_by_slot.slot,
+                        transfer_position,
+                        transaction_id,
+                        transfer.mint,
+                        transfer.symbol,
+                        txn.from,
+                        txn.to,
+                        txn.amount,
+                        txn.decimals,
+                        txn.ui_amount,
+                        txn.fee,
+                        txn.instruction_index,
+                        txn.stack_height,
+                        txn.from_owner,
+                        txn.to_owner,
+                    ]);
+
+                    skip_unstorable(txns_by_slot.slot, &transfer.to_string(), inserted)?;
+                    transfer_position += 1;
+                }
+            }
+        }
+
+        {
+            let mut insert_supply_change = db_txn.prepare(
+                "INSERT INTO supply_changes (slot, position, type, signature, mint, symbol, account, authority, amount,
+                                             decimals, ui_amount, instruction_index, stack_height, block_time)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            )?;
+
+            for (position, supply_change) in txns_by_slot.supply_changes.iter().enumerate() {
+                let inserted = insert_supply_change.execute(params![
+                    txns_by_slot.slot,
+                    position,
+                    supply_change.kind.as_str(),
+                    supply_change.signature,
+                    supply_change.mint,
+                    supply_change.symbol,
+                    supply_change.account,
+                    supply_change.authority,
+                    supply_change.amount,
+                    supply_change.decimals,
+                    supply_change.ui_amount,
+                    supply_change.instruction_index,
+                    supply_change.stack_height,
+                    supply_change.block_time,
+                ]);
+
+                let row = format!("{} of {}", supply_change.kind.as_str(), supply_change.signature);
+
+                skip_unstorable(txns_by_slot.slot, &row, inserted)?;
+            }
+        }
+
+        {
+            let mut insert_account_event = db_txn.prepare(
+                "INSERT INTO account_events (slot, position, type, account, signature, mint, symbol, instruction_index,
+                                             stack_height, block_time, event)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            )?;
+
+            for (position, account_event) in txns_by_slot.account_events.iter().enumerate() {
+                let event = serde_json::to_string(&account_event.event)
+                    .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+
+                insert_account_event.execute(params![
+                    txns_by_slot.slot,
+                    position,
+                    account_event.event.get_type(),
+                    account_event.event.get_account(),
+                    account_event.signature,
+                    account_event.mint,
+                    account_event.symbol,
+                    account_event.instruction_index,
+                    account_event.stack_height,
+                    account_event.block_time,
+                    event,
+                ])?;
+            }
+        }
+
+        {
+            let mut insert_failed_transaction = db_txn.prepare(
+                "INSERT INTO failed_transactions (slot, position, signature, token_error, record)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            let mut insert_failed_transfer = db_txn.prepare(
+                "INSERT INTO failed_transfers (failed_transaction_id, position, mint, symbol, source, destination,
+                                               amount, decimals, ui_amount, fee, instruction_index, stack_height,
+                                               source_owner, destination_owner)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            )?;
+
+            for (position, failed_txn) in txns_by_slot.failed_transactions.iter().enumerate() {
+                // The transfers are stored in their own table
+                let record = serde_json::to_string(&FailedTransaction {
+                    transaction: UsdcTransaction {
+                        transfers: Vec::new(),
+                        ..failed_txn.transaction.clone()
+                    },
+                    ..failed_txn.clone()
+                })
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+                let failed_transaction_id = insert_failed_transaction.insert(params![
+                    txns_by_slot.slot,
+                    position,
+                    failed_txn.transaction.signature,
+                    failed_txn.token_error,
+                    record,
+                ])?;
+
+                for (transfer_position, transfer) in failed_txn.transaction.transfers.iter().enumerate() {
+                    let txn = &transfer.txn;
+                    let inserted = insert_failed_transfer.execute(params![
+                        failed_transaction_id,
+                        transfer_position,
+                        transfer.mint,
+                        transfer.symbol,
+                        txn.from,
+                        txn.to,
+                        txn.amount,
+                        txn.decimals,
+                        txn.ui_amount,
+                        txn.fee,
+                        txn.instruction_index,
+                        txn.stack_height,
+                        txn.from_owner,
+                        txn.to_owner,
+                    ]);
+
+                    skip_unstorable(txns_by_slot.slot, &transfer.to_string(), inserted)?;
+                }
+            }
+        }
+
+        db_txn.commit()
+    }
+
+    // Only the columns of the transfers table are referenced, so the same conditions select both the page of slots (or
+    // of transactions) and the transfers within them
+    fn get_conditions(query: &TransactionsQuery) -> (String, Vec<(&'static str, &dyn ToSql)>) {
+        let filters: [Filter; 8] = [
+            ("symbol = :symbol COLLATE NOCASE", ":symbol", query.symbol.as_ref().map(|v| v as &dyn ToSql)),
+            ("transfers.slot >= :from_slot", ":from_slot", query.from_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("transfers.slot <= :to_slot", ":to_slot", query.to_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("transfers.slot > :after_slot", ":after_slot", query.after_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("amount >= :min_amount", ":min_amount", query.min_amount.as_ref().map(|v| v as &dyn ToSql)),
+            ("amount <= :max_amount", ":max_amount", query.max_amount.as_ref().map(|v| v as &dyn ToSql)),
+            (
+                "(source = :address OR destination = :address)",
+                ":address",
+                query.address.as_ref().map(|v| v as &dyn ToSql),
+            ),
+            (
+                "(source_owner = :owner OR destination_owner = :owner)",
+                ":owner",
+                query.owner.as_ref().map(|v| v as &dyn ToSql),
+            ),
+        ];
+        let mut conditions: Vec<&str> = vec!["1"];
+        let mut params: Vec<(&'static str, &dyn ToSql)> = Vec::new();
+
+        add_filters(&mut conditions, &mut params, filters);
+
+        (conditions.join(" AND "), params)
+    }
+
+    // Group the selected transfers, which must be ordered by slot and position, by transaction
+    fn read_transactions(
+        reader: &Connection,
+        condition: &str,
+        params: &[(&str, &dyn ToSql)],
+    ) -> rusqlite::Result<Vec<UsdcTransaction>> {
+        let mut select_transfers = reader.prepare(&format!(
+            "SELECT transactions.id, transactions.slot, transactions.position, signature, status, block_time, fee_payer,
+                    transaction_fee, compute_units_consumed, version, mint, symbol, source, destination, amount,
+                    decimals, ui_amount, fee, instruction_index, stack_height, source_owner, destination_owner
+             FROM transfers JOIN transactions ON transactions.id = transfers.transaction_id
+             WHERE {condition}
+             ORDER BY transfers.slot, transfers.position"
+        ))?;
+        let mut select_signatures =
+            reader.prepare("SELECT signature FROM signatures WHERE transaction_id = ?1 ORDER BY position")?;
+        let mut transactions: Vec<UsdcTransaction> = Vec::new();
+        let mut last_transaction_id: Option<i64> = None;
+        let mut rows = select_transfers.query(params)?;
+
+        while let Some(row) = rows.next()? {
+            let transaction_id: i64 = row.get(0)?;
+            let transfer = get_transfer(row, 10)?;
+
+            // The transfers of a transaction are stored together
+            match transactions.last_mut() {
+                Some(transaction) if last_transaction_id == Some(transaction_id) => {
+                    transaction.transfers.push(transfer)
+                }
+                _ => {
+                    let signatures = select_signatures
+                        .query_map(params![transaction_id], |row| row.get(0))?
+                        .collect::<rusqlite::Result<Vec<String>>>()?;
+
+                    transactions.push(UsdcTransaction {
+                        signature: row.get(3)?,
+                        signatures: signatures.into(),
+                        slot: row.get(1)?,
+                        position: row.get(2)?,
+                        block_time: row.get(5)?,
+                        status: get_status(&row.get::<_, String>(4)?),
+                        fee_payer: row.get(6)?,
+                        transaction_fee: row.get(7)?,
+                        compute_units_consumed: row.get(8)?,
+                        version: row.get::<_, Option<String>>(9)?.as_deref().and_then(get_version),
+                        transfers: vec![transfer],
+                    });
+                    last_transaction_id = Some(transaction_id);
+                }
+            }
+        }
+
+        Ok(transactions)
+    }
+
+    pub fn read_page(&self, query: &TransactionsQuery) -> rusqlite::Result<TransactionsPage> {
+        let (conditions, mut params) = Database::get_conditions(query);
+        let limit = page::get_fetch_limit(query.get_limit());
+        let mut slots: Vec<SignedUsdcTransactionsBySlot> = Vec::new();
+
+        params.push((":limit", &limit));
+
+        let transactions = Database::read_transactions(
+            &self.reader()?,
+            &format!(
+                "{conditions}
+                 AND transfers.slot IN (SELECT DISTINCT transfers.slot FROM transfers WHERE {conditions}
+                                        ORDER BY transfers.slot LIMIT :limit)"
+            ),
+            &params,
+        )?;
+
+        for transaction in transactions {
+            match slots.last_mut() {
+                Some(txns_by_slot) if txns_by_slot.slot == transaction.slot => {
+                    txns_by_slot.transactions.push(transaction)
+                }
+                _ => slots.push(SignedUsdcTransactionsBySlot {
+                    slot: transaction.slot,
+                    transactions: vec![transaction],
+                    ..SignedUsdcTransactionsBySlot::default()
+                }),
+            }
+        }
+
+        Ok(query.get_page(slots))
+    }
+
+    // As read_page, but limiting the number of transactions rather than of slots.  The cursor points at a transaction,
+    // so it is applied to the transactions rather than to their transfers.  The page of transactions is found by
+    // walking the matching transfers in order, which stops as soon as enough transactions have been seen
+    pub fn read_transaction_page(&self, query: &TransactionsQuery) -> rusqlite::Result<TransactionRecordsPage> {
+        let transfer_query = TransactionsQuery {
+            after_slot: None,
+            ..query.clone()
+        };
+        let (conditions, mut params) = Database::get_conditions(&transfer_query);
+        let limit = page::get_fetch_limit(query.get_limit());
+        let cursor = match (&query.after_slot, &query.after_position) {
+            (Some(after_slot), Some(after_position)) => {
+                params.extend([(":after_slot", after_slot as &dyn ToSql), (":after_position", after_position)]);
+                "transfers.slot >= :after_slot
+                 AND (transactions.slot, transactions.position) > (:after_slot, :after_position)"
+            }
+            (Some(after_slot), None) => {
+                params.push((":after_slot", after_slot));
+                "transfers.slot > :after_slot"
+            }
+            (None, _) => "1",
+        };
+
+        params.push((":limit", &limit));
+
+        // The transfers of a transaction are stored together, so they are in the same order as the transactions
+        let transactions = Database::read_transactions(
+            &self.reader()?,
+            &format!(
+                "{conditions}
+                 AND transactions.id IN (
+                     SELECT DISTINCT transfers.transaction_id
+                     FROM transfers JOIN transactions ON transactions.id = transfers.transaction_id
+                     WHERE {conditions} AND {cursor}
+                     ORDER BY transfers.slot, transfers.position
+                     LIMIT :limit
+                 )"
+            ),
+            &params,
+        )?;
+
+        Ok(query.get_records_page(transactions))
+    }
+
+    // The transaction with this signature, along with the transfers it performed.  A signature belongs to a single
+    // transaction
+    pub fn find_signature(&self, signature: &str) -> rusqlite::Result<Option<UsdcTransaction>> {
+        let transactions = Database::read_transactions(
+            &self.reader()?,
+            "transactions.id IN (SELECT transaction_id FROM signatures WHERE signature = :signature)",
+            &[(":signature", &signature)],
+        )?;
+
+        Ok(transactions.into_iter().next())
+    }
+
+    // Flows through the query's address (which must be set) of the transfers matching the query, by mint
+    pub fn read_flow_totals(&self, query: &TransactionsQuery) -> rusqlite::Result<Vec<FlowTotals>> {
+        let (conditions, params) = Database::get_conditions(query);
+        let reader = self.reader()?;
+        let mut select_totals = reader.prepare(&format!(
+            "SELECT mint, symbol, decimals, COUNT(*), {}, {}
+             FROM transfers
+             WHERE {conditions}
+             GROUP BY mint
+             ORDER BY mint",
+            get_split_sum("CASE WHEN destination = :address THEN amount ELSE 0 END"),
+            get_split_sum("CASE WHEN source = :address THEN amount ELSE 0 END"),
+        ))?;
+
+        let totals = select_totals
+            .query_map(params.as_slice(), |row| {
+                Ok(FlowTotals::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    read_split_sum(row, 4)?,
+                    read_split_sum(row, 6)?,
+                ))
+            })?
+            .collect();
+
+        totals
+    }
+
+    // Totals of the supply changes matching the query, by mint
+    pub fn read_supply_totals(&self, query: &SupplyQuery) -> rusqlite::Result<Vec<SupplyTotals>> {
+        let filters: [Filter; 5] = [
+            ("symbol = :symbol COLLATE NOCASE", ":symbol", query.symbol.as_ref().map(|v| v as &dyn ToSql)),
+            ("slot >= :from_slot", ":from_slot", query.from_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("slot <= :to_slot", ":to_slot", query.to_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("block_time >= :from_time", ":from_time", query.from_time.as_ref().map(|v| v as &dyn ToSql)),
+            ("block_time <= :to_time", ":to_time", query.to_time.as_ref().map(|v| v as &dyn ToSql)),
+        ];
+        let mut conditions: Vec<&str> = vec!["1"];
+        let mut params: Vec<(&str, &dyn ToSql)> = Vec::new();
+
+        add_filters(&mut conditions, &mut params, filters);
+
+        let reader = self.reader()?;
+        let mut select_totals = reader.prepare(&format!(
+            "SELECT mint, symbol, decimals, SUM(type = 'mint'), SUM(type = 'burn'), {}, {}
+             FROM supply_changes
+             WHERE {}
+             GROUP BY mint
+             ORDER BY mint",
+            get_split_sum("CASE WHEN type = 'mint' THEN amount ELSE 0 END"),
+            get_split_sum("CASE WHEN type = 'burn' THEN amount ELSE 0 END"),
+            conditions.join(" AND ")
+        ))?;
+
+        let totals = select_totals
+            .query_map(params.as_slice(), |row| {
+                Ok(SupplyTotals::new(
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    read_split_sum(row, 5)?,
+                    read_split_sum(row, 7)?,
+                ))
+            })?
+            .collect();
+
+        totals
+    }
+
+    // Up to one more than the query's limit of the events acting upon this address, in execution order
+    pub fn read_address_events(
+        &self,
+        address: &str,
+        query: &AccountEventsQuery,
+    ) -> rusqlite::Result<Vec<AddressEvent>> {
+        let limit = page::get_fetch_limit(query.get_limit());
+        let filters: [Filter; 4] = [
+            ("symbol = :symbol COLLATE NOCASE", ":symbol", query.symbol.as_ref().map(|v| v as &dyn ToSql)),
+            ("type = :type", ":type", query.kind.as_ref().map(|v| v as &dyn ToSql)),
+            ("slot >= :from_slot", ":from_slot", query.from_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("slot <= :to_slot", ":to_slot", query.to_slot.as_ref().map(|v| v as &dyn ToSql)),
+        ];
+        let mut conditions: Vec<&str> = vec!["account = :address"];
+        let mut params: Vec<(&str, &dyn ToSql)> = vec![(":address", &address), (":limit", &limit)];
+
+        add_filters(&mut conditions, &mut params, filters);
+
+        let reader = self.reader()?;
+        let mut select_events = reader.prepare(&format!(
+            "SELECT slot, signature, mint, symbol, instruction_index, stack_height, block_time, event
+             FROM account_events
+             WHERE {}
+             ORDER BY slot, position
+             LIMIT :limit",
+            conditions.join(" AND ")
+        ))?;
+
+        let events = select_events
+            .query_map(params.as_slice(), |row| {
+                let event: String = row.get(7)?;
+
+                Ok(AddressEvent {
+                    slot: row.get(0)?,
+                    event: AccountEvent {
+                        signature: row.get(1)?,
+                        mint: row.get(2)?,
+                        symbol: row.get(3)?,
+                        event: serde_json::from_str(&event)
+                            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(7, Type::Text, e.into()))?,
+                        instruction_index: row.get(4)?,
+                        stack_height: row.get(5)?,
+                        block_time: row.get(6)?,
+                    },
+                })
+            })?
+            .collect();
+
+        events
+    }
+
+    // Up to one more than the query's limit of the failed transactions matching the query, in execution order
+    pub fn read_failed_transactions(
+        &self,
+        query: &FailedTransactionsQuery,
+    ) -> rusqlite::Result<Vec<FailedTransaction>> {
+        let limit = page::get_fetch_limit(query.get_limit());
+        let filters: [Filter; 3] = [
+            ("token_error = :token_error", ":token_error", query.token_error.as_ref().map(|v| v as &dyn ToSql)),
+            ("slot >= :from_slot", ":from_slot", query.from_slot.as_ref().map(|v| v as &dyn ToSql)),
+            ("slot <= :to_slot", ":to_slot", query.to_slot.as_ref().map(|v| v as &dyn ToSql)),
+        ];
+        // A transaction matches if any one of the transfers it attempted matches all of these
+        let transfer_filters: [Filter; 3] = [
+            ("symbol = :symbol COLLATE NOCASE", ":symbol", query.symbol.as_ref().map(|v| v as &dyn ToSql)),
+            (
+                "(source = :address OR destination = :address)",
+                ":address",
+                query.address.as_ref().map(|v| v as &dyn ToSql),
+            ),
+            (
+                "(source_owner = :owner OR destination_owner = :owner)",
+                ":owner",
+                query.owner.as_ref().map(|v| v as &dyn ToSql),
+            ),
+        ];
+        let mut conditions: Vec<&str> = vec!["1"];
+        let mut transfer_conditions: Vec<&str> = vec!["1"];
+        let mut params: Vec<(&str, &dyn ToSql)> = vec![(":limit", &limit)];
+
+        add_filters(&mut conditions, &mut params, filters);
+        add_filters(&mut transfer_conditions, &mut params, transfer_filters);
+
+        let reader = self.reader()?;
+        let mut select_failed_transactions = reader.prepare(&format!(
+            "SELECT id, record
+             FROM failed_transactions
+             WHERE {}
+               AND EXISTS (SELECT 1 FROM failed_transfers WHERE failed_transaction_id = failed_transactions.id AND {})
+             ORDER BY slot, position
+             LIMIT :limit",
+            conditions.join(" AND "),
+            transfer_conditions.join(" AND ")
+        ))?;
+        let mut select_failed_transfers = reader.prepare(
+            "SELECT mint, symbol, source, destination, amount, decimals, ui_amount, fee, instruction_index,
+                    stack_height, source_owner, destination_owner
+             FROM failed_transfers
+             WHERE failed_transaction_id = ?1
+             ORDER BY position",
+        )?;
+        let mut failed_txns: Vec<FailedTransaction> = Vec::new();
+        let mut rows = select_failed_transactions.query(params.as_slice())?;
+
+        while let Some(row) = rows.next()? {
+            let failed_transaction_id: i64 = row.get(0)?;
+            let record: String = row.get(1)?;
+            let mut failed_txn: FailedTransaction = serde_json::from_str(&record)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, Type::Text, e.into()))?;
+
+            failed_txn.transaction.transfers = select_failed_transfers
+                .query_map(params![failed_transaction_id], |row| get_transfer(row, 0))?
+                .collect::<rusqlite::Result<Vec<TransferRecord>>>()?;
+            failed_txns.push(failed_txn);
+        }
+
+        Ok(failed_txns)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
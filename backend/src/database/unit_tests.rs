@@ -0,0 +1,862 @@
+use crate::{
+    account_event::{
+        unit_tests::{freeze, get_account_event, revoke},
+        AccountEventsQuery,
+    },
+    config::Config,
+    database::{Database, MIGRATIONS, SCHEMA},
+    failed::FailedTransactionsQuery,
+    query::{TransactionCursor, TransactionsQuery},
+    slot::unit_tests::{create_mock_client, MockRpcSender},
+    solana::{
+        FailedTransaction, SignedUsdcTransactionsBySlot, SupplyChangeKind, TransactionStatus, UsdcTransaction,
+        UsdcTransfer,
+    },
+    state::AppState,
+    supply::{unit_tests::get_supply_change, SupplyQuery},
+    test_data::{get_slot, get_transaction, get_transfer},
+};
+
+use solana_sdk::{
+    instruction::InstructionError,
+    transaction::{TransactionError, TransactionVersion},
+};
+use rusqlite::{params, Connection};
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Each test gets its own database, which is deleted (along with its write-ahead log) when the test finishes
+struct TestDatabase {
+    path: PathBuf,
+    database: Database,
+}
+
+impl TestDatabase {
+    fn get_path(test_name: &str) -> PathBuf {
+        env::temp_dir().join(format!("solana_usdc_monitor_{}_{}.db", test_name, std::process::id()))
+    }
+
+    fn open(test_name: &str) -> Result<Self, String> {
+        let path = TestDatabase::get_path(test_name);
+
+        Ok(TestDatabase {
+            database: Database::open(&path).map_err(|e| e.to_string())?,
+            path,
+        })
+    }
+}
+
+impl Drop for TestDatabase {
+    fn drop(&mut self) {
+        for extension in ["db", "db-wal", "db-shm"] {
+            let _ = fs::remove_file(self.path.with_extension(extension));
+        }
+    }
+}
+
+// A transaction of one transfer, with every optional field that is stored set
+fn get_stored_txn(symbol: &str, amount: u64, fee: Option<u64>, signatures: &[&str]) -> UsdcTransaction {
+    let mut transaction = get_transaction(symbol, "Source", "Destination", amount);
+
+    transaction.transfers[0].txn = UsdcTransfer {
+        from_owner: Some("SourceOwner".to_string()),
+        ui_amount: amount.to_string(),
+        fee,
+        instruction_index: 1,
+        stack_height: 2,
+        ..transaction.transfers[0].txn.clone()
+    };
+
+    UsdcTransaction {
+        signature: signatures[0].to_string(),
+        signatures: signatures.iter().map(|signature| signature.to_string()).collect(),
+        block_time: Some(1739353792),
+        fee_payer: "FeePayer".to_string(),
+        transaction_fee: Some(5000),
+        version: Some(TransactionVersion::Number(0)),
+        ..transaction
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_read_back_written_slots_in_order() -> Result<(), String> {
+    let test_db = TestDatabase::open("read_back")?;
+    let slots = vec![
+        get_slot(
+            7,
+            vec![
+                get_stored_txn("USDC", 1470000, Some(1470), &["Signature1", "Signature2"]),
+                get_stored_txn("USDT", 2000000, None, &["Signature3"]),
+            ],
+        ),
+        get_slot(9, vec![get_stored_txn("USDC", 1, None, &["Signature4"])]),
+    ];
+
+    // Write out of order, as a backfill would
+    for txns_by_slot in slots.iter().rev() {
+        test_db.database.write_slot(txns_by_slot).map_err(|e| e.to_string())?;
+    }
+
+    let stored = test_db
+        .database
+        .read_page(&TransactionsQuery::default())
+        .map_err(|e| e.to_string())?
+        .slots;
+
+    if serde_json::to_value(&stored).ok() == serde_json::to_value(&slots).ok() {
+        Ok(())
+    } else {
+        Err(format!("Stored slots differ from those written: {:?}", stored))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_replace_rewritten_slot() -> Result<(), String> {
+    let test_db = TestDatabase::open("replace")?;
+
+    test_db
+        .database
+        .write_slot(&get_slot(7, vec![get_stored_txn("USDC", 1, None, &["Signature1"]); 2]))
+        .map_err(|e| e.to_string())?;
+    test_db
+        .database
+        .write_slot(&get_slot(7, vec![get_stored_txn("USDC", 2, None, &["Signature2"])]))
+        .map_err(|e| e.to_string())?;
+
+    let stored = test_db
+        .database
+        .read_page(&TransactionsQuery::default())
+        .map_err(|e| e.to_string())?
+        .slots;
+
+    match stored.as_slice() {
+        [txns_by_slot]
+            if txns_by_slot.transactions.len() == 1 && *txns_by_slot.transactions[0].signatures == ["Signature2"] =>
+        {
+            Ok(())
+        }
+        _ => Err(format!("Slot should have been replaced, got {:?}", stored)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_filter_by_symbol_ignoring_case() -> Result<(), String> {
+    let test_db = TestDatabase::open("symbol")?;
+
+    test_db
+        .database
+        .write_slot(&get_slot(
+            7,
+            vec![
+                get_stored_txn("USDC", 1, None, &["Signature1"]),
+                get_stored_txn("USDT", 2, None, &["Signature2"]),
+            ],
+        ))
+        .map_err(|e| e.to_string())?;
+    test_db
+        .database
+        .write_slot(&get_slot(8, vec![get_stored_txn("USDC", 3, None, &["Signature3"])]))
+        .map_err(|e| e.to_string())?;
+
+    let query = TransactionsQuery {
+        symbol: Some("usdt".to_string()),
+        ..TransactionsQuery::default()
+    };
+    let stored = test_db.database.read_page(&query).map_err(|e| e.to_string())?.slots;
+
+    match stored.as_slice() {
+        [txns_by_slot] if txns_by_slot.slot == 7 && txns_by_slot.transfer_count() == 1 => Ok(()),
+        _ => Err(format!("Expected only the USDT transfer in slot 7, got {:?}", stored)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_page_through_filtered_slots() -> Result<(), String> {
+    let test_db = TestDatabase::open("page")?;
+
+    for slot in 1..=5 {
+        test_db
+            .database
+            .write_slot(&get_slot(
+                slot,
+                vec![
+                    get_stored_txn("USDC", slot * 10, None, &["Signature1"]),
+                    get_stored_txn("USDC", slot * 100, None, &["Signature2"]),
+                ],
+            ))
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut query = TransactionsQuery {
+        to_slot: Some(4),
+        min_amount: Some(20),
+        max_amount: Some(100),
+        address: Some("Destination".to_string()),
+        limit: Some(2),
+        ..TransactionsQuery::default()
+    };
+    let mut pages: Vec<(Vec<u64>, usize, Option<u64>, bool)> = Vec::new();
+
+    for _ in 0..2 {
+        let page = test_db.database.read_page(&query).map_err(|e| e.to_string())?;
+
+        query.after_slot = page.next_cursor;
+        pages.push((
+            page.slots.iter().map(|txns_by_slot| txns_by_slot.slot).collect(),
+            page.slots.iter().map(SignedUsdcTransactionsBySlot::transfer_count).sum(),
+            page.next_cursor,
+            page.has_more,
+        ));
+    }
+
+    // Each of slots 1 to 4 has exactly one transfer within the amount range (100, 20, 30 and 40), and slot 5 is beyond
+    // to_slot
+    if pages == vec![(vec![1, 2], 2, Some(2), true), (vec![3, 4], 2, Some(4), false)] {
+        Ok(())
+    } else {
+        Err(format!("Wrong pages {:?}", pages))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_sum_flows_through_address() -> Result<(), String> {
+    let test_db = TestDatabase::open("flow_totals")?;
+    let mut outbound = get_stored_txn("USDC", 30, None, &["Signature3"]);
+
+    outbound.transfers[0].txn.from = "Destination".to_string();
+    outbound.transfers[0].txn.to = "Supplier".to_string();
+    test_db
+        .database
+        .write_slot(&get_slot(
+            7,
+            vec![get_stored_txn("USDC", 100, None, &["Signature1"]), get_stored_txn("USDT", 5, None, &["Signature2"])],
+        ))
+        .map_err(|e| e.to_string())?;
+    test_db.database.write_slot(&get_slot(8, vec![outbound])).map_err(|e| e.to_string())?;
+
+    let query = TransactionsQuery {
+        address: Some("Destination".to_string()),
+        ..TransactionsQuery::default()
+    };
+    let totals: Vec<(String, u64, u128, u128, i128)> = test_db
+        .database
+        .read_flow_totals(&query)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|flow| (flow.symbol, flow.transfer_count, flow.inbound, flow.outbound, flow.net))
+        .collect();
+
+    if totals == vec![("USDC".to_string(), 2, 100, 30, 70), ("USDT".to_string(), 1, 5, 0, 5)] {
+        Ok(())
+    } else {
+        Err(format!("Wrong totals {:?}", totals))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_06_should_filter_by_owner() -> Result<(), String> {
+    let test_db = TestDatabase::open("owner")?;
+    let mut other_owner = get_stored_txn("USDC", 2, None, &["Signature2"]);
+
+    other_owner.transfers[0].txn.from_owner = None;
+    other_owner.transfers[0].txn.to_owner = Some("OtherOwner".to_string());
+    test_db
+        .database
+        .write_slot(&get_slot(7, vec![get_stored_txn("USDC", 1, None, &["Signature1"]), other_owner]))
+        .map_err(|e| e.to_string())?;
+
+    let mut counts = Vec::new();
+
+    for owner in ["SourceOwner", "OtherOwner", "Destination"] {
+        let query = TransactionsQuery {
+            owner: Some(owner.to_string()),
+            ..TransactionsQuery::default()
+        };
+        let page = test_db.database.read_page(&query).map_err(|e| e.to_string())?;
+
+        counts.push(page.slots.iter().map(SignedUsdcTransactionsBySlot::transfer_count).sum::<usize>());
+    }
+
+    // A token account address is not an owner
+    if counts == [1, 1, 0] {
+        Ok(())
+    } else {
+        Err(format!("Wrong number of transfers by owner {:?}", counts))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_07_should_sum_supply_changes() -> Result<(), String> {
+    let test_db = TestDatabase::open("supply")?;
+
+    // A slot holding only supply changes is still stored
+    for (slot, supply_changes) in [
+        (7, vec![get_supply_change(SupplyChangeKind::Mint, "USDC", 100, Some(1000))]),
+        (
+            8,
+            vec![
+                get_supply_change(SupplyChangeKind::Burn, "USDC", 30, Some(2000)),
+                get_supply_change(SupplyChangeKind::Mint, "USDT", 10, None),
+            ],
+        ),
+    ] {
+        let txns_by_slot = SignedUsdcTransactionsBySlot {
+            slot,
+            supply_changes,
+            ..Default::default()
+        };
+
+        test_db.database.write_slot(&txns_by_slot).map_err(|e| e.to_string())?;
+    }
+
+    let mut totals = Vec::new();
+
+    for query in [
+        SupplyQuery::default(),
+        SupplyQuery {
+            from_time: Some(1500),
+            ..SupplyQuery::default()
+        },
+    ] {
+        for supply in test_db.database.read_supply_totals(&query).map_err(|e| e.to_string())? {
+            totals.push((supply.symbol, supply.mint_count, supply.burn_count, supply.net));
+        }
+    }
+
+    let expected = [("USDC", 1, 1, 70), ("USDT", 1, 0, 10), ("USDC", 0, 1, -30)]
+        .map(|(symbol, mint_count, burn_count, net)| (symbol.to_string(), mint_count, burn_count, net));
+
+    if totals == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong totals {:?}", totals))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_08_should_read_back_account_events() -> Result<(), String> {
+    let test_db = TestDatabase::open("account_events")?;
+
+    for (slot, account_events) in [
+        (7, vec![get_account_event("Treasury", freeze), get_account_event("Customer", revoke)]),
+        (8, vec![get_account_event("Treasury", revoke)]),
+    ] {
+        let txns_by_slot = SignedUsdcTransactionsBySlot {
+            slot,
+            account_events,
+            ..Default::default()
+        };
+
+        test_db.database.write_slot(&txns_by_slot).map_err(|e| e.to_string())?;
+    }
+
+    let mut got = Vec::new();
+
+    for kind in [None, Some("revoke".to_string())] {
+        let query = AccountEventsQuery {
+            kind,
+            ..AccountEventsQuery::default()
+        };
+
+        for address_event in test_db.database.read_address_events("Treasury", &query).map_err(|e| e.to_string())? {
+            got.push((address_event.slot, serde_json::to_value(address_event.event).map_err(|e| e.to_string())?));
+        }
+    }
+
+    let get_expected =
+        |slot, event| (slot, serde_json::to_value(get_account_event("Treasury", event)).unwrap_or_default());
+    let expected = [get_expected(7, freeze), get_expected(8, revoke), get_expected(8, revoke)];
+
+    if got == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong events {:?}", got))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_09_should_read_back_failed_transactions() -> Result<(), String> {
+    let test_db = TestDatabase::open("failed_transactions")?;
+    let insufficient_funds = || {
+        (
+            TransactionError::InstructionError(0, InstructionError::Custom(1)),
+            Some("insufficient_funds".to_string()),
+        )
+    };
+
+    for (slot, transactions, errors) in [
+        (
+            7,
+            vec![get_stored_txn("USDC", 10, None, &["First"]), get_stored_txn("USDT", 20, None, &["Second"])],
+            vec![insufficient_funds(), (TransactionError::AccountInUse, None)],
+        ),
+        (8, vec![get_stored_txn("USDT", 30, None, &["Third"])], vec![insufficient_funds()]),
+    ] {
+        let txns_by_slot = SignedUsdcTransactionsBySlot {
+            slot,
+            failed_transactions: get_slot(slot, transactions)
+                .transactions
+                .into_iter()
+                .zip(errors)
+                .map(|(transaction, (error, token_error))| {
+                    let transaction = UsdcTransaction {
+                        status: TransactionStatus::Failed,
+                        ..transaction
+                    };
+
+                    FailedTransaction::new(transaction, error, token_error)
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        test_db.database.write_slot(&txns_by_slot).map_err(|e| e.to_string())?;
+    }
+
+    let mut got = Vec::new();
+
+    for query in [
+        FailedTransactionsQuery::default(),
+        FailedTransactionsQuery {
+            symbol: Some("usdt".to_string()),
+            token_error: Some("insufficient_funds".to_string()),
+            ..FailedTransactionsQuery::default()
+        },
+        FailedTransactionsQuery {
+            owner: Some("SourceOwner".to_string()),
+            from_slot: Some(8),
+            ..FailedTransactionsQuery::default()
+        },
+        FailedTransactionsQuery {
+            address: Some("Nobody".to_string()),
+            ..FailedTransactionsQuery::default()
+        },
+    ] {
+        let failed_txns = test_db.database.read_failed_transactions(&query).map_err(|e| e.to_string())?;
+
+        let signatures: Vec<&str> =
+            failed_txns.iter().map(|failed_txn| failed_txn.transaction.signature.as_str()).collect();
+
+        got.push(signatures.join(","));
+
+        if let Some(failed_txn) = failed_txns.iter().find(|failed_txn| failed_txn.transaction.signature == "Second") {
+            let transaction = &failed_txn.transaction;
+
+            if failed_txn.error != TransactionError::AccountInUse
+                || transaction.status != TransactionStatus::Failed
+                || transaction.transfers[0].txn.amount != 20
+            {
+                return Err(format!("Wrong failed transaction {:?}", failed_txn));
+            }
+        }
+    }
+
+    if got == ["First,Second,Third", "Third", "Third", ""] {
+        Ok(())
+    } else {
+        Err(format!("Wrong failed transactions {:?}", got))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_10_should_leave_out_only_unstorable_amounts() -> Result<(), String> {
+    let test_db = TestDatabase::open("unstorable")?;
+    let txns_by_slot = SignedUsdcTransactionsBySlot {
+        supply_changes: vec![
+            get_supply_change(SupplyChangeKind::Mint, "USDC", u64::MAX, None),
+            get_supply_change(SupplyChangeKind::Mint, "USDC", 100, None),
+        ],
+        ..get_slot(
+            7,
+            vec![
+                get_stored_txn("USDC", u64::MAX, None, &["Signature1"]),
+                get_stored_txn("USDC", 1, None, &["Signature2"]),
+            ],
+        )
+    };
+
+    test_db.database.write_slot(&txns_by_slot).map_err(|e| e.to_string())?;
+
+    let stored = test_db
+        .database
+        .read_page(&TransactionsQuery::default())
+        .map_err(|e| e.to_string())?
+        .slots;
+    let supply = test_db
+        .database
+        .read_supply_totals(&SupplyQuery::default())
+        .map_err(|e| e.to_string())?;
+
+    match (stored.as_slice(), supply.as_slice()) {
+        ([txns_by_slot], [totals])
+            if txns_by_slot.transfer_count() == 1
+                && txns_by_slot.transactions[0].transfers[0].txn.amount == 1
+                && totals.minted == 100 =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Only the unstorable rows should have been left out, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_11_should_store_signatures_once_per_transaction() -> Result<(), String> {
+    let test_db = TestDatabase::open("signatures")?;
+    let mut transaction = get_stored_txn("USDC", 1, None, &["Signature1", "Signature2"]);
+
+    transaction.transfers.push(get_stored_txn("USDT", 2, None, &["Signature1"]).transfers.remove(0));
+    test_db
+        .database
+        .write_slot(&get_slot(7, vec![transaction]))
+        .map_err(|e| e.to_string())?;
+
+    let signature_count: u64 = test_db
+        .database
+        .reader()
+        .and_then(|reader| reader.query_row("SELECT COUNT(*) FROM signatures", [], |row| row.get(0)))
+        .map_err(|e| e.to_string())?;
+    let found = test_db.database.find_signature("Signature2").map_err(|e| e.to_string())?;
+
+    match found {
+        Some(transaction)
+            if signature_count == 2 && transaction.signature == "Signature1" && transaction.transfers.len() == 2 =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Wrong transaction {:?} with {} signatures stored", other, signature_count)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_12_should_page_through_transactions() -> Result<(), String> {
+    let test_db = TestDatabase::open("transaction_page")?;
+
+    for slot in 1..=3 {
+        test_db
+            .database
+            .write_slot(&get_slot(
+                slot,
+                vec![
+                    get_stored_txn("USDC", slot * 10, None, &["Signature1"]),
+                    get_stored_txn("USDT", slot * 100, None, &["Signature2"]),
+                    get_stored_txn("USDC", slot * 1000, None, &["Signature3"]),
+                ],
+            ))
+            .map_err(|e| e.to_string())?;
+    }
+
+    // Only the USDC transactions are selected, two at a time
+    let mut query = TransactionsQuery {
+        symbol: Some("usdc".to_string()),
+        limit: Some(2),
+        ..TransactionsQuery::default()
+    };
+    let mut pages: Vec<(Vec<u64>, Option<TransactionCursor>, bool)> = Vec::new();
+
+    for _ in 0..3 {
+        let page = test_db.database.read_transaction_page(&query).map_err(|e| e.to_string())?;
+
+        query.after_slot = page.next_cursor.as_ref().map(|cursor| cursor.after_slot);
+        query.after_position = page.next_cursor.as_ref().and_then(|cursor| cursor.after_position);
+        pages.push((
+            page.transactions.iter().map(|transaction| transaction.transfers[0].txn.amount).collect(),
+            page.next_cursor,
+            page.has_more,
+        ));
+    }
+
+    let cursor = |after_slot, after_position| {
+        Some(TransactionCursor {
+            after_slot,
+            after_position: Some(after_position),
+        })
+    };
+    let expected = vec![
+        (vec![10, 1000], cursor(1, 2), true),
+        (vec![20, 2000], cursor(2, 2), true),
+        (vec![30, 3000], cursor(3, 2), false),
+    ];
+
+    if pages == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong pages {:?}", pages))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_13_should_migrate_transfers_into_transactions() -> Result<(), String> {
+    let path = TestDatabase::get_path("migrate_transactions");
+
+    // A database last opened before transactions were stored, holding a transaction of two transfers (each with its
+    // own copy of the signatures) followed by a transaction of one
+    {
+        let connection = Connection::open(&path).map_err(|e| e.to_string())?;
+        let insert_transfer = "INSERT INTO transfers (slot, position, mint, symbol, source, destination, amount,
+                                                      decimals, ui_amount, instruction_index, stack_height, block_time,
+                                                      fee_payer, transaction_fee, version)
+                               VALUES (7, ?1, 'USDCMint', 'USDC', 'Source', 'Destination', ?2, 6, ?2, ?1, 1,
+                                       1739353792, 'FeePayer', 5000, '0')";
+
+        connection.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+
+        for migration in &MIGRATIONS[..6] {
+            connection.execute_batch(migration).map_err(|e| e.to_string())?;
+        }
+
+        connection.pragma_update(None, "user_version", 6).map_err(|e| e.to_string())?;
+        connection.execute("INSERT INTO slots (slot) VALUES (7)", []).map_err(|e| e.to_string())?;
+
+        for (position, signatures) in [(0, ["First", "Extra"].as_slice()), (1, &["First", "Extra"]), (2, &["Second"])] {
+            connection.execute(insert_transfer, params![position, position + 1]).map_err(|e| e.to_string())?;
+
+            let transfer_id = connection.last_insert_rowid();
+
+            for (signature_position, signature) in signatures.iter().enumerate() {
+                connection
+                    .execute(
+                        "INSERT INTO signatures (transfer_id, position, signature) VALUES (?1, ?2, ?3)",
+                        params![transfer_id, signature_position, signature],
+                    )
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    let test_db = TestDatabase {
+        database: Database::open(&path).map_err(|e| e.to_string())?,
+        path,
+    };
+    let found = ["Extra", "Second"]
+        .into_iter()
+        .map(|signature| test_db.database.find_signature(signature))
+        .collect::<rusqlite::Result<Vec<Option<UsdcTransaction>>>>()
+        .map_err(|e| e.to_string())?;
+    // Both signatures must be found
+    let summary: Vec<(String, usize, u32, Option<u64>)> = found
+        .iter()
+        .flatten()
+        .map(|transaction| {
+            (
+                transaction.signatures.join(","),
+                transaction.transfers.len(),
+                transaction.position,
+                transaction.transaction_fee,
+            )
+        })
+        .collect();
+    let expected = vec![
+        ("First,Extra".to_string(), 2, 0, Some(5000)),
+        ("Second".to_string(), 1, 2, Some(5000)),
+    ];
+
+    if summary == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong migrated transactions {:?}", summary))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_14_should_record_a_dead_letter_when_a_slot_cannot_be_stored() -> Result<(), String> {
+    let test_db = TestDatabase::open("unstorable_slot")?;
+    let state = AppState::new(
+        Config::default(),
+        create_mock_client(MockRpcSender::new(HashMap::new())),
+        Some(Database::open(&test_db.path).map_err(|e| e.to_string())?),
+    );
+
+    // Every write of a transaction now fails
+    Connection::open(&test_db.path)
+        .and_then(|connection| connection.execute_batch("DROP TABLE transactions"))
+        .map_err(|e| e.to_string())?;
+
+    let txns_by_slot = get_slot(7, vec![get_transaction("USDC", "Source", "Destination", 1)]);
+    let recorded = state.record_slot(Ok((txns_by_slot, Default::default(), Default::default()))).await;
+    let dead_letters: Vec<u64> = state.dead_letters.lock().await.slots().collect();
+
+    match recorded {
+        Err(dead_letter) if dead_letter.slot == 7 && dead_letters == [7] => Ok(()),
+        _ => Err(format!("Expected slot 7 as a dead letter, got {:?} and {:?}", recorded, dead_letters)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_15_should_limit_transactions_rather_than_transfers() -> Result<(), String> {
+    let test_db = TestDatabase::open("transaction_page_transfers")?;
+    // Transactions of three transfers each, of which only the last two are to Bob
+    let transactions = (1..=3)
+        .map(|amount| {
+            let mut transaction = get_transaction("USDC", "Source", "Alice", amount);
+
+            transaction.transfers.push(get_transfer("USDC", "Source", "Bob", amount * 10));
+            transaction.transfers.push(get_transfer("USDC", "Source", "Bob", amount * 100));
+            transaction
+        })
+        .collect();
+
+    test_db.database.write_slot(&get_slot(7, transactions)).map_err(|e| e.to_string())?;
+
+    let query = TransactionsQuery {
+        address: Some("Bob".to_string()),
+        limit: Some(2),
+        ..TransactionsQuery::default()
+    };
+    let page = test_db.database.read_transaction_page(&query).map_err(|e| e.to_string())?;
+    let amounts: Vec<Vec<u64>> = page
+        .transactions
+        .iter()
+        .map(|transaction| transaction.transfers.iter().map(|transfer| transfer.txn.amount).collect())
+        .collect();
+
+    if amounts == [vec![10, 100], vec![20, 200]] && page.has_more {
+        Ok(())
+    } else {
+        Err(format!("Wrong page {:?} (more: {})", amounts, page.has_more))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_16_should_serve_slots_from_the_database_rather_than_memory() -> Result<(), String> {
+    let test_db = TestDatabase::open("database_only")?;
+    let state = AppState::new(
+        Config::default(),
+        create_mock_client(MockRpcSender::new(HashMap::new())),
+        Some(Database::open(&test_db.path).map_err(|e| e.to_string())?),
+    );
+    let txns_by_slot = get_slot(7, vec![get_transaction("USDC", "Source", "Destination", 1)]);
+
+    state
+        .record_slot(Ok((txns_by_slot, Default::default(), Default::default())))
+        .await
+        .map_err(|dead_letter| dead_letter.error)?;
+
+    let buffered = state.txns.lock().await.iter().count();
+    let page = state.read_page(TransactionsQuery::default()).await?;
+    let slots: Vec<u64> = page.slots.iter().map(|txns_by_slot| txns_by_slot.slot).collect();
+
+    if buffered == 0 && slots == [7] {
+        Ok(())
+    } else {
+        Err(format!("Expected slot 7 in the database only, got {} buffered and {:?} stored", buffered, slots))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_17_should_migrate_failed_transfers_out_of_the_record() -> Result<(), String> {
+    let path = TestDatabase::get_path("migrate_failed_transfers");
+
+    // A database last opened before the transfers of failed transactions had their own table, holding a failed
+    // transaction that attempted two transfers
+    {
+        let connection = Connection::open(&path).map_err(|e| e.to_string())?;
+        let mut transaction = get_transaction("USDC", "Source", "Alice", 1);
+
+        transaction.transfers.push(get_transfer("USDC", "Source", "Bob", 2));
+
+        let failed_txn = FailedTransaction::new(
+            UsdcTransaction {
+                slot: 7,
+                status: TransactionStatus::Failed,
+                ..transaction
+            },
+            TransactionError::AccountInUse,
+            None,
+        );
+        let record = serde_json::to_string(&failed_txn).map_err(|e| e.to_string())?;
+
+        connection.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+
+        for migration in &MIGRATIONS[..7] {
+            connection.execute_batch(migration).map_err(|e| e.to_string())?;
+        }
+
+        connection.pragma_update(None, "user_version", 7).map_err(|e| e.to_string())?;
+        connection.execute("INSERT INTO slots (slot) VALUES (7)", []).map_err(|e| e.to_string())?;
+        connection
+            .execute(
+                "INSERT INTO failed_transactions (slot, position, signature, token_error, record)
+                 VALUES (7, 0, 'Signature1', NULL, ?1)",
+                params![record],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    let test_db = TestDatabase {
+        database: Database::open(&path).map_err(|e| e.to_string())?,
+        path,
+    };
+    let query = FailedTransactionsQuery {
+        address: Some("Bob".to_string()),
+        ..FailedTransactionsQuery::default()
+    };
+    let failed_txns = test_db.database.read_failed_transactions(&query).map_err(|e| e.to_string())?;
+    let amounts: Vec<Vec<u64>> = failed_txns
+        .iter()
+        .map(|failed_txn| failed_txn.transaction.transfers.iter().map(|transfer| transfer.txn.amount).collect())
+        .collect();
+    let recorded: usize = Connection::open(&test_db.path)
+        .and_then(|connection| {
+            connection.query_row(
+                "SELECT SUM(json_array_length(record, '$.transfers')) FROM failed_transactions",
+                [],
+                |row| row.get(0),
+            )
+        })
+        .map_err(|e| e.to_string())?;
+
+    if amounts == [vec![1, 2]] && recorded == 0 {
+        Ok(())
+    } else {
+        Err(format!("Wrong migrated failed transactions {:?} ({} transfers left in records)", amounts, recorded))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_18_should_sum_amounts_beyond_the_range_of_sqlite_integers() -> Result<(), String> {
+    let test_db = TestDatabase::open("large_totals")?;
+    let amount = i64::MAX as u64;
+
+    for slot in 1..=3 {
+        let mut txns_by_slot = get_slot(slot, vec![get_stored_txn("USDC", amount, None, &["Signature"])]);
+
+        txns_by_slot.supply_changes = vec![get_supply_change(SupplyChangeKind::Mint, "USDC", amount, None)];
+        test_db.database.write_slot(&txns_by_slot).map_err(|e| e.to_string())?;
+    }
+
+    let query = TransactionsQuery {
+        address: Some("Destination".to_string()),
+        ..TransactionsQuery::default()
+    };
+    let flows = test_db.database.read_flow_totals(&query).map_err(|e| e.to_string())?;
+    let supply = test_db
+        .database
+        .read_supply_totals(&SupplyQuery::default())
+        .map_err(|e| e.to_string())?;
+    let expected = amount as u128 * 3;
+
+    match (flows.as_slice(), supply.as_slice()) {
+        ([flow], [totals]) if flow.inbound == expected && flow.net == expected as i128 && totals.minted == expected => {
+            Ok(())
+        }
+        other => Err(format!("Expected totals of {}, got {:?}", expected, other)),
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{query::TransactionsQuery, solana::SignedUsdcTransactionsBySlot, state::AppState, MAX_PAGE_LIMIT};
+
+use futures::{stream, Stream};
+use log::{error, warn};
+use std::{collections::VecDeque, sync::Arc};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[derive(Clone, Debug)]
+pub enum StreamEvent {
+    // A slot holding only the matching transfers
+    Slot(SignedUsdcTransactionsBySlot),
+    // This many slots were missed, and could not be replayed from the store, so the client should reload /transactions
+    Lagged { missed_slots: u64 },
+}
+
+struct Subscription {
+    state: AppState,
+    query: TransactionsQuery,
+    receiver: Receiver<Arc<SignedUsdcTransactionsBySlot>>,
+    // Highest slot seen so far, whether or not it matched the query
+    last_slot: Option<u64>,
+    // Stored slots still to be sent before any more live ones
+    backlog: VecDeque<SignedUsdcTransactionsBySlot>,
+    // Where to read the next page of stored slots from, once the backlog has been sent
+    backlog_cursor: Option<u64>,
+}
+
+impl Subscription {
+    // Queue the first page of stored slots after this one that match the query.  Further pages are read as the backlog
+    // is sent
+    async fn fill_backlog(&mut self, after_slot: u64) {
+        let query = TransactionsQuery {
+            after_slot: Some(after_slot),
+            limit: Some(MAX_PAGE_LIMIT),
+            ..self.query.clone()
+        };
+
+        self.backlog_cursor = None;
+
+        match self.state.read_page(query).await {
+            Ok(page) => {
+                self.backlog.extend(page.slots);
+
+                if page.has_more {
+                    self.backlog_cursor = page.next_cursor;
+                }
+            }
+            Err(e) => error!("Unable to read slots after slot {}: {}", after_slot, e),
+        }
+    }
+
+    async fn next(&mut self) -> Option<StreamEvent> {
+        loop {
+            if let Some(txns_by_slot) = self.backlog.pop_front() {
+                self.last_slot = Some(txns_by_slot.slot);
+                return Some(StreamEvent::Slot(txns_by_slot));
+            }
+
+            if let Some(cursor) = self.backlog_cursor {
+                self.fill_backlog(cursor).await;
+                continue;
+            }
+
+            match self.receiver.recv().await {
+                Ok(txns_by_slot) => {
+                    // Already sent from the backlog
+                    if self.last_slot.is_some_and(|last_slot| txns_by_slot.slot <= last_slot) {
+                        continue;
+                    }
+
+                    self.last_slot = Some(txns_by_slot.slot);
+
+                    if let Some(matching) = self.query.filter_slot(&txns_by_slot) {
+                        return Some(StreamEvent::Slot(matching));
+                    }
+                }
+                // Without a slot to replay from, the subscriber can only be told what it missed
+                Err(RecvError::Lagged(missed)) if self.last_slot.is_none() => {
+                    warn!("Subscriber missed {} slots before receiving any", missed);
+                    return Some(StreamEvent::Lagged { missed_slots: missed });
+                }
+                // The subscriber fell too far behind, so catch up from the store instead
+                Err(RecvError::Lagged(missed)) => {
+                    warn!("Subscriber missed {} slots, catching up from the store", missed);
+
+                    if let Some(last_slot) = self.last_slot {
+                        self.fill_backlog(last_slot).await;
+                    }
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+// Stream the slots published by the monitor, holding only the transfers that match the query.  If a slot is given,
+// first replay any stored slots after it, so a client that reconnects misses nothing
+pub async fn subscribe(
+    state: AppState,
+    query: TransactionsQuery,
+    last_slot: Option<u64>,
+) -> impl Stream<Item = StreamEvent> {
+    // Subscribe before reading the backlog, so that no slot is published unseen in between
+    let mut subscription = Subscription {
+        receiver: state.events.subscribe(),
+        state,
+        query,
+        last_slot,
+        backlog: VecDeque::new(),
+        backlog_cursor: None,
+    };
+
+    if let Some(last_slot) = last_slot {
+        subscription.fill_backlog(last_slot).await;
+    }
+
+    stream::unfold(subscription, |mut subscription| async move {
+        subscription.next().await.map(|stream_event| (stream_event, subscription))
+    })
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,163 @@
+use crate::{
+    config::Config,
+    events::{subscribe, StreamEvent},
+    query::TransactionsQuery,
+    slot::unit_tests::{create_mock_client, MockRpcSender},
+    solana::SignedUsdcTransactionsBySlot,
+    state::AppState,
+    test_data::{get_slot, get_transaction},
+    EVENT_CHANNEL_CAPACITY, MAX_PAGE_LIMIT,
+};
+
+use futures::StreamExt;
+use std::{collections::HashMap, time::Duration};
+use tokio::time::timeout;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn get_state() -> AppState {
+    AppState::new(Config::default(), create_mock_client(MockRpcSender::new(HashMap::new())), None)
+}
+
+// A slot of transfers from Source to each of the destinations
+fn get_slot_to(slot: u64, destinations: &[&str]) -> SignedUsdcTransactionsBySlot {
+    get_slot(
+        slot,
+        destinations.iter().map(|destination| get_transaction("USDC", "Source", destination, slot)).collect(),
+    )
+}
+
+// The number of the slot sent, if any
+fn get_slot_number(stream_event: StreamEvent) -> Option<u64> {
+    match stream_event {
+        StreamEvent::Slot(txns_by_slot) => Some(txns_by_slot.slot),
+        StreamEvent::Lagged { .. } => None,
+    }
+}
+
+// Store the slot, then publish it, as the monitor does
+async fn detect(state: &AppState, txns_by_slot: SignedUsdcTransactionsBySlot) {
+    if state.record_slot(Ok((txns_by_slot.clone(), Default::default(), Default::default()))).await.is_ok() {
+        state.publish(txns_by_slot);
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_01_should_replay_stored_slots_before_live_ones() -> Result<(), String> {
+    let state = get_state();
+
+    for slot in 1..=4 {
+        detect(&state, get_slot_to(slot, &["Destination"])).await;
+    }
+
+    let events = subscribe(state.clone(), TransactionsQuery::default(), Some(2)).await;
+
+    // Slot 4 has already been replayed from the store, so must not be sent twice
+    state.publish(get_slot_to(4, &["Destination"]));
+    detect(&state, get_slot_to(5, &["Destination"])).await;
+
+    let slots: Vec<u64> = timeout(
+        Duration::from_secs(5),
+        events.filter_map(|stream_event| async move { get_slot_number(stream_event) }).take(3).collect(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if slots == [3, 4, 5] {
+        Ok(())
+    } else {
+        Err(format!("Expected slots [3, 4, 5], got {:?}", slots))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_02_should_send_only_matching_transfers() -> Result<(), String> {
+    let state = get_state();
+    let query = TransactionsQuery {
+        address: Some("Bob".to_string()),
+        ..TransactionsQuery::default()
+    };
+    let mut events = Box::pin(subscribe(state.clone(), query, None).await);
+
+    detect(&state, get_slot_to(1, &["Alice"])).await;
+    detect(&state, get_slot_to(2, &["Alice", "Bob"])).await;
+
+    match timeout(Duration::from_secs(5), events.next()).await {
+        Ok(Some(StreamEvent::Slot(txns_by_slot))) if txns_by_slot.slot == 2 && txns_by_slot.transfer_count() == 1 => {
+            Ok(())
+        }
+        other => Err(format!("Expected only the transfer to Bob in slot 2, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_03_should_catch_up_from_store_when_lagging() -> Result<(), String> {
+    let state = get_state();
+    let slot_count = EVENT_CHANNEL_CAPACITY as u64 + 10;
+    let mut events = Box::pin(subscribe(state.clone(), TransactionsQuery::default(), None).await);
+
+    // Receive one slot, then fall behind by more than the channel can hold
+    detect(&state, get_slot_to(1, &["Destination"])).await;
+    let first = timeout(Duration::from_secs(5), events.next()).await.map_err(|e| e.to_string())?;
+
+    for slot in 2..=slot_count {
+        detect(&state, get_slot_to(slot, &["Destination"])).await;
+    }
+
+    let rest: Vec<Option<u64>> = timeout(
+        Duration::from_secs(5),
+        events.map(get_slot_number).take(slot_count as usize - 1).collect(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    match first.and_then(get_slot_number) {
+        Some(1) if rest == (2..=slot_count).map(Some).collect::<Vec<Option<u64>>>() => Ok(()),
+        _ => Err(format!("Slots missed or repeated while catching up: {:?}", rest)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_04_should_report_slots_missed_before_any_was_received() -> Result<(), String> {
+    let state = get_state();
+    let slot_count = EVENT_CHANNEL_CAPACITY as u64 + 10;
+    let mut events = Box::pin(subscribe(state.clone(), TransactionsQuery::default(), None).await);
+
+    // Fall behind by more than the channel can hold before receiving anything, so there is no slot to replay from
+    for slot in 1..=slot_count {
+        detect(&state, get_slot_to(slot, &["Destination"])).await;
+    }
+
+    match timeout(Duration::from_secs(5), events.next()).await {
+        Ok(Some(StreamEvent::Lagged { missed_slots: 10 })) => Ok(()),
+        other => Err(format!("Expected notice of 10 missed slots, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_05_should_replay_more_than_one_page_of_stored_slots() -> Result<(), String> {
+    let state = get_state();
+    let slot_count = MAX_PAGE_LIMIT as u64 * 2 + 10;
+
+    for slot in 1..=slot_count {
+        detect(&state, get_slot_to(slot, &["Destination"])).await;
+    }
+
+    let events = subscribe(state.clone(), TransactionsQuery::default(), Some(0)).await;
+    let slots: Vec<Option<u64>> = timeout(
+        Duration::from_secs(5),
+        events.map(get_slot_number).take(slot_count as usize).collect(),
+    )
+    .await
+    .map_err(|e| e.to_string())?;
+
+    if slots == (1..=slot_count).map(Some).collect::<Vec<Option<u64>>>() {
+        Ok(())
+    } else {
+        Err(format!("Slots missed or repeated while replaying: {:?}", slots))
+    }
+}
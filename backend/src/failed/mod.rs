@@ -0,0 +1,178 @@
+use crate::{
+    config::Config,
+    page,
+    query::TransferFilter,
+    solana::{FailedTransaction, SignedUsdcTransactionsBySlot},
+};
+
+use serde::{Deserialize, Serialize};
+use solana_instruction::error::InstructionError;
+use solana_transaction_error::TransactionError;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedTransaction, EncodedTransactionWithStatusMeta, UiInstruction,
+    UiMessage, UiParsedInstruction, UiParsedMessage, UiRawMessage, UiTransaction,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Custom errors of the SPL Token program, by code.  Token-2022 raises the same errors under the same codes, and adds
+// its own after them, which are left undecoded
+const TOKEN_ERRORS: &[&str] = &[
+    "not_rent_exempt",
+    "insufficient_funds",
+    "invalid_mint",
+    "mint_mismatch",
+    "owner_mismatch",
+    "fixed_supply",
+    "already_in_use",
+    "invalid_number_of_provided_signers",
+    "invalid_number_of_required_signers",
+    "uninitialized_state",
+    "native_not_supported",
+    "non_native_has_balance",
+    "invalid_instruction",
+    "invalid_state",
+    "overflow",
+    "authority_type_not_supported",
+    "mint_cannot_freeze",
+    "account_frozen",
+    "mint_decimals_mismatch",
+    "non_native_not_supported",
+];
+
+// The program of a top-level instruction.  Programs are never loaded from an address lookup table, so are always among
+// the static account keys
+fn get_instruction_program(txn: &EncodedTransactionWithStatusMeta, instruction_index: u8) -> Option<String> {
+    let EncodedTransaction::Json(UiTransaction { message, .. }) = &txn.transaction else {
+        return None;
+    };
+
+    match message {
+        UiMessage::Parsed(UiParsedMessage {
+            account_keys,
+            instructions,
+            ..
+        }) => match instructions.get(instruction_index as usize)? {
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) => Some(instruction.program_id.clone()),
+            UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) => {
+                Some(instruction.program_id.clone())
+            }
+            UiInstruction::Compiled(instruction) => account_keys
+                .get(instruction.program_id_index as usize)
+                .map(|account_key| account_key.pubkey.clone()),
+        },
+        UiMessage::Raw(UiRawMessage {
+            account_keys,
+            instructions,
+            ..
+        }) => account_keys.get(instructions.get(instruction_index as usize)?.program_id_index as usize).cloned(),
+    }
+}
+
+// The program that raised the error of a failed instruction.  The first "Program <id> failed" log line is written by
+// the innermost program, which is the one that raised it, even when it was invoked via CPI.  Without logs, or if they
+// were truncated before that line, the best guess is the program of the top-level instruction
+fn get_failed_program(txn: &EncodedTransactionWithStatusMeta, instruction_index: u8) -> Option<String> {
+    let logged_program = match txn.meta.as_ref().map(|meta| &meta.log_messages) {
+        Some(OptionSerializer::Some(log_messages)) => log_messages.iter().find_map(|log_message| {
+            let (program_id, _) = log_message.strip_prefix("Program ")?.split_once(" failed: ")?;
+
+            Some(program_id.to_string())
+        }),
+        _ => None,
+    };
+
+    logged_program.or_else(|| get_instruction_program(txn, instruction_index))
+}
+
+// Name the custom error with which one of the token programs failed the transaction, e.g. insufficient_funds.  Custom
+// error codes mean something different to every program, so those raised by any other program are not decoded
+pub fn get_token_error(
+    txn: &EncodedTransactionWithStatusMeta,
+    error: &TransactionError,
+    config: &Config,
+) -> Option<String> {
+    let TransactionError::InstructionError(instruction_index, InstructionError::Custom(code)) = error else {
+        return None;
+    };
+
+    get_failed_program(txn, *instruction_index)
+        .filter(|program_id| config.is_token_program(program_id))
+        .and_then(|_| TOKEN_ERRORS.get(*code as usize))
+        .map(|name| name.to_string())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Query parameters of /failed_transactions.  A transaction matches if any of the transfers it attempted does.  Slot
+// bounds are inclusive
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct FailedTransactionsQuery {
+    pub symbol: Option<String>,
+    // Token account from or to which a transfer was attempted
+    pub address: Option<String>,
+    // Wallet owning either of those token accounts
+    pub owner: Option<String>,
+    // e.g. insufficient_funds
+    pub token_error: Option<String>,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+    // Maximum number of transactions to return
+    pub limit: Option<usize>,
+}
+
+impl FailedTransactionsQuery {
+    pub fn get_limit(&self) -> usize {
+        page::get_limit(self.limit)
+    }
+
+    pub fn matches(&self, failed_txn: &FailedTransaction) -> bool {
+        let slot = failed_txn.transaction.slot;
+        let filter = TransferFilter {
+            symbols: self.symbol.as_slice(),
+            addresses: self.address.as_slice(),
+            owners: self.owner.as_slice(),
+            ..TransferFilter::default()
+        };
+
+        self.token_error
+            .as_ref()
+            .is_none_or(|token_error| failed_txn.token_error.as_ref() == Some(token_error))
+            && self.from_slot.is_none_or(|from_slot| slot >= from_slot)
+            && self.to_slot.is_none_or(|to_slot| slot <= to_slot)
+            && failed_txn.transaction.transfers.iter().any(|transfer| filter.matches(transfer))
+    }
+}
+
+// The earliest matching failed transactions, in execution order.  If there are more than the limit, narrow the slot
+// range to fetch the rest
+#[derive(Clone, Debug, Serialize)]
+pub struct FailedTransactions {
+    pub transactions: Vec<FailedTransaction>,
+    pub has_more: bool,
+}
+
+impl FailedTransactions {
+    // Build the response from up to one more than the limit of matching transactions
+    pub fn new(query: &FailedTransactionsQuery, transactions: Vec<FailedTransaction>) -> Self {
+        let (transactions, has_more) = page::take_page(transactions, query.get_limit());
+
+        FailedTransactions { transactions, has_more }
+    }
+}
+
+pub fn find_failed_transactions<'a>(
+    query: &FailedTransactionsQuery,
+    slots: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>,
+) -> FailedTransactions {
+    let transactions = slots
+        .flat_map(|txns_by_slot| txns_by_slot.failed_transactions.iter())
+        .filter(|failed_txn| query.matches(failed_txn))
+        .take(page::get_fetch_limit(query.get_limit()))
+        .cloned()
+        .collect();
+
+    FailedTransactions::new(query, transactions)
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,198 @@
+use crate::{
+    config::Config,
+    failed::{find_failed_transactions, get_token_error, FailedTransactionsQuery},
+    instruction::test_data::get_parsed_ui_instruction_for_program,
+    solana::{FailedTransaction, SignedUsdcTransactionsBySlot, TransactionStatus, UsdcTransaction},
+    test_data::get_transfer,
+    transaction::unit_tests::{get_ui_transaction, get_ui_txn_status_meta, get_v0_txn},
+    TOKEN_PROGRAM_ID,
+};
+
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, EncodedTransaction, EncodedTransactionWithStatusMeta, UiMessage,
+};
+use std::sync::Arc;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+const AGGREGATOR_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+
+// A failed transaction whose only top-level instruction belongs to program_id
+fn get_failed_txn(program_id: &str, log_messages: Option<Vec<String>>) -> EncodedTransactionWithStatusMeta {
+    let mut transaction = get_ui_transaction(true);
+    let mut meta = get_ui_txn_status_meta(true);
+
+    if let UiMessage::Parsed(ref mut message) = transaction.message {
+        message.instructions = vec![get_parsed_ui_instruction_for_program(1, program_id)];
+    }
+
+    meta.log_messages = OptionSerializer::from(log_messages);
+
+    EncodedTransactionWithStatusMeta {
+        transaction: EncodedTransaction::Json(transaction),
+        meta: Some(meta),
+        version: None,
+    }
+}
+
+fn custom_error(code: u32) -> TransactionError {
+    TransactionError::InstructionError(0, InstructionError::Custom(code))
+}
+
+// A failed transaction attempting transfers of the given symbols to the given destinations
+fn get_attempted_transfers(signature: &str, transfers: &[(&str, &str)]) -> UsdcTransaction {
+    UsdcTransaction {
+        signature: signature.to_string(),
+        signatures: Arc::from([signature.to_string()]),
+        status: TransactionStatus::Failed,
+        transfers: transfers.iter().map(|(symbol, to)| get_transfer(symbol, "Treasury", to, 1000000)).collect(),
+        ..UsdcTransaction::default()
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_decode_token_error_raised_via_cpi() -> Result<(), String> {
+    let config = Config::default();
+    let logs = |failed_programs: &[&str]| {
+        let mut log_messages = vec![format!("Program {} invoke [1]", AGGREGATOR_PROGRAM_ID)];
+
+        for program_id in failed_programs {
+            log_messages.push(format!("Program {} failed: custom program error: 0x1", program_id));
+        }
+
+        Some(log_messages)
+    };
+    let raised_by_token_program =
+        get_failed_txn(AGGREGATOR_PROGRAM_ID, logs(&[TOKEN_PROGRAM_ID, AGGREGATOR_PROGRAM_ID]));
+    let raised_by_aggregator = get_failed_txn(TOKEN_PROGRAM_ID, logs(&[AGGREGATOR_PROGRAM_ID]));
+
+    match (
+        get_token_error(&raised_by_token_program, &custom_error(1), &config),
+        get_token_error(&raised_by_aggregator, &custom_error(1), &config),
+    ) {
+        (Some(token_error), None) if token_error == "insufficient_funds" => Ok(()),
+        other => Err(format!("Wrong token errors {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_fall_back_to_program_of_failed_instruction() -> Result<(), String> {
+    let config = Config::default();
+    let token_txn = get_failed_txn(TOKEN_PROGRAM_ID, None);
+    let aggregator_txn = get_failed_txn(AGGREGATOR_PROGRAM_ID, None);
+
+    match (
+        get_token_error(&token_txn, &custom_error(17), &config),
+        get_token_error(&token_txn, &custom_error(999), &config),
+        get_token_error(&token_txn, &TransactionError::InsufficientFundsForFee, &config),
+        get_token_error(&aggregator_txn, &custom_error(17), &config),
+    ) {
+        (Some(token_error), None, None, None) if token_error == "account_frozen" => Ok(()),
+        other => Err(format!("Wrong token errors {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_filter_and_limit_failed_transactions() -> Result<(), String> {
+    // The first transaction of slot 1 attempted two transfers
+    let slots: Vec<SignedUsdcTransactionsBySlot> = [
+        (
+            1,
+            vec![
+                (
+                    get_attempted_transfers("First", &[("USDC", "Customer"), ("USDT", "Supplier")]),
+                    custom_error(1),
+                    Some("insufficient_funds".to_string()),
+                ),
+                (get_attempted_transfers("Second", &[("USDC", "Supplier")]), custom_error(17), None),
+            ],
+        ),
+        (
+            2,
+            vec![(
+                get_attempted_transfers("Third", &[("USDC", "Customer")]),
+                custom_error(1),
+                Some("insufficient_funds".to_string()),
+            )],
+        ),
+    ]
+    .into_iter()
+    .map(|(slot, attempts)| SignedUsdcTransactionsBySlot {
+        slot,
+        failed_transactions: attempts
+            .into_iter()
+            .enumerate()
+            .map(|(position, (transaction, error, token_error))| {
+                let transaction = UsdcTransaction { slot, position: position as u32, ..transaction };
+
+                FailedTransaction::new(transaction, error, token_error)
+            })
+            .collect(),
+        ..SignedUsdcTransactionsBySlot::default()
+    })
+    .collect();
+
+    let all = find_failed_transactions(&FailedTransactionsQuery::default(), slots.iter());
+    let supplier = find_failed_transactions(
+        &FailedTransactionsQuery {
+            address: Some("Supplier".to_string()),
+            symbol: Some("usdt".to_string()),
+            ..FailedTransactionsQuery::default()
+        },
+        slots.iter(),
+    );
+    let insufficient_funds = find_failed_transactions(
+        &FailedTransactionsQuery {
+            token_error: Some("insufficient_funds".to_string()),
+            limit: Some(1),
+            ..FailedTransactionsQuery::default()
+        },
+        slots.iter(),
+    );
+    let get_signatures = |transactions: &[FailedTransaction]| -> Vec<String> {
+        transactions.iter().map(|failed_txn| failed_txn.transaction.signature.clone()).collect()
+    };
+
+    if all.transactions.iter().any(|failed_txn| failed_txn.transaction.status != TransactionStatus::Failed) {
+        Err("Failed transactions should have a status of failed".to_string())
+    } else if all.transactions.first().map(|failed_txn| failed_txn.transaction.transfers.len()) != Some(2) {
+        Err("The transfers of a transaction should be grouped together".to_string())
+    } else if get_signatures(&all.transactions) != ["First", "Second", "Third"] {
+        Err(format!("Wrong failed transactions {:?}", get_signatures(&all.transactions)))
+    } else if get_signatures(&supplier.transactions) != ["First"] {
+        Err(format!("Wrong failed transactions {:?}", get_signatures(&supplier.transactions)))
+    } else if get_signatures(&insufficient_funds.transactions) != ["First"] || !insufficient_funds.has_more {
+        Err(format!("Wrong failed transactions {:?}", get_signatures(&insufficient_funds.transactions)))
+    } else {
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_fall_back_when_logs_name_no_failed_program() -> Result<(), String> {
+    let config = Config::default();
+    // The logs were truncated before the failure was written
+    let truncated_logs = Some(vec![format!("Program {} invoke [1]", TOKEN_PROGRAM_ID), "Log truncated".to_string()]);
+    let token_txn = get_failed_txn(TOKEN_PROGRAM_ID, truncated_logs);
+    // Its instructions are compiled, the first belonging to the aggregator and the second to the token program
+    let compiled_txn = get_v0_txn(false);
+    let compiled_error =
+        |instruction_index| TransactionError::InstructionError(instruction_index, InstructionError::Custom(1));
+
+    match (
+        get_token_error(&token_txn, &custom_error(17), &config),
+        get_token_error(&compiled_txn, &compiled_error(0), &config),
+        get_token_error(&compiled_txn, &compiled_error(1), &config),
+    ) {
+        (Some(frozen), None, Some(insufficient))
+            if frozen == "account_frozen" && insufficient == "insufficient_funds" =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Wrong token errors {:?}", other)),
+    }
+}
@@ -1,94 +1,294 @@
-use crate::{solana::UsdcTransaction, TOKEN_PROGRAM_ID, USDC_MINT};
+use crate::{
+    config::{Config, MonitoredMint},
+    parse_error::ParseError,
+    solana::{format_ui_amount, UsdcEvent, UsdcTransfer},
+    token_account::{TokenAccount, TokenAccounts},
+};
 use serde_json::{Map, Value};
+use solana_message::{compiled_instruction::CompiledInstruction, AccountKeys};
+use solana_pubkey::Pubkey;
 use solana_transaction_status::{
-    parse_instruction::ParsedInstruction, UiInstruction, UiParsedInstruction,
+    parse_instruction::{parse, ParsedInstruction},
+    UiCompiledInstruction, UiInstruction, UiParsedInstruction, UiPartiallyDecodedInstruction,
 };
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 fn get_prop_as_str<'a>(obj: &'a Map<String, Value>, property: &'a str) -> Option<&'a str> {
     obj.get(property).and_then(|v| v.as_str())
 }
-fn get_prop_as_f64(obj: &Map<String, Value>, property: &str) -> Option<f64> {
-    obj.get(property).and_then(|v| v.as_f64())
+fn get_prop_as_u8(obj: &Map<String, Value>, property: &str) -> Option<u8> {
+    obj.get(property)
+        .and_then(|v| v.as_u64())
+        .and_then(|v| u8::try_from(v).ok())
 }
-fn get_prop_as_str_then_f64(obj: &Map<String, Value>, property: &str) -> Option<f64> {
-    get_prop_as_str(obj, property).map(|s| s.parse::<f64>().unwrap())
+fn get_prop_as_str_then_u64(obj: &Map<String, Value>, property: &str) -> Option<Result<u64, ParseError>> {
+    get_prop_as_str(obj, property).map(|s| s.parse::<u64>().map_err(|_| ParseError::BadAmount(s.to_string())))
 }
 
-// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-// This function will bail out at the earliest opportunity
-pub fn process_instruction(instruction: &UiInstruction) -> Option<UsdcTransaction> {
-    let no_mint = "mint missing";
+// Some fields are reported under a different name when held by a multisig account
+fn get_first_prop<'a>(obj: &'a Map<String, Value>, properties: &[&'static str]) -> Result<&'a str, ParseError> {
+    properties
+        .iter()
+        .find_map(|property| get_prop_as_str(obj, property))
+        .ok_or(ParseError::MissingField(properties[0]))
+}
 
-    match instruction {
+// The type and info of an instruction parsed by the RPC node, provided it belongs to one of the token programs
+fn get_parsed_info<'a>(
+    instruction: &'a UiInstruction,
+    config: &Config,
+) -> Result<(&'a str, &'a Map<String, Value>), ParseError> {
+    let (program_id, parsed) = match instruction {
         UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
             program_id,
             parsed,
             ..
-        })) => {
-            if !program_id.eq(TOKEN_PROGRAM_ID) {
-                return None;
-            }
+        })) => (program_id, parsed),
+        // The RPC node could not parse this instruction, so we cannot either
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(UiPartiallyDecodedInstruction {
+            program_id,
+            ..
+        })) if !config.is_token_program(program_id) => return Err(ParseError::WrongProgram(program_id.clone())),
+        _ => return Err(ParseError::UnsupportedEncoding),
+    };
 
-            if let Some(info) = parsed
-                .as_object()
-                .and_then(|parsed_obj| parsed_obj.get("info"))
-                .and_then(|info| info.as_object())
-            {
-                if !get_prop_as_str(info, "mint")
-                    .unwrap_or(&no_mint)
-                    .eq(USDC_MINT)
-                {
-                    return None;
-                }
+    if !config.is_token_program(program_id) {
+        return Err(ParseError::WrongProgram(program_id.clone()));
+    }
+
+    let parsed_obj = parsed.as_object().ok_or(ParseError::UnsupportedEncoding)?;
+    let instruction_type = get_prop_as_str(parsed_obj, "type").ok_or(ParseError::MissingField("type"))?;
+    let info = parsed_obj
+        .get("info")
+        .and_then(|info| info.as_object())
+        .ok_or(ParseError::MissingField("info"))?;
+
+    Ok((instruction_type, info))
+}
+
+// The amount and decimals of an instruction that either names them (the checked variants) or leaves the decimals to be
+// found from the token balances or the configuration
+fn get_amount(
+    info: &Map<String, Value>,
+    token_account: Option<&TokenAccount>,
+    monitored_mint: &MonitoredMint,
+) -> Result<(u64, u8), ParseError> {
+    // If info.amount is missing, then look for tokenAmount.amount
+    if let Some(amt) = get_prop_as_str_then_u64(info, "amount") {
+        // If decimal places are not specified, take them from the token balances, else from the configuration
+        let decimals = get_prop_as_u8(info, "decimals")
+            .or(token_account.map(|acc| acc.decimals))
+            .unwrap_or(monitored_mint.decimals);
+
+        Ok((amt?, decimals))
+    } else {
+        let token_amount = info
+            .get("tokenAmount")
+            .and_then(|tkn_amt| tkn_amt.as_object())
+            .ok_or(ParseError::MissingField("tokenAmount"))?;
+
+        Ok((
+            get_prop_as_str_then_u64(token_amount, "amount").ok_or(ParseError::MissingField("tokenAmount.amount"))??,
+            get_prop_as_u8(token_amount, "decimals").ok_or(ParseError::MissingField("tokenAmount.decimals"))?,
+        ))
+    }
+}
+
+// The monitored mint an instruction acts upon.  Instructions that do not name the mint are matched through the token
+// balances of the account they act upon
+fn get_monitored_mint<'a>(
+    info: &Map<String, Value>,
+    token_account: Option<&TokenAccount>,
+    config: &'a Config,
+) -> Result<&'a MonitoredMint, ParseError> {
+    let mint = get_prop_as_str(info, "mint")
+        .or(token_account.map(|acc| acc.mint.as_str()))
+        .ok_or(ParseError::MissingField("mint"))?;
+
+    config.get_mint(mint).ok_or_else(|| ParseError::WrongMint(mint.to_string()))
+}
+
+fn process_transfer<'a>(
+    info: &Map<String, Value>,
+    token_accounts: &TokenAccounts,
+    config: &'a Config,
+) -> Result<(UsdcEvent, &'a MonitoredMint), ParseError> {
+    let source = get_prop_as_str(info, "source").ok_or(ParseError::MissingField("source"))?;
+    let destination = get_prop_as_str(info, "destination").ok_or(ParseError::MissingField("destination"))?;
+
+    // A plain transfer (as opposed to transferChecked) does not name the mint, so look it up from the token
+    // balances of either the source or destination account
+    let token_account: Option<&TokenAccount> = token_accounts
+        .get(source)
+        .or_else(|| token_accounts.get(destination));
+    let monitored_mint = get_monitored_mint(info, token_account, config)?;
+    let (amount, decimals) = get_amount(info, token_account, monitored_mint)?;
+
+    // Token-2022 transferCheckedWithFee withholds part of the amount in the destination account.  The fee is expressed
+    // in the same base units as the amount
+    let fee = match info.get("feeAmount").and_then(|fee_amt| fee_amt.as_object()) {
+        Some(fee_amount) => {
+            Some(get_prop_as_str_then_u64(fee_amount, "amount").ok_or(ParseError::MissingField("feeAmount.amount"))??)
+        }
+        None => None,
+    };
 
-                let source = if let Some(src) = get_prop_as_str(info, "source") {
-                    src
-                } else {
-                    return None;
-                };
-                let destination = if let Some(dest) = get_prop_as_str(info, "destination") {
-                    dest
-                } else {
-                    return None;
-                };
-
-                // If info.amount is missing, then look for tokenAmount.uiAmount
-                let amount = if let Some(amt) = get_prop_as_str_then_f64(info,"amount") {
-                    let exp = if let Some(decimals) = get_prop_as_f64(info,"decimals") {
-                        decimals
-                    } else {
-                        // If decimal places are not specified, then assume 6
-                        6_f64
-                    };
-                    
-                    amt / 10_f64.powf(exp)
-                } else {
-                    if let Some(mut token_amount) = info
-                        .get("tokenAmount")
-                        .and_then(|tkn_amt| tkn_amt.as_object())
-                    {
-                        if let Some(ui_amount) = get_prop_as_f64(&mut token_amount,"uiAmount") {
-                            ui_amount
-                        } else {
-                            return None;
-                        }
-                    } else {
-                        return None;
-                    }
-                };
-
-                return Some(UsdcTransaction {
-                    from: source.to_string(),
-                    to: destination.to_string(),
+    // Any owner missing from the token balances is looked up later, with the account's address
+    let get_owner = |address: &str| token_accounts.get(address).and_then(|acc| acc.owner.clone());
+
+    Ok((
+        UsdcEvent::Transfer(UsdcTransfer {
+            from: source.to_string(),
+            to: destination.to_string(),
+            from_owner: get_owner(source),
+            to_owner: get_owner(destination),
+            amount,
+            decimals,
+            ui_amount: format_ui_amount(amount, decimals),
+            fee,
+            ..Default::default()
+        }),
+        monitored_mint,
+    ))
+}
+
+// Every other instruction acts upon a single account, which approve and revoke call the source.  When setting the
+// authority of a mint, rather than of a token account, the mint itself is acted upon
+fn process_account_instruction<'a>(
+    instruction_type: &str,
+    info: &Map<String, Value>,
+    token_accounts: &TokenAccounts,
+    config: &'a Config,
+) -> Result<(UsdcEvent, &'a MonitoredMint), ParseError> {
+    let account_fields: &[&'static str] = match instruction_type {
+        "approve" | "approveChecked" | "revoke" => &["source"],
+        "setAuthority" => &["account", "mint"],
+        _ => &["account"],
+    };
+    let account = get_first_prop(info, account_fields)?.to_string();
+    let token_account = token_accounts.get(&account);
+    let monitored_mint = get_monitored_mint(info, token_account, config)?;
+    let get_owner = || get_first_prop(info, &["owner", "multisigOwner"]).map(str::to_string);
+    let get_freeze_authority =
+        || get_first_prop(info, &["freezeAuthority", "multisigFreezeAuthority"]).map(str::to_string);
+
+    let event = match instruction_type {
+        "mintTo" | "mintToChecked" | "burn" | "burnChecked" => {
+            let (amount, decimals) = get_amount(info, token_account, monitored_mint)?;
+            let ui_amount = format_ui_amount(amount, decimals);
+
+            if instruction_type.starts_with("mint") {
+                UsdcEvent::Mint {
+                    account,
+                    authority: get_first_prop(info, &["mintAuthority", "multisigMintAuthority"])?.to_string(),
                     amount,
-                });
+                    decimals,
+                    ui_amount,
+                }
+            } else {
+                UsdcEvent::Burn {
+                    account,
+                    authority: get_first_prop(info, &["authority", "multisigAuthority"])?.to_string(),
+                    amount,
+                    decimals,
+                    ui_amount,
+                }
+            }
+        }
+        "approve" | "approveChecked" => {
+            let (amount, decimals) = get_amount(info, token_account, monitored_mint)?;
+
+            UsdcEvent::Approve {
+                account,
+                delegate: get_first_prop(info, &["delegate"])?.to_string(),
+                owner: get_owner()?,
+                amount,
+                decimals,
+                ui_amount: format_ui_amount(amount, decimals),
             }
         }
-        _ => {}
+        "revoke" => UsdcEvent::Revoke {
+            account,
+            owner: get_owner()?,
+        },
+        "closeAccount" => UsdcEvent::CloseAccount {
+            account,
+            destination: get_first_prop(info, &["destination"])?.to_string(),
+            owner: get_owner()?,
+        },
+        "freezeAccount" => UsdcEvent::FreezeAccount {
+            account,
+            freeze_authority: get_freeze_authority()?,
+        },
+        "thawAccount" => UsdcEvent::ThawAccount {
+            account,
+            freeze_authority: get_freeze_authority()?,
+        },
+        "setAuthority" => UsdcEvent::SetAuthority {
+            account,
+            authority_type: get_first_prop(info, &["authorityType"])?.to_string(),
+            new_authority: get_prop_as_str(info, "newAuthority").map(str::to_string),
+            authority: get_first_prop(info, &["authority", "multisigAuthority"])?.to_string(),
+        },
+        _ => return Err(ParseError::UnsupportedInstruction(instruction_type.to_string())),
+    };
+
+    Ok((event, monitored_mint))
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Parse an instruction of one of the token programs that the RPC node left compiled, exactly as the node would have
+// parsed it.  This is the case for every instruction of a transaction that was not requested as jsonParsed.  The
+// instruction refers to its program and accounts by their index in the account keys, which must therefore include any
+// keys loaded from address lookup tables
+pub fn decode_compiled_instruction(
+    instruction: &UiCompiledInstruction,
+    account_keys: &[Pubkey],
+    config: &Config,
+) -> Result<UiInstruction, ParseError> {
+    let program_id = account_keys
+        .get(instruction.program_id_index as usize)
+        .ok_or(ParseError::UnsupportedEncoding)?;
+
+    if !config.is_token_program(&program_id.to_string()) {
+        return Err(ParseError::WrongProgram(program_id.to_string()));
     }
 
-    None
+    let compiled_instruction = CompiledInstruction {
+        program_id_index: instruction.program_id_index,
+        accounts: instruction.accounts.clone(),
+        data: bs58::decode(&instruction.data)
+            .into_vec()
+            .map_err(|_| ParseError::UnsupportedEncoding)?,
+    };
+    let parsed = parse(
+        program_id,
+        &compiled_instruction,
+        &AccountKeys::new(account_keys, None),
+        instruction.stack_height,
+    )
+    .map_err(|_| ParseError::UnsupportedEncoding)?;
+
+    Ok(UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed)))
+}
+
+// This function will bail out at the earliest opportunity.  The instruction index and stack height of a transfer are
+// left for the caller to fill in
+pub fn process_instruction<'a>(
+    instruction: &UiInstruction,
+    token_accounts: &TokenAccounts,
+    config: &'a Config,
+) -> Result<(UsdcEvent, &'a MonitoredMint), ParseError> {
+    let (instruction_type, info) = get_parsed_info(instruction, config)?;
+
+    match instruction_type {
+        "transfer" | "transferChecked" | "transferCheckedWithFee" => process_transfer(info, token_accounts, config),
+        "mintTo" | "mintToChecked" | "burn" | "burnChecked" | "approve" | "approveChecked" | "revoke"
+        | "closeAccount" | "freezeAccount" | "thawAccount" | "setAuthority" => {
+            process_account_instruction(instruction_type, info, token_accounts, config)
+        }
+        _ => Err(ParseError::UnsupportedInstruction(instruction_type.to_string())),
+    }
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
@@ -1,14 +1,21 @@
-use crate::*;
+use crate::{token_account::{TokenAccount, TokenAccounts}, *};
 use serde_json::{json, Value};
-use solana_transaction_status::{parse_instruction::ParsedInstruction, UiInstruction, UiParsedInstruction};
+use solana_transaction_status::{
+    parse_instruction::ParsedInstruction, UiCompiledInstruction, UiInstruction, UiParsedInstruction,
+};
 use std::collections::HashMap;
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 pub const AUTHORITY: &str = "Because I said so...";
 pub const SOURCE: &str = "The sender";
 pub const DESTINATION: &str = "The receiver";
+pub const DELEGATE: &str = "The spender";
 pub const AMOUNT_STR: &str = "1470000";
+pub const AMOUNT_U64: u64 = 1470000;
 pub const AMOUNT_F64: f64 = 1.470;
+pub const AMOUNT_UI_STR: &str = "1.47";
+pub const BAD_AMOUNT_STR: &str = "1.47e6";
+pub const FEE_U64: u64 = 1470;
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 // Info contains amount and decimals
@@ -134,8 +141,8 @@ fn test_data_missing_token_amount() -> Value {
     })
 }
 
-// Missing tokenAmount.uiAmount property
-fn test_data_missing_token_amount_ui_amount() -> Value {
+// Missing tokenAmount.amount property
+fn test_data_missing_token_amount_amount() -> Value {
     json!({
         "info": {
             "authority": AUTHORITY,
@@ -143,8 +150,8 @@ fn test_data_missing_token_amount_ui_amount() -> Value {
             "source": SOURCE,
             "mint": USDC_MINT,
             "tokenAmount": {
-                "amount": "1470000000",
                 "decimals": 9,
+                "uiAmount": AMOUNT_F64,
                 "uiAmountString": "1.47"
             }
         },
@@ -152,6 +159,220 @@ fn test_data_missing_token_amount_ui_amount() -> Value {
     })
 }
 
+// Plain transfer: info contains neither mint nor decimals
+fn test_data_plain_transfer() -> Value {
+    json!({
+        "info": {
+            "authority": AUTHORITY,
+            "destination": DESTINATION,
+            "source": SOURCE,
+            "amount": AMOUNT_STR,
+        },
+        "type": "transfer"
+    })
+}
+
+// Amount is not an integer number of base units
+fn test_data_bad_amount() -> Value {
+    json!({
+        "info": {
+            "authority": AUTHORITY,
+            "destination": DESTINATION,
+            "source": SOURCE,
+            "mint": USDC_MINT,
+            "amount": BAD_AMOUNT_STR,
+            "decimals": 6,
+        },
+        "type": "transferChecked"
+    })
+}
+
+// Token instruction that does not transfer tokens
+fn test_data_initialize_account() -> Value {
+    json!({
+        "info": {
+            "account": DESTINATION,
+            "mint": USDC_MINT,
+            "owner": AUTHORITY,
+        },
+        "type": "initializeAccount3"
+    })
+}
+
+// Token-2022 transfer with a transfer fee
+fn test_data_transfer_checked_with_fee() -> Value {
+    json!({
+        "info": {
+            "authority": AUTHORITY,
+            "destination": DESTINATION,
+            "source": SOURCE,
+            "mint": USDC_MINT,
+            "tokenAmount": {
+                "amount": AMOUNT_STR,
+                "decimals": 6,
+                "uiAmount": AMOUNT_F64,
+                "uiAmountString": "1.47"
+            },
+            "feeAmount": {
+                "amount": "1470",
+                "decimals": 6,
+                "uiAmount": 0.00147,
+                "uiAmountString": "0.00147"
+            }
+        },
+        "type": "transferCheckedWithFee"
+    })
+}
+
+// Transfer of a stablecoin other than USDC
+fn test_data_usdt_transfer() -> Value {
+    json!({
+        "info": {
+            "authority": AUTHORITY,
+            "destination": DESTINATION,
+            "source": SOURCE,
+            "mint": USDT_MINT,
+            "amount": AMOUNT_STR,
+            "decimals": 6,
+        },
+        "type": "transferChecked"
+    })
+}
+
+// Issue of new USDC into the receiver's account, by a multisig mint authority, without the decimals
+fn test_data_mint_to() -> Value {
+    json!({
+        "info": {
+            "account": DESTINATION,
+            "mint": USDC_MINT,
+            "multisigMintAuthority": AUTHORITY,
+            "signers": [AUTHORITY],
+            "amount": AMOUNT_STR,
+        },
+        "type": "mintTo"
+    })
+}
+
+// Destruction of USDC held in the sender's account
+fn test_data_burn_checked() -> Value {
+    json!({
+        "info": {
+            "account": SOURCE,
+            "authority": AUTHORITY,
+            "mint": USDC_MINT,
+            "tokenAmount": {
+                "amount": AMOUNT_STR,
+                "decimals": 6,
+                "uiAmount": AMOUNT_F64,
+            }
+        },
+        "type": "burnChecked"
+    })
+}
+
+// Delegation of the sender's USDC, without naming the mint
+fn test_data_approve() -> Value {
+    json!({
+        "info": {
+            "source": SOURCE,
+            "delegate": DELEGATE,
+            "owner": AUTHORITY,
+            "amount": AMOUNT_STR,
+        },
+        "type": "approve"
+    })
+}
+
+// Delegation of the sender's USDC by a multisig owner
+fn test_data_approve_checked() -> Value {
+    json!({
+        "info": {
+            "source": SOURCE,
+            "mint": USDC_MINT,
+            "delegate": DELEGATE,
+            "multisigOwner": AUTHORITY,
+            "signers": [AUTHORITY],
+            "tokenAmount": {
+                "amount": AMOUNT_STR,
+                "decimals": 6,
+                "uiAmount": AMOUNT_F64,
+            }
+        },
+        "type": "approveChecked"
+    })
+}
+
+fn test_data_revoke() -> Value {
+    json!({
+        "info": {
+            "source": SOURCE,
+            "owner": AUTHORITY,
+        },
+        "type": "revoke"
+    })
+}
+
+// Closure of the sender's account, with the rent going to the receiver
+fn test_data_close_account() -> Value {
+    json!({
+        "info": {
+            "account": SOURCE,
+            "destination": DESTINATION,
+            "owner": AUTHORITY,
+        },
+        "type": "closeAccount"
+    })
+}
+
+fn test_data_freeze_account() -> Value {
+    json!({
+        "info": {
+            "account": SOURCE,
+            "mint": USDC_MINT,
+            "freezeAuthority": AUTHORITY,
+        },
+        "type": "freezeAccount"
+    })
+}
+
+fn test_data_thaw_account() -> Value {
+    json!({
+        "info": {
+            "account": SOURCE,
+            "mint": USDC_MINT,
+            "multisigFreezeAuthority": AUTHORITY,
+            "signers": [AUTHORITY],
+        },
+        "type": "thawAccount"
+    })
+}
+
+// Transfer of the ownership of the sender's account to the receiver
+fn test_data_set_account_authority() -> Value {
+    json!({
+        "info": {
+            "account": SOURCE,
+            "authorityType": "accountOwner",
+            "newAuthority": DESTINATION,
+            "authority": AUTHORITY,
+        },
+        "type": "setAuthority"
+    })
+}
+
+// Removal of the USDC mint authority, which names the mint rather than a token account
+fn test_data_set_mint_authority() -> Value {
+    json!({
+        "info": {
+            "mint": USDC_MINT,
+            "authorityType": "mintTokens",
+            "newAuthority": null,
+            "authority": AUTHORITY,
+        },
+        "type": "setAuthority"
+    })
+}
+
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 fn get_test_map() -> HashMap<u8, Value> {
     let mut test_map: HashMap<u8, Value> = HashMap::new();
@@ -165,7 +386,22 @@ fn get_test_map() -> HashMap<u8, Value> {
     test_map.insert(7, test_data_missing_destination());
     test_map.insert(8, test_data_missing_mint());
     test_map.insert(9, test_data_missing_token_amount());
-    test_map.insert(10, test_data_missing_token_amount_ui_amount());
+    test_map.insert(10, test_data_missing_token_amount_amount());
+    test_map.insert(11, test_data_plain_transfer());
+    test_map.insert(12, test_data_bad_amount());
+    test_map.insert(13, test_data_initialize_account());
+    test_map.insert(14, test_data_transfer_checked_with_fee());
+    test_map.insert(15, test_data_usdt_transfer());
+    test_map.insert(16, test_data_mint_to());
+    test_map.insert(17, test_data_burn_checked());
+    test_map.insert(18, test_data_approve());
+    test_map.insert(19, test_data_approve_checked());
+    test_map.insert(20, test_data_revoke());
+    test_map.insert(21, test_data_close_account());
+    test_map.insert(22, test_data_freeze_account());
+    test_map.insert(23, test_data_thaw_account());
+    test_map.insert(24, test_data_set_account_authority());
+    test_map.insert(25, test_data_set_mint_authority());
 
     test_map
 }
@@ -180,11 +416,41 @@ fn get_parsed_value_for_test(test_num: u8) -> Value {
     }
 }
 
-pub fn get_parsed_ui_instruction_for_test(test_num: u8, bad_program_id: bool) -> UiInstruction {
+pub fn get_parsed_ui_instruction_for_program(test_num: u8, program_id: &str) -> UiInstruction {
     UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
         parsed: get_parsed_value_for_test(test_num),
         program: "some value".to_string(),
-        program_id: (if bad_program_id { "bad program id" } else { TOKEN_PROGRAM_ID}).to_string(),
+        program_id: program_id.to_string(),
         stack_height: None,
     }))
 }
+
+pub fn get_parsed_ui_instruction_for_test(test_num: u8, bad_program_id: bool) -> UiInstruction {
+    get_parsed_ui_instruction_for_program(test_num, if bad_program_id { "bad program id" } else { TOKEN_PROGRAM_ID })
+}
+
+// Instruction the RPC node did not parse
+pub fn get_compiled_ui_instruction_for_test() -> UiInstruction {
+    UiInstruction::Compiled(UiCompiledInstruction {
+        program_id_index: 0,
+        accounts: vec![1, 2],
+        data: "3Bxs4h24hBtQy9rw".to_string(),
+        stack_height: None,
+    })
+}
+
+// Token account lookup as would be derived from a transaction's pre/post token balances
+pub fn get_token_accounts_for_test(account: &str, mint: &str) -> TokenAccounts {
+    let mut token_accounts = TokenAccounts::new();
+
+    token_accounts.insert(
+        account.to_string(),
+        TokenAccount {
+            mint: mint.to_string(),
+            decimals: 6,
+            owner: None,
+        },
+    );
+
+    token_accounts
+}
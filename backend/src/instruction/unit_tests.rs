@@ -1,12 +1,35 @@
-use crate::instruction::{process_instruction, test_data::*};
+use crate::{
+    config::Config,
+    instruction::{process_instruction, test_data::*},
+    parse_error::ParseError,
+    solana::{format_ui_amount, UsdcEvent, UsdcTransfer},
+    token_account::TokenAccounts,
+    TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID, USDC_MINT,
+};
 
 fn test_for_false_positive(test_num: u8, prop_name: &str) -> Result<(), String> {
-    match process_instruction(&get_parsed_ui_instruction_for_test(test_num, false)) {
-        Some(_) => Err(format!(
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(test_num, false),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok(_) => Err(format!(
             "Should not have parsed instruction. Property '{}' is missing",
             prop_name
         )),
-        None => Ok(()),
+        Err(_) => Ok(()),
+    }
+}
+
+fn test_for_parse_error(test_num: u8, token_accounts: &TokenAccounts, expected: ParseError) -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(test_num, false),
+        token_accounts,
+        &Config::default(),
+    ) {
+        Ok(_) => Err(format!("Should not have parsed instruction. Expected error: {}", expected)),
+        Err(err) if err == expected => Ok(()),
+        Err(err) => Err(format!("Wrong parse error. Expected '{}', got '{}'", expected, err)),
     }
 }
 
@@ -17,20 +40,14 @@ fn false_negative_msg(prop_name: &str) -> String {
     )
 }
 
-fn test_for_false_negative(test_num: u8, prop_name: &str) -> Result<(), String> {
-    match process_instruction(&get_parsed_ui_instruction_for_test(test_num, false)) {
-        Some(_) => Ok(()),
-        None => Err(false_negative_msg(prop_name)),
-    }
-}
-
-fn check_parsed_amount(got: f64, expected: f64) -> Result<(), String> {
-    if got == expected {
+// Every fixture transfers 1.47 USDC, but expresses it with varying numbers of decimal places
+fn check_parsed_amount(got: &UsdcTransfer, amount: u64, decimals: u8) -> Result<(), String> {
+    if got.amount == amount && got.decimals == decimals && got.ui_amount == AMOUNT_UI_STR {
         Ok(())
     } else {
         Err(format!(
-            "Wrong amount in parsed instruction.  Expected {}, got {}",
-            expected, got
+            "Wrong amount in parsed instruction.  Expected {} ({} decimals) = {}, got {} ({} decimals) = {}",
+            amount, decimals, AMOUNT_UI_STR, got.amount, got.decimals, got.ui_amount
         ))
     }
 }
@@ -38,33 +55,52 @@ fn check_parsed_amount(got: f64, expected: f64) -> Result<(), String> {
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
 fn test_01_should_parse_complete_instruction_info_amount_decimals() -> Result<(), String> {
-    match process_instruction(&get_parsed_ui_instruction_for_test(1, false)) {
-        Some(usdc_inst) => check_parsed_amount(usdc_inst.amount, AMOUNT_F64),
-        None => Err(false_negative_msg("info.amount")),
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(1, false),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => check_parsed_amount(&usdc_inst, 14700000, 7),
+        _ => Err(false_negative_msg("info.amount")),
     }
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
 fn test_02_should_parse_complete_instruction_info_amount_no_decimals() -> Result<(), String> {
-    match process_instruction(&get_parsed_ui_instruction_for_test(2, false)) {
-        Some(usdc_inst) => check_parsed_amount(usdc_inst.amount, AMOUNT_F64),
-        None => Err(false_negative_msg("info.amount")),
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(2, false),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => check_parsed_amount(&usdc_inst, AMOUNT_U64, 6),
+        _ => Err(false_negative_msg("info.amount")),
     }
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
-fn test_03_should_parse_complete_instruction_token_ui_amount() -> Result<(), String> {
-    test_for_false_negative(3, "tokenAmount.uiAmount")
+fn test_03_should_parse_complete_instruction_token_amount() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(3, false),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => check_parsed_amount(&usdc_inst, 1470000000, 9),
+        _ => Err(false_negative_msg("tokenAmount.amount")),
+    }
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
 fn test_04_should_not_parse_wrong_program_id() -> Result<(), String> {
-    match process_instruction(&get_parsed_ui_instruction_for_test(4, true)) {
-        Some(_) => Err("Should not have parsed instruction. Wrong program_id".to_string()),
-        None => Ok(()),
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(4, true),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok(_) => Err("Should not have parsed instruction. Wrong program_id".to_string()),
+        Err(_) => Ok(()),
     }
 }
 
@@ -100,6 +136,341 @@ fn test_09_should_not_parse_missing_token_amount() -> Result<(), String> {
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
-fn test_10_should_not_parse_missing_ui_amount() -> Result<(), String> {
-    test_for_false_positive(10, "tokenAmount.uiAmount")
+fn test_10_should_not_parse_missing_token_amount_amount() -> Result<(), String> {
+    test_for_false_positive(10, "tokenAmount.amount")
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_11_should_not_parse_plain_transfer_with_unknown_mint() -> Result<(), String> {
+    test_for_false_positive(11, "mint")
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_12_should_parse_plain_transfer_with_mint_from_source() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(11, false),
+        &get_token_accounts_for_test(SOURCE, USDC_MINT),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => check_parsed_amount(&usdc_inst, AMOUNT_U64, 6),
+        _ => Err(false_negative_msg("source token balance mint")),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_13_should_parse_plain_transfer_with_mint_from_destination() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(11, false),
+        &get_token_accounts_for_test(DESTINATION, USDC_MINT),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => check_parsed_amount(&usdc_inst, AMOUNT_U64, 6),
+        _ => Err(false_negative_msg("destination token balance mint")),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_14_should_not_parse_plain_transfer_of_other_mint() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(11, false),
+        &get_token_accounts_for_test(SOURCE, "some other mint"),
+        &Config::default(),
+    ) {
+        Ok(_) => Err("Should not have parsed instruction. Token balances show a non-USDC mint".to_string()),
+        Err(_) => Ok(()),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_15_should_format_ui_amount_exactly() -> Result<(), String> {
+    let cases: [(u64, u8, &str); 5] = [
+        (AMOUNT_U64, 6, AMOUNT_UI_STR),
+        (5000000, 6, "5"),
+        (1, 6, "0.000001"),
+        (u64::MAX, 6, "18446744073709.551615"),
+        (42, 0, "42"),
+    ];
+
+    for (amount, decimals, expected) in cases {
+        let got = format_ui_amount(amount, decimals);
+
+        if got != expected {
+            return Err(format!(
+                "Wrong UI amount for {} with {} decimals.  Expected {}, got {}",
+                amount, decimals, expected, got
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_16_should_report_missing_field() -> Result<(), String> {
+    test_for_parse_error(6, &TokenAccounts::new(), ParseError::MissingField("source"))?;
+    test_for_parse_error(8, &TokenAccounts::new(), ParseError::MissingField("mint"))?;
+    test_for_parse_error(10, &TokenAccounts::new(), ParseError::MissingField("tokenAmount.amount"))
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_17_should_report_bad_amount_without_panicking() -> Result<(), String> {
+    test_for_parse_error(12, &TokenAccounts::new(), ParseError::BadAmount(BAD_AMOUNT_STR.to_string()))
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_18_should_report_wrong_program() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(4, true),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Err(ParseError::WrongProgram(_)) => Ok(()),
+        other => Err(format!("Expected wrong program error, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_19_should_report_wrong_mint() -> Result<(), String> {
+    test_for_parse_error(
+        11,
+        &get_token_accounts_for_test(SOURCE, "some other mint"),
+        ParseError::WrongMint("some other mint".to_string()),
+    )
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_20_should_report_unsupported_instruction() -> Result<(), String> {
+    test_for_parse_error(
+        13,
+        &TokenAccounts::new(),
+        ParseError::UnsupportedInstruction("initializeAccount3".to_string()),
+    )
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_21_should_report_unsupported_encoding() -> Result<(), String> {
+    match process_instruction(
+        &get_compiled_ui_instruction_for_test(),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Err(ParseError::UnsupportedEncoding) => Ok(()),
+        other => Err(format!("Expected unsupported encoding error, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_22_should_parse_token_2022_transfer_checked() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_program(2, TOKEN_2022_PROGRAM_ID),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => check_parsed_amount(&usdc_inst, AMOUNT_U64, 6),
+        _ => Err(false_negative_msg("programId")),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_23_should_parse_token_2022_transfer_checked_with_fee() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_program(14, TOKEN_2022_PROGRAM_ID),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), _)) => match usdc_inst.fee {
+            Some(FEE_U64) => check_parsed_amount(&usdc_inst, AMOUNT_U64, 6),
+            fee => Err(format!("Wrong fee in parsed instruction.  Expected {}, got {:?}", FEE_U64, fee)),
+        },
+        _ => Err(false_negative_msg("feeAmount")),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_24_should_not_parse_unconfigured_token_program() -> Result<(), String> {
+    let config = Config {
+        token_program_ids: vec![TOKEN_PROGRAM_ID.to_string()],
+        ..Config::default()
+    };
+
+    match process_instruction(
+        &get_parsed_ui_instruction_for_program(14, TOKEN_2022_PROGRAM_ID),
+        &TokenAccounts::new(),
+        &config,
+    ) {
+        Err(ParseError::WrongProgram(_)) => Ok(()),
+        other => Err(format!("Expected wrong program error, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_25_should_parse_other_monitored_mint() -> Result<(), String> {
+    match process_instruction(
+        &get_parsed_ui_instruction_for_test(15, false),
+        &TokenAccounts::new(),
+        &Config::default(),
+    ) {
+        Ok((UsdcEvent::Transfer(usdc_inst), monitored_mint)) if monitored_mint.symbol == "USDT" => {
+            check_parsed_amount(&usdc_inst, AMOUNT_U64, 6)
+        }
+        Ok((_, monitored_mint)) => Err(format!("Expected USDT, got {}", monitored_mint.symbol)),
+        Err(_) => Err(false_negative_msg("mint")),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_26_should_not_parse_unmonitored_mint() -> Result<(), String> {
+    let config = Config {
+        mints: Config::default()
+            .mints
+            .into_iter()
+            .filter(|monitored| monitored.symbol != "USDT")
+            .collect(),
+        ..Config::default()
+    };
+
+    match process_instruction(&get_parsed_ui_instruction_for_test(15, false), &TokenAccounts::new(), &config) {
+        Err(ParseError::WrongMint(_)) => Ok(()),
+        other => Err(format!("Expected wrong mint error, got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_27_should_parse_mint_and_burn() -> Result<(), String> {
+    let token_accounts = get_token_accounts_for_test(DESTINATION, USDC_MINT);
+    let mut got = Vec::new();
+
+    for test_num in [16, 17] {
+        match process_instruction(
+            &get_parsed_ui_instruction_for_test(test_num, false),
+            &token_accounts,
+            &Config::default(),
+        ) {
+            Ok((UsdcEvent::Mint { account, authority, ui_amount, .. }, _)) => {
+                got.push(("mint", account, authority, ui_amount))
+            }
+            Ok((UsdcEvent::Burn { account, authority, ui_amount, .. }, _)) => {
+                got.push(("burn", account, authority, ui_amount))
+            }
+            other => return Err(format!("Expected a mint or burn, got {:?}", other)),
+        }
+    }
+
+    let expected = [("mint", DESTINATION, AUTHORITY, AMOUNT_UI_STR), ("burn", SOURCE, AUTHORITY, AMOUNT_UI_STR)]
+        .map(|(kind, account, authority, ui_amount)| {
+            (kind, account.to_string(), authority.to_string(), ui_amount.to_string())
+        });
+
+    if got == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong supply changes {:?}", got))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_28_should_parse_account_events() -> Result<(), String> {
+    let token_accounts = get_token_accounts_for_test(SOURCE, USDC_MINT);
+    let config = Config::default();
+    let mut got = Vec::new();
+
+    for test_num in 18..=25 {
+        let (event, monitored_mint) = process_instruction(
+            &get_parsed_ui_instruction_for_test(test_num, false),
+            &token_accounts,
+            &config,
+        )
+        .map_err(|err| format!("Fixture {}: {}", test_num, err))?;
+
+        got.push((event.get_type(), event.get_account().to_string(), monitored_mint.symbol.as_str()));
+    }
+
+    let expected = [
+        ("approve", SOURCE),
+        ("approve", SOURCE),
+        ("revoke", SOURCE),
+        ("close_account", SOURCE),
+        ("freeze_account", SOURCE),
+        ("thaw_account", SOURCE),
+        ("set_authority", SOURCE),
+        ("set_authority", USDC_MINT),
+    ]
+    .map(|(kind, account)| (kind, account.to_string(), "USDC"));
+
+    if got == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong account events {:?}", got))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_29_should_take_mint_of_account_event_from_token_balances() -> Result<(), String> {
+    test_for_parse_error(20, &TokenAccounts::new(), ParseError::MissingField("mint"))?;
+    test_for_parse_error(
+        20,
+        &get_token_accounts_for_test(SOURCE, "some other mint"),
+        ParseError::WrongMint("some other mint".to_string()),
+    )
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_30_should_serialise_event_with_type_tag() -> Result<(), String> {
+    let mut got = Vec::new();
+
+    for test_num in [19, 25] {
+        let (event, _) = process_instruction(
+            &get_parsed_ui_instruction_for_test(test_num, false),
+            &TokenAccounts::new(),
+            &Config::default(),
+        )
+        .map_err(|err| err.to_string())?;
+
+        got.push(serde_json::to_value(&event).map_err(|err| err.to_string())?);
+    }
+
+    let expected = [
+        serde_json::json!({
+            "type": "approve",
+            "account": SOURCE,
+            "delegate": DELEGATE,
+            "owner": AUTHORITY,
+            "amount": AMOUNT_U64,
+            "decimals": 6,
+            "ui_amount": AMOUNT_UI_STR,
+        }),
+        serde_json::json!({
+            "type": "set_authority",
+            "account": USDC_MINT,
+            "authority_type": "mintTokens",
+            "new_authority": null,
+            "authority": AUTHORITY,
+        }),
+    ];
+
+    if got == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong serialised events {:?}", got))
+    }
 }
@@ -0,0 +1,122 @@
+use crate::{
+    owner::resolve_owners,
+    solana::{SignedUsdcTransaction, UsdcTransaction},
+    state::AppState,
+    transaction::process_transaction,
+};
+
+use log::debug;
+use serde::Serialize;
+use solana_client::{client_error::ClientErrorKind, rpc_config::RpcTransactionConfig};
+use solana_signature::Signature;
+use solana_transaction_status::UiTransactionEncoding;
+use std::str::FromStr;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LookupSource {
+    Store,
+    Rpc,
+}
+
+// The transfers performed by one transaction.  This is empty if the transaction moved none of the monitored tokens
+#[derive(Clone, Debug, Serialize)]
+pub struct SignatureLookup {
+    pub signature: String,
+    pub slot: u64,
+    pub source: LookupSource,
+    pub txns: Vec<SignedUsdcTransaction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LookupError {
+    InvalidSignature(String),
+    NotFound(String),
+    Rpc(String),
+    Store(String),
+}
+
+impl std::fmt::Display for LookupError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LookupError::InvalidSignature(signature) => write!(f, "Invalid signature '{}'", signature),
+            LookupError::NotFound(signature) => write!(f, "Transaction {} not found", signature),
+            LookupError::Rpc(err) => write!(f, "RPC request failed: {}", err),
+            LookupError::Store(err) => write!(f, "Unable to read the transaction store: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+fn get_rpc_transaction_config() -> RpcTransactionConfig {
+    RpcTransactionConfig {
+        encoding: Some(UiTransactionEncoding::JsonParsed),
+        max_supported_transaction_version: Some(0),
+        ..Default::default()
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Look in the store first.  The store only holds transactions that performed a transfer, so if the signature is not
+// found there, the transaction may either have performed no transfer, or not have been monitored.  Unless disabled,
+// fetch it from the RPC node to find out
+pub async fn lookup_signature(state: &AppState, signature: &str) -> Result<SignatureLookup, LookupError> {
+    let parsed_signature =
+        Signature::from_str(signature).map_err(|_| LookupError::InvalidSignature(signature.to_string()))?;
+
+    if let Some(transaction) = state.find_signature(signature.to_string()).await.map_err(LookupError::Store)? {
+        return Ok(SignatureLookup {
+            signature: signature.to_string(),
+            slot: transaction.slot,
+            source: LookupSource::Store,
+            txns: transaction.to_signed_txns().collect(),
+        });
+    }
+
+    if !state.config.on_demand_lookup {
+        return Err(LookupError::NotFound(signature.to_string()));
+    }
+
+    let confirmed_txn = state
+        .rpc_client
+        .get_transaction_with_config(&parsed_signature, get_rpc_transaction_config())
+        .await
+        .map_err(|e| match e.kind() {
+            // The node responds with null when it does not know the transaction
+            ClientErrorKind::SerdeJson(_) => LookupError::NotFound(signature.to_string()),
+            _ => LookupError::Rpc(e.to_string()),
+        })?;
+
+    // As when monitoring, a failed transaction moved nothing
+    let failed = confirmed_txn.transaction.meta.as_ref().is_some_and(|meta| meta.err.is_some());
+    let mut transaction = if failed {
+        None
+    } else {
+        match process_transaction(&confirmed_txn.transaction, &state.config) {
+            Ok(processed) => Some(UsdcTransaction {
+                slot: confirmed_txn.slot,
+                block_time: confirmed_txn.block_time,
+                ..processed.transaction
+            }),
+            Err(err) => {
+                debug!("{}: {}", signature, err);
+                None
+            }
+        }
+    };
+
+    resolve_owners(&state.rpc_client, &state.owners, &state.config, transaction.as_mut()).await;
+
+    Ok(SignatureLookup {
+        signature: signature.to_string(),
+        slot: confirmed_txn.slot,
+        source: LookupSource::Rpc,
+        txns: transaction.iter().flat_map(UsdcTransaction::to_signed_txns).collect(),
+    })
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,111 @@
+use crate::{
+    config::Config,
+    lookup::{lookup_signature, LookupError, LookupSource},
+    slot::unit_tests::{create_mock_client, MockRpcSender},
+    solana::{SignedUsdcTransactionsBySlot, UsdcTransaction},
+    state::AppState,
+    transaction::{
+        process_transaction,
+        ProcessedTransaction,
+        unit_tests::{get_signatures, get_ui_transaction, get_ui_txn_status_meta},
+    },
+};
+
+use serde_json::{json, Value};
+use solana_client::rpc_request::RpcRequest;
+use solana_transaction_status::{EncodedTransaction, EncodedTransactionWithStatusMeta};
+use std::collections::HashMap;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn get_state(get_transaction_response: Option<Value>, config: Config) -> AppState {
+    let mut responses = HashMap::new();
+
+    if let Some(response) = get_transaction_response {
+        responses.insert(RpcRequest::GetTransaction, response);
+    }
+
+    AppState::new(config, create_mock_client(MockRpcSender::new(responses)), None)
+}
+
+fn get_transaction_response(slot: u64) -> Value {
+    json!({
+        "slot": slot,
+        "transaction": get_ui_transaction(true),
+        "meta": get_ui_txn_status_meta(false),
+        "blockTime": null,
+        "version": null
+    })
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_01_should_find_signature_in_store() -> Result<(), String> {
+    // No response has been configured, so any RPC request would fail
+    let state = get_state(None, Config::default());
+    let ProcessedTransaction { transaction, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: None,
+            version: None,
+        },
+        &state.config,
+    )
+    .map_err(|err| err.to_string())?;
+
+    let txns_by_slot = SignedUsdcTransactionsBySlot {
+        slot: 42,
+        transactions: vec![UsdcTransaction { slot: 42, ..transaction }],
+        ..Default::default()
+    };
+
+    state
+        .record_slot(Ok((txns_by_slot, Default::default(), Default::default())))
+        .await
+        .map_err(|dead_letter| dead_letter.error)?;
+
+    // Any of the transaction's signatures will do
+    let lookup = lookup_signature(&state, &get_signatures()[1]).await.map_err(|e| e.to_string())?;
+
+    if lookup.source == LookupSource::Store && lookup.slot == 42 && lookup.txns.len() == 1 {
+        Ok(())
+    } else {
+        Err(format!("Wrong lookup {:?}", lookup))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_02_should_fetch_missing_signature_on_demand() -> Result<(), String> {
+    let state = get_state(Some(get_transaction_response(43)), Config::default());
+    let lookup = lookup_signature(&state, &get_signatures()[0]).await.map_err(|e| e.to_string())?;
+
+    if lookup.source == LookupSource::Rpc && lookup.slot == 43 && lookup.txns.len() == 1 {
+        Ok(())
+    } else {
+        Err(format!("Wrong lookup {:?}", lookup))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_03_should_report_unknown_or_invalid_signature() -> Result<(), String> {
+    let signature = get_signatures()[0].clone();
+    let unknown_to_node = lookup_signature(&get_state(Some(Value::Null), Config::default()), &signature).await;
+    let not_fetched = lookup_signature(
+        &get_state(
+            Some(get_transaction_response(43)),
+            Config {
+                on_demand_lookup: false,
+                ..Config::default()
+            },
+        ),
+        &signature,
+    )
+    .await;
+    let invalid = lookup_signature(&get_state(None, Config::default()), "not a signature").await;
+
+    match (unknown_to_node, not_fetched, invalid) {
+        (Err(LookupError::NotFound(_)), Err(LookupError::NotFound(_)), Err(LookupError::InvalidSignature(_))) => Ok(()),
+        other => Err(format!("Wrong lookup errors {:?}", other)),
+    }
+}
@@ -1,25 +1,91 @@
+mod account_event;
+mod address;
+mod backfill;
+mod balance;
+mod checkpoint;
+mod config;
+mod database;
+mod events;
+mod failed;
 mod instruction;
+mod lookup;
+mod owner;
+mod page;
+mod parse_error;
+mod query;
+mod retention;
 mod slot;
 mod solana;
+mod state;
+mod supply;
+#[cfg(test)]
+mod test_data;
+mod token_account;
 mod transaction;
+mod websocket;
 
 use crate::{
-    slot::process_slot_txns, solana::SignedUsdcTransaction, solana::SignedUsdcTransactionsBySlot,
+    account_event::{AccountEventsQuery, AddressEvents},
+    address::AddressHistory,
+    backfill::{read_progress, run_backfill, BackfillProgress, BackfillRequest, BackfillStatus},
+    balance::ReconciliationReport,
+    checkpoint::{get_checkpoint, get_start_slot, read_checkpoint, write_checkpoint},
+    config::{Config, RetryPolicy},
+    database::Database,
+    events::{subscribe, StreamEvent},
+    failed::{FailedTransactions, FailedTransactionsQuery},
+    lookup::{lookup_signature, LookupError, SignatureLookup},
+    parse_error::ParseErrorCounts,
+    query::{TransactionRecordsPage, TransactionsPage, TransactionsQuery},
+    slot::{get_retry_delay, process_slots, DeadLetters},
+    state::AppState,
+    supply::{SupplyQuery, SupplyReport},
+    websocket::handle_socket,
 };
 
-use axum::{routing::get, Json, Router};
-use env_logger;
-use log::{error, info};
-use solana_client::rpc_client::RpcClient;
-use std::{env, net::SocketAddr, str::FromStr, sync::Arc, time::Duration};
-use tokio::{sync::Mutex, task, time::sleep};
+use axum::{
+    extract::{ws::WebSocketUpgrade, Path, Query},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+    routing::get,
+    Json, Router,
+};
+use futures::{Stream, StreamExt};
+use log::{error, info, warn};
+use solana_client::{client_error::ClientError, nonblocking::rpc_client::RpcClient};
+use std::{net::SocketAddr, pin::pin, str::FromStr, time::Duration};
+use tokio::{task, time::sleep};
 use tower_http::{cors::{Any, CorsLayer}, services::ServeDir};
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 const DEFAULT_SOLANA_RPC_URL: &str = "https://api.mainnet-beta.solana.com";
 const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+const USDT_MINT: &str = "Es9vMFrzaCERmJfrF4H2FYD4KCoNkY11McCe8BenwNYB";
+const PYUSD_MINT: &str = "2b1kV6DkPAnxd5ixfnxCpjxmKwqjjaYmCZfHsFu24GXo";
 const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
 const LOCAL_ADDR: &str = "0.0.0.0:3000";
+const DEFAULT_MAX_CONCURRENT_SLOTS: usize = 4;
+const DEFAULT_RETRY_MAX_ATTEMPTS: u32 = 5;
+const DEFAULT_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+const DEFAULT_RETRY_MAX_DELAY: Duration = Duration::from_millis(8000);
+const DEFAULT_CHECKPOINT_PATH: &str = "checkpoint";
+const DEFAULT_MAX_CATCH_UP_SLOTS: u64 = 1000;
+const DEFAULT_BACKFILL_PATH: &str = "backfill.json";
+const DEFAULT_BACKFILL_CHUNK_SLOTS: u64 = 1000;
+const DEFAULT_RETENTION_MAX_TRANSFERS: usize = 100_000;
+const DEFAULT_PAGE_LIMIT: usize = 100;
+const MAX_PAGE_LIMIT: usize = 1000;
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+const WS_SEND_BUFFER: usize = 256;
+const MAX_WS_SUBSCRIPTIONS: usize = 100;
+const OWNER_CACHE_CAPACITY: usize = 100_000;
+const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+const MAX_RECONCILIATION_DISAGREEMENTS: usize = 100;
+const MAX_DEAD_LETTERS: usize = 10_000;
 const SLEEP_TIME_MILLIS: Duration = Duration::from_millis(1000);
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
@@ -27,12 +93,24 @@ const SLEEP_TIME_MILLIS: Duration = Duration::from_millis(1000);
 async fn main() {
     env_logger::init();
 
-    let txns: Arc<Mutex<Vec<SignedUsdcTransactionsBySlot>>> = Arc::new(Mutex::new(Vec::new()));
-    let txns_clone = Arc::clone(&txns);
-
-    task::spawn(async move {
-        monitor_solana_txns(txns_clone).await;
+    let config = Config::from_env();
+    let rpc_client = RpcClient::new(config.solana_rpc_url.clone());
+    let database = config.database_path.as_ref().map(|path| {
+        Database::open(path).unwrap_or_else(|e| panic!("Unable to open database {}: {}", path.display(), e))
     });
+    let state = AppState::new(config, rpc_client, database);
+
+    task::spawn(monitor_solana_txns(state.clone()));
+
+    // Pick up any backfill that was interrupted by the previous shutdown.  One that had completed, or given up, is only
+    // reported, and a failed one is resumed by requesting the same range again
+    if let Some(previous) = read_progress(&state.config.backfill_path) {
+        *state.backfill.lock().await = Some(previous.clone());
+
+        if previous.status == BackfillStatus::Running {
+            task::spawn(run_backfill(state.clone(), previous));
+        }
+    }
 
     let permissive_cors = CorsLayer::new()
         .allow_origin(Any)
@@ -42,9 +120,21 @@ async fn main() {
     let app = Router::new()
         .route("/api/health", get(|| async { "API is running!" }))
         .route("/transactions", get(get_transactions))
+        .route("/transactions/stream", get(stream_transactions))
+        .route("/transactions/{signature}", get(get_transaction))
+        .route("/v2/transactions", get(get_transaction_records))
+        .route("/addresses/{address}/transfers", get(get_address_transfers))
+        .route("/addresses/{address}/events", get(get_address_events))
+        .route("/supply", get(get_supply))
+        .route("/failed_transactions", get(get_failed_transactions))
+        .route("/ws", get(upgrade_to_websocket))
+        .route("/parse_errors", get(get_parse_errors))
+        .route("/reconciliation", get(get_reconciliation))
+        .route("/dead_letters", get(get_dead_letters))
+        .route("/backfill", get(get_backfill).post(start_backfill))
         .fallback_service(ServeDir::new("./frontend/dist"))
         .layer(permissive_cors)
-        .with_state(txns);
+        .with_state(state);
     let listener = tokio::net::TcpListener::bind(SocketAddr::from_str(LOCAL_ADDR).unwrap())
         .await
         .unwrap();
@@ -54,17 +144,39 @@ async fn main() {
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-async fn monitor_solana_txns(transactions: Arc<Mutex<Vec<SignedUsdcTransactionsBySlot>>>) {
-    let mut next_slot: u64 = 0;
-    let solana_rpc_url = env::var("SOLANA_RPC_URL").unwrap_or_else(|_| DEFAULT_SOLANA_RPC_URL.to_string());
-    let rpc_client = RpcClient::new(&solana_rpc_url);
+async fn monitor_solana_txns(state: AppState) {
+    let config = &state.config;
+    let rpc_client = &state.rpc_client;
+
+    info!(
+        "Monitoring: {} ({} concurrent getBlock requests)",
+        config.solana_rpc_url, config.max_concurrent_slots
+    );
+
+    // Resume from where the previous run left off.  Without a checkpoint, or without the tip to bound how far behind
+    // that leaves us, start from the tip
+    let mut next_slot: u64 = match read_checkpoint(&config.checkpoint_path) {
+        Some(checkpoint) => match get_tip(rpc_client, &config.retry).await {
+            Ok(tip) => {
+                let start_slot = get_start_slot(checkpoint, tip, config.max_catch_up_slots);
 
-    info!("Monitoring: {}", solana_rpc_url);
+                info!("Resuming from slot {} (checkpoint {})", start_slot, checkpoint);
+                start_slot
+            }
+            Err(e) => {
+                error!("Unable to resume from checkpoint {}, starting from the tip: {}", checkpoint, e);
+                0
+            }
+        },
+        None => 0,
+    };
+    // The first slot processed by this run
+    let mut first_slot: Option<u64> = None;
 
     loop {
         // Fetch latest slot, or slot range
         let slots: Vec<u64> = if next_slot == 0 {
-            match rpc_client.get_slot() {
+            match rpc_client.get_slot().await {
                 Ok(slot) => vec![slot],
                 Err(e) => {
                     error!("{}", e);
@@ -72,7 +184,7 @@ async fn monitor_solana_txns(transactions: Arc<Mutex<Vec<SignedUsdcTransactionsB
                 }
             }
         } else {
-            match rpc_client.get_blocks(next_slot, None) {
+            match rpc_client.get_blocks(next_slot, None).await {
                 Ok(s) => s,
                 Err(e) => {
                     error!("{}", e);
@@ -82,39 +194,211 @@ async fn monitor_solana_txns(transactions: Arc<Mutex<Vec<SignedUsdcTransactionsB
         };
 
         // Process all transactions per slot
-        for slot in slots.iter() {
-            let signed_usdc_txns: Vec<SignedUsdcTransaction> =
-                process_slot_txns(&rpc_client, *slot)
-                    .txns
-                    .into_iter()
-                    .collect();
-
-            if !signed_usdc_txns.is_empty() {
-                transactions
-                    .lock()
-                    .await
-                    .push(SignedUsdcTransactionsBySlot {
-                        slot: *slot,
-                        txns: signed_usdc_txns,
-                    });
+        let rejected = state.parse_errors.lock().await.total();
+        let mut processed_slots = pin!(process_slots(rpc_client, &state.owners, &slots, config));
+
+        while let Some(result) = processed_slots.next().await {
+            let detected = match &result {
+                Ok((txns_by_slot, ..)) if !txns_by_slot.transactions.is_empty() => Some(txns_by_slot.clone()),
+                _ => None,
+            };
+            let recorded = state.record_slot(result).await;
+
+            // Publish only once stored, so a subscriber resuming from this slot finds it in the store
+            if let (Some(txns_by_slot), Ok(_)) = (detected, &recorded) {
+                state.publish(txns_by_slot);
+            }
+
+            let slot = recorded.unwrap_or_else(|dead_letter| dead_letter.slot);
+
+            // Slots are yielded in order, so every slot up to and including this one has now been processed.  Any that
+            // ended up as a dead letter has not been accounted for, though
+            let checkpoint = get_checkpoint(
+                slot,
+                *first_slot.get_or_insert(slot),
+                state.dead_letters.lock().await.slots(),
+            );
+
+            if let Some(Err(e)) = checkpoint.map(|checkpoint| write_checkpoint(&config.checkpoint_path, checkpoint)) {
+                error!("Unable to write checkpoint {}: {}", config.checkpoint_path.display(), e);
             }
         }
 
-        // Bump slot number for next iteration
-        next_slot = if let Some(last_slot) = slots.last() {
-            *last_slot + 1
-        } else {
-            0
-        };
+        let parse_errors = *state.parse_errors.lock().await;
+
+        if parse_errors.total() > rejected {
+            info!("Rejected instructions: {}", parse_errors);
+        }
+
+        // Bump slot number for next iteration.  If no new blocks have been produced yet, ask again from the same slot
+        if let Some(last_slot) = slots.last() {
+            next_slot = *last_slot + 1;
+        }
 
         info!("Sleeping for {:?}", SLEEP_TIME_MILLIS);
         sleep(SLEEP_TIME_MILLIS).await;
     }
 }
 
+// The current slot, retrying as getBlock does
+async fn get_tip(rpc_client: &RpcClient, retry: &RetryPolicy) -> Result<u64, ClientError> {
+    let mut attempt: u32 = 1;
+
+    loop {
+        match rpc_client.get_slot().await {
+            Ok(tip) => return Ok(tip),
+            Err(e) if attempt >= retry.max_attempts => return Err(e),
+            Err(e) => {
+                let delay = get_retry_delay(retry, attempt);
+
+                warn!("getSlot attempt {} failed ({}). Retrying in {:.3?}", attempt, e, delay);
+                sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// The response to a request served from the store, which can only fail on the server's side
+type StoreResponse<T> = Result<Json<T>, (StatusCode, String)>;
+
+fn respond<T>(result: Result<T, String>) -> StoreResponse<T> {
+    result.map(Json).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))
+}
+
 async fn get_transactions(
-    state: axum::extract::State<Arc<Mutex<Vec<SignedUsdcTransactionsBySlot>>>>,
-) -> Json<Vec<SignedUsdcTransactionsBySlot>> {
-    Json(state.lock().await.clone())
+    state: axum::extract::State<AppState>,
+    Query(query): Query<TransactionsQuery>,
+) -> StoreResponse<TransactionsPage> {
+    respond(state.read_page(query).await)
+}
+
+async fn get_address_transfers(
+    state: axum::extract::State<AppState>,
+    Path(address): Path<String>,
+    Query(query): Query<TransactionsQuery>,
+) -> StoreResponse<AddressHistory> {
+    respond(state.read_address_history(address, query).await)
+}
+
+async fn get_address_events(
+    state: axum::extract::State<AppState>,
+    Path(address): Path<String>,
+    Query(query): Query<AccountEventsQuery>,
+) -> StoreResponse<AddressEvents> {
+    respond(state.read_address_events(address, query).await)
+}
+
+async fn get_supply(
+    state: axum::extract::State<AppState>,
+    Query(query): Query<SupplyQuery>,
+) -> StoreResponse<SupplyReport> {
+    respond(state.read_supply(query).await)
+}
+
+async fn get_failed_transactions(
+    state: axum::extract::State<AppState>,
+    Query(query): Query<FailedTransactionsQuery>,
+) -> StoreResponse<FailedTransactions> {
+    respond(state.read_failed_transactions(query).await)
+}
+
+async fn get_transaction_records(
+    state: axum::extract::State<AppState>,
+    Query(query): Query<TransactionsQuery>,
+) -> StoreResponse<TransactionRecordsPage> {
+    respond(state.read_transaction_page(query).await)
+}
+
+async fn get_transaction(
+    state: axum::extract::State<AppState>,
+    Path(signature): Path<String>,
+) -> Result<Json<SignatureLookup>, (StatusCode, String)> {
+    lookup_signature(&state, &signature).await.map(Json).map_err(|e| {
+        let status = match e {
+            LookupError::InvalidSignature(_) => StatusCode::BAD_REQUEST,
+            LookupError::NotFound(_) => StatusCode::NOT_FOUND,
+            LookupError::Rpc(_) => StatusCode::BAD_GATEWAY,
+            LookupError::Store(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+
+        (status, e.to_string())
+    })
+}
+
+// Server-Sent Events, one per slot containing matching transfers, identified by the slot number.  A reconnecting client
+// sends the last slot it received as Last-Event-ID, and is first sent the stored slots that it missed.  A client that
+// falls behind before receiving any slot is sent a lagged event instead
+async fn stream_transactions(
+    state: axum::extract::State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<TransactionsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let last_slot = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .or(query.after_slot);
+    let events = subscribe(state.0.clone(), query, last_slot).await.map(|stream_event| match stream_event {
+        StreamEvent::Slot(txns_by_slot) => Event::default()
+            .id(txns_by_slot.slot.to_string())
+            .event("slot")
+            .json_data(&txns_by_slot),
+        StreamEvent::Lagged { missed_slots } => Event::default()
+            .event("lagged")
+            .json_data(serde_json::json!({ "missed_slots": missed_slots })),
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+// Bidirectional alternative to the event stream: the client subscribes to the transfers it is interested in
+async fn upgrade_to_websocket(state: axum::extract::State<AppState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| handle_socket(socket, state.0))
+}
+
+async fn get_parse_errors(state: axum::extract::State<AppState>) -> Json<ParseErrorCounts> {
+    Json(*state.parse_errors.lock().await)
+}
+
+async fn get_reconciliation(state: axum::extract::State<AppState>) -> Json<ReconciliationReport> {
+    Json(state.reconciliation.lock().await.clone())
+}
+
+async fn get_dead_letters(state: axum::extract::State<AppState>) -> Json<DeadLetters> {
+    Json(state.dead_letters.lock().await.clone())
+}
+
+async fn get_backfill(state: axum::extract::State<AppState>) -> Json<Option<BackfillProgress>> {
+    Json(state.backfill.lock().await.clone())
+}
+
+// Start a backfill of the requested slot range in the background.  Requesting the same range as an unfinished
+// backfill resumes it.  Only one backfill runs at a time, and only up to the current slot
+async fn start_backfill(
+    state: axum::extract::State<AppState>,
+    Json(request): Json<BackfillRequest>,
+) -> Result<(StatusCode, Json<BackfillProgress>), (StatusCode, String)> {
+    let tip = state.rpc_client.get_slot().await.map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))?;
+
+    request.validate(tip).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut backfill = state.backfill.lock().await;
+
+    if let Some(BackfillProgress {
+        status: BackfillStatus::Running,
+        ..
+    }) = backfill.as_ref()
+    {
+        return Err((StatusCode::CONFLICT, "A backfill is already running".to_string()));
+    }
+
+    let previous = backfill.clone().or_else(|| read_progress(&state.config.backfill_path));
+    let progress = BackfillProgress::new(request, previous.as_ref());
+
+    *backfill = Some(progress.clone());
+    task::spawn(run_backfill(state.0.clone(), progress.clone()));
+
+    Ok((StatusCode::ACCEPTED, Json(progress)))
 }
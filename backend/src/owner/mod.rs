@@ -0,0 +1,119 @@
+use crate::{config::Config, solana::{UsdcTransaction, UsdcTransfer}, MAX_MULTIPLE_ACCOUNTS, OWNER_CACHE_CAPACITY};
+
+use log::warn;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_pubkey::Pubkey;
+use std::{
+    collections::{BTreeSet, HashMap},
+    str::FromStr,
+    sync::Mutex,
+};
+
+// The data of an SPL Token (or Token-2022) account starts with its mint, followed by its owner
+const OWNER_OFFSET: usize = 32;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Owners of token accounts fetched from the RPC node, by token account address.  Almost every owner is reported in the
+// token balances, so rather than track usage, the cache is simply emptied once full
+#[derive(Default)]
+pub struct OwnerCache {
+    owners: Mutex<HashMap<String, String>>,
+}
+
+impl OwnerCache {
+    fn get(&self, address: &str) -> Option<String> {
+        self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).get(address).cloned()
+    }
+
+    fn insert(&self, address: String, owner: String) {
+        let mut owners = self.owners.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if owners.len() >= OWNER_CACHE_CAPACITY {
+            owners.clear();
+        }
+
+        owners.insert(address, owner);
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Fetch the owners of the given token accounts.  An account that has since been closed has no owner
+async fn get_owners(rpc_client: &RpcClient, config: &Config, addresses: &[String]) -> HashMap<String, String> {
+    let mut owners = HashMap::new();
+    let pubkeys: Vec<Pubkey> = addresses.iter().filter_map(|address| Pubkey::from_str(address).ok()).collect();
+
+    for chunk in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+        match rpc_client.get_multiple_accounts(chunk).await {
+            Ok(accounts) => {
+                for (pubkey, account) in chunk.iter().zip(accounts) {
+                    let owner = account
+                        .filter(|account| config.is_token_program(&account.owner.to_string()))
+                        .and_then(|account| {
+                            let data = account.data.get(OWNER_OFFSET..OWNER_OFFSET + 32)?;
+
+                            Pubkey::try_from(data).ok()
+                        });
+
+                    if let Some(owner) = owner {
+                        owners.insert(pubkey.to_string(), owner.to_string());
+                    }
+                }
+            }
+            Err(e) => warn!("Unable to fetch the owners of {} token accounts: {}", chunk.len(), e),
+        }
+    }
+
+    owners
+}
+
+// Fill in the owners that were missing from the token balances, first from the cache, then from the RPC node
+pub async fn resolve_owners(
+    rpc_client: &RpcClient,
+    cache: &OwnerCache,
+    config: &Config,
+    transactions: impl IntoIterator<Item = &mut UsdcTransaction>,
+) {
+    if !config.owner_lookup {
+        return;
+    }
+
+    let mut missing: BTreeSet<String> = BTreeSet::new();
+    let mut transfers: Vec<&mut UsdcTransfer> = transactions
+        .into_iter()
+        .flat_map(|transaction| transaction.transfers.iter_mut().map(|transfer| &mut transfer.txn))
+        .collect();
+
+    for txn in transfers.iter_mut() {
+        for (address, owner) in [(&txn.from, &mut txn.from_owner), (&txn.to, &mut txn.to_owner)] {
+            if owner.is_none() {
+                *owner = cache.get(address);
+
+                if owner.is_none() {
+                    missing.insert(address.clone());
+                }
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        return;
+    }
+
+    let owners = get_owners(rpc_client, config, &missing.into_iter().collect::<Vec<String>>()).await;
+
+    for txn in transfers.iter_mut() {
+        for (address, owner) in [(&txn.from, &mut txn.from_owner), (&txn.to, &mut txn.to_owner)] {
+            if owner.is_none() {
+                *owner = owners.get(address).cloned();
+            }
+        }
+    }
+
+    for (address, owner) in owners {
+        cache.insert(address, owner);
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
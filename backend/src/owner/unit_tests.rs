@@ -0,0 +1,125 @@
+use crate::{
+    config::Config,
+    owner::{resolve_owners, OwnerCache},
+    slot::unit_tests::{create_mock_client, MockRpcSender},
+    solana::{UsdcTransaction, UsdcTransfer},
+    test_data::get_transaction,
+    TOKEN_PROGRAM_ID, USDC_MINT,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_request::RpcRequest};
+use solana_pubkey::Pubkey;
+use std::{collections::HashMap, str::FromStr};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+const SOURCE_ACCOUNT: &str = "4yV1DkPtGameUwtM3CzwM23inphbosNbmX4d3MRLaPBc";
+const DESTINATION_ACCOUNT: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+const OWNER: &str = "E1wid5KyTfkzxWDUmhxhJTKZqVRR4N6kyuTLXB5bPiU";
+
+fn get_usdc_transaction(from_owner: Option<&str>) -> UsdcTransaction {
+    let mut transaction = get_transaction("USDC", SOURCE_ACCOUNT, DESTINATION_ACCOUNT, 1);
+
+    transaction.transfers[0].mint = USDC_MINT.to_string();
+    transaction.transfers[0].txn.from_owner = from_owner.map(str::to_string);
+    transaction
+}
+
+// A token account as returned by getMultipleAccounts: 165 bytes, starting with the mint and the owner
+fn get_token_account(owner: &str) -> Result<Value, String> {
+    let mut data = vec![0u8; 165];
+
+    for (offset, pubkey) in [(0, USDC_MINT), (32, owner)] {
+        let pubkey = Pubkey::from_str(pubkey).map_err(|e| e.to_string())?;
+
+        data[offset..offset + 32].copy_from_slice(&pubkey.to_bytes());
+    }
+
+    Ok(json!({
+        "data": [STANDARD.encode(data), "base64"],
+        "executable": false,
+        "lamports": 2039280,
+        "owner": TOKEN_PROGRAM_ID,
+        "rentEpoch": 0,
+        "space": 165
+    }))
+}
+
+fn get_client(accounts: Option<Vec<Value>>) -> RpcClient {
+    let mut responses = HashMap::new();
+
+    if let Some(accounts) = accounts {
+        responses.insert(
+            RpcRequest::GetMultipleAccounts,
+            json!({"context": {"slot": 1}, "value": accounts}),
+        );
+    }
+
+    create_mock_client(MockRpcSender::new(responses))
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_01_should_fetch_missing_owner_once() -> Result<(), String> {
+    let cache = OwnerCache::default();
+    let mut first = vec![get_usdc_transaction(Some("Wallet"))];
+    let mut second = vec![get_usdc_transaction(Some("Wallet"))];
+
+    // Only the destination is missing, so only one account is requested
+    resolve_owners(&get_client(Some(vec![get_token_account(OWNER)?])), &cache, &Config::default(), &mut first).await;
+    // Without a response configured, the owner can only come from the cache
+    resolve_owners(&get_client(None), &cache, &Config::default(), &mut second).await;
+
+    let owners: Vec<(Option<String>, Option<String>)> = first
+        .iter()
+        .chain(second.iter())
+        .map(|transaction| &transaction.transfers[0].txn)
+        .map(|txn| (txn.from_owner.clone(), txn.to_owner.clone()))
+        .collect();
+    let expected = (Some("Wallet".to_string()), Some(OWNER.to_string()));
+
+    if owners == [expected.clone(), expected] {
+        Ok(())
+    } else {
+        Err(format!("Wrong owners {:?}", owners))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_02_should_leave_closed_or_disabled_owners_unknown() -> Result<(), String> {
+    let accounts = vec![Value::Null, get_token_account(OWNER)?];
+    let mut resolved = vec![get_usdc_transaction(None)];
+    let mut disabled = vec![get_usdc_transaction(None)];
+
+    // The source account has been closed
+    resolve_owners(&get_client(Some(accounts.clone())), &OwnerCache::default(), &Config::default(), &mut resolved)
+        .await;
+    resolve_owners(
+        &get_client(Some(accounts)),
+        &OwnerCache::default(),
+        &Config {
+            owner_lookup: false,
+            ..Config::default()
+        },
+        &mut disabled,
+    )
+    .await;
+
+    match (&resolved[0].transfers[0].txn, &disabled[0].transfers[0].txn) {
+        (
+            UsdcTransfer {
+                from_owner: None,
+                to_owner: Some(owner),
+                ..
+            },
+            UsdcTransfer {
+                from_owner: None,
+                to_owner: None,
+                ..
+            },
+        ) if owner == OWNER => Ok(()),
+        other => Err(format!("Wrong owners {:?}", other)),
+    }
+}
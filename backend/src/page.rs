@@ -0,0 +1,24 @@
+use crate::{DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Every paged list is built from up to one more than its limit of matching items.  Any extra item is dropped, as it
+// only shows that there is another page
+
+// The limit requested, if any, kept within 1 and MAX_PAGE_LIMIT
+pub fn get_limit(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT)
+}
+
+// Number of matching items to fetch for a page of this limit
+pub fn get_fetch_limit(limit: usize) -> usize {
+    limit + 1
+}
+
+// The items of the page, along with whether there is another page
+pub fn take_page<T>(mut items: Vec<T>, limit: usize) -> (Vec<T>, bool) {
+    let has_more = items.len() > limit;
+
+    items.truncate(limit);
+
+    (items, has_more)
+}
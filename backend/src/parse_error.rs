@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Reasons why a transaction or instruction was not recognised as a USDC transfer
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    MissingField(&'static str),
+    BadAmount(String),
+    WrongProgram(String),
+    WrongMint(String),
+    UnsupportedInstruction(String),
+    UnsupportedEncoding,
+    Unsigned,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::MissingField(field) => write!(f, "Missing field '{}'", field),
+            ParseError::BadAmount(amount) => write!(f, "Invalid amount '{}'", amount),
+            ParseError::WrongProgram(program_id) => write!(f, "Not a token program: {}", program_id),
+            ParseError::WrongMint(mint) => write!(f, "Not a monitored mint: {}", mint),
+            ParseError::UnsupportedInstruction(instruction_type) => {
+                write!(f, "Unsupported instruction type '{}'", instruction_type)
+            }
+            ParseError::UnsupportedEncoding => write!(f, "Unsupported encoding"),
+            ParseError::Unsigned => write!(f, "Unsigned transaction"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Number of rejections per ParseError variant
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+pub struct ParseErrorCounts {
+    pub missing_field: u64,
+    pub bad_amount: u64,
+    pub wrong_program: u64,
+    pub wrong_mint: u64,
+    pub unsupported_instruction: u64,
+    pub unsupported_encoding: u64,
+    pub unsigned: u64,
+}
+
+impl ParseErrorCounts {
+    pub fn add(&mut self, err: &ParseError) {
+        match err {
+            ParseError::MissingField(_) => self.missing_field += 1,
+            ParseError::BadAmount(_) => self.bad_amount += 1,
+            ParseError::WrongProgram(_) => self.wrong_program += 1,
+            ParseError::WrongMint(_) => self.wrong_mint += 1,
+            ParseError::UnsupportedInstruction(_) => self.unsupported_instruction += 1,
+            ParseError::UnsupportedEncoding => self.unsupported_encoding += 1,
+            ParseError::Unsigned => self.unsigned += 1,
+        }
+    }
+
+    pub fn merge(&mut self, other: &ParseErrorCounts) {
+        self.missing_field += other.missing_field;
+        self.bad_amount += other.bad_amount;
+        self.wrong_program += other.wrong_program;
+        self.wrong_mint += other.wrong_mint;
+        self.unsupported_instruction += other.unsupported_instruction;
+        self.unsupported_encoding += other.unsupported_encoding;
+        self.unsigned += other.unsigned;
+    }
+
+    pub fn total(&self) -> u64 {
+        self.missing_field
+            + self.bad_amount
+            + self.wrong_program
+            + self.wrong_mint
+            + self.unsupported_instruction
+            + self.unsupported_encoding
+            + self.unsigned
+    }
+}
+
+impl std::fmt::Display for ParseErrorCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "missing field {}, bad amount {}, wrong program {}, wrong mint {}, unsupported instruction {}, \
+             unsupported encoding {}, unsigned {}",
+            self.missing_field,
+            self.bad_amount,
+            self.wrong_program,
+            self.wrong_mint,
+            self.unsupported_instruction,
+            self.unsupported_encoding,
+            self.unsigned
+        )
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    page,
+    solana::{SignedUsdcTransactionsBySlot, TransferRecord, UsdcTransaction},
+};
+
+use serde::{Deserialize, Serialize};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Query parameters of /transactions.  Slot bounds are inclusive, amounts are in the token's base units, and address
+// (a token account) or owner (a wallet) matches either the source or the destination of a transfer
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct TransactionsQuery {
+    pub symbol: Option<String>,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+    // Cursor: only return slots after this one
+    pub after_slot: Option<u64>,
+    // Cursor of version 2 of the API, within after_slot: only return its transactions after this position
+    pub after_position: Option<u32>,
+    // Maximum number of slots to return, or, in version 2 of the API, of transactions
+    pub limit: Option<usize>,
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+    pub address: Option<String>,
+    pub owner: Option<String>,
+}
+
+// One page of slots, in slot order, each holding only the matching transfers.  Pass next_cursor as after_slot to get
+// the following page, or to poll for slots processed since this one
+#[derive(Clone, Debug, Serialize)]
+pub struct TransactionsPage {
+    pub slots: Vec<SignedUsdcTransactionsBySlot>,
+    pub next_cursor: Option<u64>,
+    // Whether more matching slots were already available when this page was produced
+    pub has_more: bool,
+}
+
+// Where a page of version 2 of the API ended.  Pass these as the query parameters of the same name to get the following
+// page, or to poll for transactions processed since this one
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct TransactionCursor {
+    pub after_slot: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after_position: Option<u32>,
+}
+
+// One page in the shape of version 2 of the API: the matching transactions, in execution order, each holding only its
+// matching transfers
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TransactionRecordsPage {
+    pub transactions: Vec<UsdcTransaction>,
+    pub next_cursor: Option<TransactionCursor>,
+    pub has_more: bool,
+}
+
+// Criteria on a single transfer, shared by the queries and the WebSocket subscriptions.  A transfer matches when every
+// criterion does, and a criterion listing no values matches every transfer
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TransferFilter<'a> {
+    // Token symbols, in any case
+    pub symbols: &'a [String],
+    // Mint addresses, or token symbols in any case
+    pub mints: &'a [String],
+    // Token accounts, either the source or the destination
+    pub addresses: &'a [String],
+    // Wallets owning either the source or the destination
+    pub owners: &'a [String],
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+}
+
+impl TransferFilter<'_> {
+    pub fn matches(&self, transfer: &TransferRecord) -> bool {
+        let txn = &transfer.txn;
+
+        (self.symbols.is_empty() || self.symbols.iter().any(|symbol| transfer.symbol.eq_ignore_ascii_case(symbol)))
+            && (self.mints.is_empty()
+                || self
+                    .mints
+                    .iter()
+                    .any(|mint| *mint == transfer.mint || mint.eq_ignore_ascii_case(&transfer.symbol)))
+            && (self.addresses.is_empty()
+                || self.addresses.iter().any(|address| *address == txn.from || *address == txn.to))
+            && (self.owners.is_empty()
+                || self.owners.iter().any(|owner| {
+                    txn.from_owner.as_ref() == Some(owner) || txn.to_owner.as_ref() == Some(owner)
+                }))
+            && self.min_amount.is_none_or(|min_amount| txn.amount >= min_amount)
+            && self.max_amount.is_none_or(|max_amount| txn.amount <= max_amount)
+    }
+}
+
+impl TransactionsQuery {
+    pub fn get_limit(&self) -> usize {
+        page::get_limit(self.limit)
+    }
+
+    fn is_in_range(&self, slot: u64) -> bool {
+        self.from_slot.is_none_or(|from_slot| slot >= from_slot) && self.to_slot.is_none_or(|to_slot| slot <= to_slot)
+    }
+
+    pub fn matches_slot(&self, slot: u64) -> bool {
+        self.is_in_range(slot) && self.after_slot.is_none_or(|after_slot| slot > after_slot)
+    }
+
+    // Whether the transaction is within the slot range and after the cursor of version 2 of the API
+    pub fn matches_position(&self, transaction: &UsdcTransaction) -> bool {
+        self.is_in_range(transaction.slot)
+            && match (self.after_slot, self.after_position) {
+                (Some(after_slot), Some(after_position)) => {
+                    (transaction.slot, transaction.position) > (after_slot, after_position)
+                }
+                (Some(after_slot), None) => transaction.slot > after_slot,
+                (None, _) => true,
+            }
+    }
+
+    pub fn matches_transfer(&self, transfer: &TransferRecord) -> bool {
+        TransferFilter {
+            symbols: self.symbol.as_slice(),
+            addresses: self.address.as_slice(),
+            owners: self.owner.as_slice(),
+            min_amount: self.min_amount,
+            max_amount: self.max_amount,
+            ..TransferFilter::default()
+        }
+        .matches(transfer)
+    }
+
+    // Build the page from up to one more than the limit of matching slots
+    pub fn get_page(&self, slots: Vec<SignedUsdcTransactionsBySlot>) -> TransactionsPage {
+        let (slots, has_more) = page::take_page(slots, self.get_limit());
+
+        TransactionsPage {
+            next_cursor: slots.last().map(|txns_by_slot| txns_by_slot.slot).or(self.after_slot),
+            slots,
+            has_more,
+        }
+    }
+
+    // Build the page from up to one more than the limit of matching transactions
+    pub fn get_records_page(&self, transactions: Vec<UsdcTransaction>) -> TransactionRecordsPage {
+        let (transactions, has_more) = page::take_page(transactions, self.get_limit());
+
+        TransactionRecordsPage {
+            next_cursor: match transactions.last() {
+                Some(transaction) => Some(TransactionCursor {
+                    after_slot: transaction.slot,
+                    after_position: Some(transaction.position),
+                }),
+                None => self.after_slot.map(|after_slot| TransactionCursor {
+                    after_slot,
+                    after_position: self.after_position,
+                }),
+            },
+            transactions,
+            has_more,
+        }
+    }
+
+    // The transaction holding only the matching transfers, or None if there are none
+    pub fn filter_transaction(&self, transaction: &UsdcTransaction) -> Option<UsdcTransaction> {
+        let transfers: Vec<TransferRecord> =
+            transaction.transfers.iter().filter(|transfer| self.matches_transfer(transfer)).cloned().collect();
+
+        (!transfers.is_empty()).then(|| UsdcTransaction {
+            transfers,
+            ..transaction.clone()
+        })
+    }
+
+    // The slot holding only the matching transfers, or None if there are none
+    pub fn filter_slot(&self, txns_by_slot: &SignedUsdcTransactionsBySlot) -> Option<SignedUsdcTransactionsBySlot> {
+        if !self.matches_slot(txns_by_slot.slot) {
+            return None;
+        }
+
+        let transactions: Vec<UsdcTransaction> =
+            txns_by_slot.transactions.iter().filter_map(|transaction| self.filter_transaction(transaction)).collect();
+
+        (!transactions.is_empty()).then_some(SignedUsdcTransactionsBySlot {
+            slot: txns_by_slot.slot,
+            transactions,
+            ..SignedUsdcTransactionsBySlot::default()
+        })
+    }
+
+    // Apply the query to slots held in slot order
+    pub fn paginate<'a>(&self, slots: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>) -> TransactionsPage {
+        self.get_page(
+            slots
+                .filter_map(|txns_by_slot| self.filter_slot(txns_by_slot))
+                .take(page::get_fetch_limit(self.get_limit()))
+                .collect(),
+        )
+    }
+
+    // Apply the query to slots held in slot order, as version 2 of the API does, limiting the number of transactions
+    // rather than of slots
+    pub fn paginate_transactions<'a>(
+        &self,
+        slots: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>,
+    ) -> TransactionRecordsPage {
+        self.get_records_page(
+            slots
+                .flat_map(|txns_by_slot| txns_by_slot.transactions.iter())
+                .filter(|transaction| self.matches_position(transaction))
+                .filter_map(|transaction| self.filter_transaction(transaction))
+                .take(page::get_fetch_limit(self.get_limit()))
+                .collect(),
+        )
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
@@ -0,0 +1,253 @@
+use crate::{
+    query::{TransactionCursor, TransactionsQuery, TransferFilter},
+    solana::{SignedUsdcTransactionsBySlot, UsdcTransaction},
+    test_data::{get_slot, get_transaction, get_transfer},
+    MAX_PAGE_LIMIT,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn get_slots() -> Vec<SignedUsdcTransactionsBySlot> {
+    (1..=6)
+        .map(|slot| {
+            get_slot(
+                slot,
+                vec![
+                    get_transaction("USDC", "Alice", "Bob", slot * 100),
+                    get_transaction("USDT", "Bob", "Carol", slot * 100 + 1),
+                ],
+            )
+        })
+        .collect()
+}
+
+fn get_slot_numbers(slots: &[SignedUsdcTransactionsBySlot]) -> Vec<u64> {
+    slots.iter().map(|txns_by_slot| txns_by_slot.slot).collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_follow_cursor_to_last_page() -> Result<(), String> {
+    let slots = get_slots();
+    let mut query = TransactionsQuery {
+        from_slot: Some(2),
+        limit: Some(2),
+        ..TransactionsQuery::default()
+    };
+    let mut pages: Vec<(Vec<u64>, Option<u64>, bool)> = Vec::new();
+
+    for _ in 0..4 {
+        let page = query.paginate(slots.iter());
+
+        query.after_slot = page.next_cursor;
+        pages.push((get_slot_numbers(&page.slots), page.next_cursor, page.has_more));
+    }
+
+    // Once there are no more slots, the cursor stays put, ready to poll for new ones
+    let expected = vec![
+        (vec![2, 3], Some(3), true),
+        (vec![4, 5], Some(5), true),
+        (vec![6], Some(6), false),
+        (vec![], Some(6), false),
+    ];
+
+    if pages == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong pages {:?}", pages))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_keep_only_matching_transfers() -> Result<(), String> {
+    let slots = get_slots();
+    let query = TransactionsQuery {
+        to_slot: Some(5),
+        min_amount: Some(301),
+        max_amount: Some(500),
+        address: Some("Carol".to_string()),
+        ..TransactionsQuery::default()
+    };
+    let page = query.paginate(slots.iter());
+    let amounts: Vec<u64> = page
+        .slots
+        .iter()
+        .flat_map(|txns_by_slot| txns_by_slot.transfers().map(|(_, transfer)| transfer.txn.amount))
+        .collect();
+
+    // Only the transfers to Carol (USDT) match the address, and the slot 6 transfer is beyond to_slot
+    match (get_slot_numbers(&page.slots), amounts) {
+        (slots, amounts) if slots == [3, 4] && amounts == [301, 401] => Ok(()),
+        other => Err(format!("Wrong matching transfers {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_clamp_limit() -> Result<(), String> {
+    let limits: Vec<usize> = [Some(0), Some(usize::MAX), None]
+        .into_iter()
+        .map(|limit| TransactionsQuery {
+            limit,
+            ..TransactionsQuery::default()
+        }
+        .get_limit())
+        .collect();
+
+    match limits.as_slice() {
+        [1, max, default] if *max == MAX_PAGE_LIMIT && *default < MAX_PAGE_LIMIT => Ok(()),
+        other => Err(format!("Wrong limits {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_keep_only_matching_transfers_of_each_transaction() -> Result<(), String> {
+    let transaction = |signature: &str, transfers| UsdcTransaction {
+        signature: signature.to_string(),
+        signatures: [signature.to_string(), "Cosigner".to_string()].into(),
+        transfers,
+        ..UsdcTransaction::default()
+    };
+    let txns_by_slot = get_slot(
+        1,
+        vec![
+            transaction(
+                "A",
+                vec![get_transfer("USDC", "Alice", "Bob", 100), get_transfer("USDT", "Alice", "Bob", 100)],
+            ),
+            transaction("B", vec![get_transfer("USDC", "Alice", "Bob", 100)]),
+        ],
+    );
+    let mut records = Vec::new();
+
+    for symbol in [None, Some("USDC".to_string())] {
+        let query = TransactionsQuery {
+            symbol,
+            ..TransactionsQuery::default()
+        };
+        let page = query.paginate_transactions([&txns_by_slot].into_iter());
+
+        records.push(
+            page.transactions
+                .iter()
+                .map(|record| (record.signature.clone(), record.signatures.len(), record.transfers.len()))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // Filtering by symbol leaves transaction A with only its USDC transfer
+    let expected = vec![
+        vec![("A".to_string(), 2, 2), ("B".to_string(), 2, 1)],
+        vec![("A".to_string(), 2, 1), ("B".to_string(), 2, 1)],
+    ];
+
+    if records == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong transaction records {:?}", records))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_limit_transactions_rather_than_slots() -> Result<(), String> {
+    let slots = get_slots();
+    let mut query = TransactionsQuery {
+        from_slot: Some(5),
+        limit: Some(3),
+        ..TransactionsQuery::default()
+    };
+    let mut pages: Vec<(Vec<u64>, Option<TransactionCursor>, bool)> = Vec::new();
+
+    for _ in 0..3 {
+        let page = query.paginate_transactions(slots.iter());
+
+        query.after_slot = page.next_cursor.map(|cursor| cursor.after_slot);
+        query.after_position = page.next_cursor.and_then(|cursor| cursor.after_position);
+        pages.push((
+            page.transactions.iter().map(|transaction| transaction.transfers[0].txn.amount).collect(),
+            page.next_cursor,
+            page.has_more,
+        ));
+    }
+
+    // The first page ends part of the way through slot 6
+    let cursor = |after_slot, after_position| {
+        Some(TransactionCursor {
+            after_slot,
+            after_position: Some(after_position),
+        })
+    };
+    let expected = vec![
+        (vec![500, 501, 600], cursor(6, 0), true),
+        (vec![601], cursor(6, 1), false),
+        (vec![], cursor(6, 1), false),
+    ];
+
+    if pages == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong pages {:?}", pages))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_06_should_list_each_transfer_with_its_transaction_in_version_1() -> Result<(), String> {
+    let txns_by_slot = get_slot(
+        1,
+        vec![UsdcTransaction {
+            fee_payer: "Alice".to_string(),
+            transfers: vec![get_transfer("USDC", "Alice", "Bob", 100), get_transfer("USDT", "Alice", "Carol", 7)],
+            ..get_transaction("USDC", "Alice", "Bob", 100)
+        }],
+    );
+    let value = serde_json::to_value(&txns_by_slot).map_err(|e| e.to_string())?;
+    let txns: Vec<(String, String, String)> = value["txns"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|txn| {
+            (
+                txn["signatures"][0].as_str().unwrap_or_default().to_string(),
+                txn["fee_payer"].as_str().unwrap_or_default().to_string(),
+                txn["txn"]["to"].as_str().unwrap_or_default().to_string(),
+            )
+        })
+        .collect();
+    let transfer = |to: &str| ("Signature100".to_string(), "Alice".to_string(), to.to_string());
+
+    if txns == [transfer("Bob"), transfer("Carol")] && value.get("transactions").is_none() {
+        Ok(())
+    } else {
+        Err(format!("Wrong version 1 slot {}", value))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_07_should_match_any_value_of_each_transfer_criterion() -> Result<(), String> {
+    let addresses = ["Bob".to_string(), "Dave".to_string()];
+    let mints = ["usdt".to_string(), "USDCMint".to_string()];
+    let filter = TransferFilter {
+        addresses: &addresses,
+        mints: &mints,
+        ..TransferFilter::default()
+    };
+    let transfers = [
+        get_transfer("USDC", "Alice", "Bob", 1),
+        get_transfer("USDT", "Dave", "Carol", 2),
+        get_transfer("PYUSD", "Alice", "Bob", 3),
+        get_transfer("USDC", "Alice", "Carol", 4),
+    ];
+    let matching: Vec<u64> =
+        transfers.iter().filter(|transfer| filter.matches(transfer)).map(|transfer| transfer.txn.amount).collect();
+    let unfiltered = transfers.iter().filter(|transfer| TransferFilter::default().matches(transfer)).count();
+
+    if matching == [1, 2] && unfiltered == transfers.len() {
+        Ok(())
+    } else {
+        Err(format!("Wrong matching transfers {:?} ({} unfiltered)", matching, unfiltered))
+    }
+}
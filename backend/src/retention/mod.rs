@@ -0,0 +1,175 @@
+use crate::{
+    config::RetentionPolicy,
+    solana::SignedUsdcTransactionsBySlot,
+};
+
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    time::Instant,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Receives the slots evicted from the in-memory store, for example to archive them
+pub trait EvictionSink: Send + Sync {
+    fn evict(&self, evicted: &[SignedUsdcTransactionsBySlot]) -> io::Result<()>;
+}
+
+// Appends each evicted slot to a file as one line of JSON
+pub struct JsonLinesSink {
+    path: PathBuf,
+}
+
+impl JsonLinesSink {
+    pub fn new(path: PathBuf) -> Self {
+        JsonLinesSink { path }
+    }
+}
+
+impl EvictionSink for JsonLinesSink {
+    fn evict(&self, evicted: &[SignedUsdcTransactionsBySlot]) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        let mut lines = String::new();
+
+        for txns_by_slot in evicted {
+            lines.push_str(&serde_json::to_string(txns_by_slot)?);
+            lines.push('\n');
+        }
+
+        file.write_all(lines.as_bytes())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// The token accounts each transfer is from and to, and that each account event acts upon
+fn get_addresses(txns_by_slot: &SignedUsdcTransactionsBySlot) -> impl Iterator<Item = &str> {
+    txns_by_slot
+        .transfers()
+        .flat_map(|(_, transfer)| [transfer.txn.from.as_str(), transfer.txn.to.as_str()])
+        .chain(txns_by_slot.account_events.iter().map(|account_event| account_event.event.get_account()))
+}
+
+fn index(addresses: &mut HashMap<String, BTreeSet<u64>>, txns_by_slot: &SignedUsdcTransactionsBySlot) {
+    for address in get_addresses(txns_by_slot) {
+        addresses.entry(address.to_string()).or_default().insert(txns_by_slot.slot);
+    }
+}
+
+fn unindex(addresses: &mut HashMap<String, BTreeSet<u64>>, txns_by_slot: &SignedUsdcTransactionsBySlot) {
+    for address in get_addresses(txns_by_slot) {
+        if let Some(slots) = addresses.get_mut(address) {
+            slots.remove(&txns_by_slot.slot);
+
+            if slots.is_empty() {
+                addresses.remove(address);
+            }
+        }
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Ring buffer of the most recent slots containing transfers (or other events), kept in slot order.  Once any limit of
+// the retention policy is exceeded, the lowest slots are evicted, although the highest slot is always kept
+pub struct TransactionBuffer {
+    policy: RetentionPolicy,
+    // Each slot is stored alongside the time at which it was added
+    slots: VecDeque<(Instant, SignedUsdcTransactionsBySlot)>,
+    transfer_count: usize,
+    // Slots holding transfers from or to (or other events acting upon) each token account, so the history of an address
+    // needs no scan
+    addresses: HashMap<String, BTreeSet<u64>>,
+}
+
+impl TransactionBuffer {
+    pub fn new(policy: RetentionPolicy) -> Self {
+        TransactionBuffer {
+            policy,
+            slots: VecDeque::new(),
+            transfer_count: 0,
+            addresses: HashMap::new(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &SignedUsdcTransactionsBySlot> {
+        self.slots.iter().map(|(_, txns_by_slot)| txns_by_slot)
+    }
+
+    // The slots holding transfers from or to (or other events acting upon) this address, in slot order
+    pub fn iter_address(&self, address: &str) -> impl Iterator<Item = &SignedUsdcTransactionsBySlot> {
+        self.addresses.get(address).into_iter().flatten().filter_map(|slot| {
+            self.slots
+                .binary_search_by_key(slot, |(_, existing)| existing.slot)
+                .ok()
+                .map(|idx| &self.slots[idx].1)
+        })
+    }
+
+    // Add a slot, replacing any earlier result for the same slot, and return whatever this caused to be evicted
+    pub fn insert(&mut self, txns_by_slot: SignedUsdcTransactionsBySlot) -> Vec<SignedUsdcTransactionsBySlot> {
+        let now = Instant::now();
+
+        self.transfer_count += txns_by_slot.transfer_count();
+
+        let idx = match self.slots.binary_search_by_key(&txns_by_slot.slot, |(_, existing)| existing.slot) {
+            Ok(idx) => {
+                let (_, replaced) = std::mem::replace(&mut self.slots[idx], (now, txns_by_slot));
+
+                self.transfer_count -= replaced.transfer_count();
+                unindex(&mut self.addresses, &replaced);
+                idx
+            }
+            Err(idx) => {
+                self.slots.insert(idx, (now, txns_by_slot));
+                idx
+            }
+        };
+
+        // Only once any replaced result has been unindexed, as the same addresses may appear in both
+        index(&mut self.addresses, &self.slots[idx].1);
+
+        self.evict(now)
+    }
+
+    fn is_over_limit(&self) -> bool {
+        self.policy.max_slots.is_some_and(|max_slots| self.slots.len() > max_slots)
+            || self.policy.max_transfers.is_some_and(|max_transfers| self.transfer_count > max_transfers)
+    }
+
+    fn evict(&mut self, now: Instant) -> Vec<SignedUsdcTransactionsBySlot> {
+        let mut evicted = Vec::new();
+        let max_age = self.policy.max_age;
+        let highest_slot = self.slots.back().map(|(_, txns_by_slot)| txns_by_slot.slot);
+        let is_expired = |(added, txns_by_slot): &(Instant, SignedUsdcTransactionsBySlot)| {
+            max_age.is_some_and(|max_age| now.saturating_duration_since(*added) > max_age)
+                && Some(txns_by_slot.slot) != highest_slot
+        };
+
+        // A backfill adds old slots with a recent time, so expired slots are not necessarily at the front
+        if self.slots.iter().any(is_expired) {
+            let (expired, kept): (VecDeque<_>, VecDeque<_>) = self.slots.drain(..).partition(is_expired);
+
+            self.slots = kept;
+            evicted.extend(expired.into_iter().map(|(_, txns_by_slot)| txns_by_slot));
+            self.transfer_count -= evicted.iter().map(|txns_by_slot| txns_by_slot.transfer_count()).sum::<usize>();
+        }
+
+        while self.slots.len() > 1 && self.is_over_limit() {
+            if let Some((_, txns_by_slot)) = self.slots.pop_front() {
+                self.transfer_count -= txns_by_slot.transfer_count();
+                evicted.push(txns_by_slot);
+            }
+        }
+
+        for txns_by_slot in &evicted {
+            unindex(&mut self.addresses, txns_by_slot);
+        }
+
+        evicted
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
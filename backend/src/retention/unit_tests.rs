@@ -0,0 +1,139 @@
+use crate::{
+    config::RetentionPolicy,
+    retention::{EvictionSink, JsonLinesSink, TransactionBuffer},
+    solana::SignedUsdcTransactionsBySlot,
+    test_data::{get_slot, get_transaction},
+};
+
+use serde_json::Value;
+use std::{env, fs, thread::sleep, time::Duration};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// A slot of transfer_count identical transfers
+fn get_slot_of_size(slot: u64, transfer_count: usize) -> SignedUsdcTransactionsBySlot {
+    get_slot(slot, vec![get_transaction("USDC", "Source", "Destination", slot); transfer_count])
+}
+
+fn get_slot_numbers<'a>(txns_by_slot: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>) -> Vec<u64> {
+    txns_by_slot.map(|txns_by_slot| txns_by_slot.slot).collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_evict_lowest_slots_beyond_max_slots() -> Result<(), String> {
+    let mut buffer = TransactionBuffer::new(RetentionPolicy {
+        max_slots: Some(2),
+        ..RetentionPolicy::default()
+    });
+    let mut evicted = Vec::new();
+
+    for slot in [10, 12, 11, 13] {
+        evicted.append(&mut buffer.insert(get_slot_of_size(slot, 1)));
+    }
+
+    match (get_slot_numbers(buffer.iter()), get_slot_numbers(evicted.iter())) {
+        (kept, evicted) if kept == [12, 13] && evicted == [10, 11] => Ok(()),
+        other => Err(format!("Expected to keep [12, 13] and evict [10, 11], got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_count_transfers_of_replaced_slot_once() -> Result<(), String> {
+    let mut buffer = TransactionBuffer::new(RetentionPolicy {
+        max_transfers: Some(3),
+        ..RetentionPolicy::default()
+    });
+
+    buffer.insert(get_slot_of_size(10, 3));
+    buffer.insert(get_slot_of_size(10, 1));
+    let evicted = buffer.insert(get_slot_of_size(11, 2));
+
+    if !evicted.is_empty() {
+        return Err(format!("Nothing should have been evicted, got {:?}", get_slot_numbers(evicted.iter())));
+    }
+
+    // A slot exceeding the limit on its own is still kept, as it is the highest
+    let evicted = buffer.insert(get_slot_of_size(12, 5));
+
+    match (get_slot_numbers(buffer.iter()), get_slot_numbers(evicted.iter())) {
+        (kept, evicted) if kept == [12] && evicted == [10, 11] => Ok(()),
+        other => Err(format!("Expected to keep [12] and evict [10, 11], got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_evict_expired_slots_regardless_of_position() -> Result<(), String> {
+    let mut buffer = TransactionBuffer::new(RetentionPolicy {
+        max_age: Some(Duration::from_millis(50)),
+        ..RetentionPolicy::default()
+    });
+
+    buffer.insert(get_slot_of_size(10, 1));
+    sleep(Duration::from_millis(100));
+
+    // A backfilled slot is added below the expired one
+    buffer.insert(get_slot_of_size(5, 1));
+    let evicted = buffer.insert(get_slot_of_size(11, 1));
+
+    match (get_slot_numbers(buffer.iter()), get_slot_numbers(evicted.iter())) {
+        (kept, evicted) if kept == [5, 11] && evicted == [10] => Ok(()),
+        other => Err(format!("Expected to keep [5, 11] and evict [10], got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_append_evicted_slots_as_json_lines() -> Result<(), String> {
+    let path = env::temp_dir().join(format!("solana_usdc_monitor_evicted_{}.jsonl", std::process::id()));
+    let sink = JsonLinesSink::new(path.clone());
+
+    sink.evict(&[get_slot_of_size(10, 1)]).map_err(|e| e.to_string())?;
+    sink.evict(&[get_slot_of_size(11, 2), get_slot_of_size(12, 1)]).map_err(|e| e.to_string())?;
+    let contents = fs::read_to_string(&path).map_err(|e| e.to_string());
+    let _ = fs::remove_file(&path);
+
+    // Each line holds a slot, along with its transfers
+    let slots = contents?
+        .lines()
+        .map(|line| {
+            let value = serde_json::from_str::<Value>(line).map_err(|e| e.to_string())?;
+
+            Ok((value["slot"].as_u64(), value["txns"].as_array().map(Vec::len)))
+        })
+        .collect::<Result<Vec<(Option<u64>, Option<usize>)>, String>>()?;
+
+    if slots == [(Some(10), Some(1)), (Some(11), Some(2)), (Some(12), Some(1))] {
+        Ok(())
+    } else {
+        Err(format!("Wrong evicted slots {:?}", slots))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_index_addresses_of_kept_slots() -> Result<(), String> {
+    let mut buffer = TransactionBuffer::new(RetentionPolicy {
+        max_slots: Some(2),
+        ..RetentionPolicy::default()
+    });
+    let get_slot_to = |slot: u64, to: &str| {
+        let mut txns_by_slot = get_slot_of_size(slot, 1);
+
+        txns_by_slot.transactions[0].transfers[0].txn.to = to.to_string();
+        txns_by_slot
+    };
+
+    buffer.insert(get_slot_to(10, "Treasury"));
+    buffer.insert(get_slot_to(11, "Treasury"));
+    // Replaces slot 11, which no longer involves the treasury
+    buffer.insert(get_slot_to(11, "Supplier"));
+    // Evicts slot 10
+    buffer.insert(get_slot_to(12, "Treasury"));
+
+    match (get_slot_numbers(buffer.iter_address("Treasury")), get_slot_numbers(buffer.iter_address("Supplier"))) {
+        (treasury, supplier) if treasury == [12] && supplier == [11] => Ok(()),
+        other => Err(format!("Expected the treasury in [12] and the supplier in [11], got {:?}", other)),
+    }
+}
@@ -1,12 +1,27 @@
 use crate::{
-    solana::{SignedUsdcTransaction, SignedUsdcTransactionsBySlot},
-    transaction::process_transaction,
+    balance::ReconciliationReport,
+    config::{Config, RetryPolicy},
+    failed::get_token_error,
+    owner::{resolve_owners, OwnerCache},
+    parse_error::ParseErrorCounts,
+    solana::{AccountEvent, FailedTransaction, SignedUsdcTransactionsBySlot, SupplyChange, UsdcTransaction},
+    transaction::{process_transaction, ProcessedTransaction},
+    MAX_DEAD_LETTERS,
 };
 
-use log::{error, info};
-use solana_client::{rpc_client::RpcClient, rpc_config::RpcBlockConfig};
-use solana_transaction_status::UiTransactionEncoding;
-use tokio::time::Instant;
+use futures::{stream, Stream, StreamExt};
+use log::{debug, error, info, warn};
+use serde::{Serialize, Serializer};
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::RpcBlockConfig,
+    rpc_custom_error::{JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED, JSON_RPC_SERVER_ERROR_SLOT_SKIPPED},
+    rpc_request::RpcError,
+};
+use solana_transaction_status::{UiConfirmedBlock, UiTransactionEncoding};
+use std::{collections::BTreeMap, time::Duration};
+use tokio::time::{sleep, Instant};
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 fn get_rpc_client_config() -> RpcBlockConfig {
@@ -17,62 +32,247 @@ fn get_rpc_client_config() -> RpcBlockConfig {
     }
 }
 
-pub fn process_slot_txns(rpc_client: &RpcClient, slot: u64) -> SignedUsdcTransactionsBySlot {
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// A slot whose block could not be fetched, even after retrying, or could not be stored
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DeadLetter {
+    pub slot: u64,
+    pub attempts: u32,
+    pub error: String,
+}
+
+// The dead letters, by slot.  Only the most recent slots are kept, so once the list is full, the oldest is given up on
+// (and no longer holds the checkpoint back) to make room for another
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeadLetters {
+    // Number of dead letters given up on to keep within the limit
+    pub dropped: u64,
+    #[serde(rename = "dead_letters", serialize_with = "serialize_values")]
+    by_slot: BTreeMap<u64, DeadLetter>,
+}
+
+impl DeadLetters {
+    // Any earlier dead letter for the same slot is replaced
+    pub fn insert(&mut self, dead_letter: DeadLetter) {
+        self.by_slot.insert(dead_letter.slot, dead_letter);
+
+        while self.by_slot.len() > MAX_DEAD_LETTERS {
+            if let Some((slot, _)) = self.by_slot.pop_first() {
+                warn!("Giving up on slot {}: too many dead letters", slot);
+                self.dropped += 1;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, slot: u64) {
+        self.by_slot.remove(&slot);
+    }
+
+    pub fn slots(&self) -> impl Iterator<Item = u64> + '_ {
+        self.by_slot.keys().copied()
+    }
+}
+
+fn serialize_values<S: Serializer>(by_slot: &BTreeMap<u64, DeadLetter>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(by_slot.values())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SlotError {
+    // The leader did not produce a block for this slot, so there is nothing to fetch
+    Skipped(u64),
+    Failed(DeadLetter),
+}
+
+// Whatever was detected in a slot, along with the instructions that were rejected, and the outcome of cross-checking
+// the detected transfers against the token balances
+pub type SlotResult = Result<(SignedUsdcTransactionsBySlot, ParseErrorCounts, ReconciliationReport), SlotError>;
+
+// Only these errors mean the block will never be available.  Anything else is worth retrying
+fn is_skipped_slot(err: &ClientError) -> bool {
+    matches!(
+        err.kind(),
+        ClientErrorKind::RpcError(RpcError::RpcResponseError { code, .. })
+            if *code == JSON_RPC_SERVER_ERROR_SLOT_SKIPPED || *code == JSON_RPC_SERVER_ERROR_LONG_TERM_STORAGE_SLOT_SKIPPED
+    )
+}
+
+// Exponential backoff with jitter: wait for somewhere between half and all of the capped delay, so that concurrent
+// requests that failed together do not all retry at the same moment
+pub fn get_retry_delay(retry: &RetryPolicy, attempt: u32) -> Duration {
+    let delay = retry
+        .base_delay
+        .saturating_mul(2_u32.saturating_pow(attempt.saturating_sub(1)))
+        .min(retry.max_delay);
+
+    delay / 2 + (delay / 2).mul_f64(rand::random::<f64>())
+}
+
+async fn get_block(rpc_client: &RpcClient, slot: u64, retry: &RetryPolicy) -> Result<UiConfirmedBlock, SlotError> {
+    let mut attempt: u32 = 1;
+
+    loop {
+        match rpc_client.get_block_with_config(slot, get_rpc_client_config()).await {
+            Ok(confirmed_block) => return Ok(confirmed_block),
+            Err(e) if is_skipped_slot(&e) => return Err(SlotError::Skipped(slot)),
+            Err(e) if attempt >= retry.max_attempts => {
+                return Err(SlotError::Failed(DeadLetter {
+                    slot,
+                    attempts: attempt,
+                    error: e.to_string(),
+                }))
+            }
+            Err(e) => {
+                let delay = get_retry_delay(retry, attempt);
+
+                warn!(
+                    "     Slot {}: attempt {} failed ({}). Retrying in {:.3?}",
+                    slot, attempt, e, delay
+                );
+                sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+pub async fn process_slot_txns(
+    rpc_client: &RpcClient,
+    owners: &OwnerCache,
+    slot: u64,
+    config: &Config,
+) -> SlotResult {
     let slot_start_time = Instant::now();
-    let mut usdc_txns: Vec<SignedUsdcTransaction> = Vec::new();
+    let mut transactions: Vec<UsdcTransaction> = Vec::new();
+    let mut supply_changes: Vec<SupplyChange> = Vec::new();
+    let mut account_events: Vec<AccountEvent> = Vec::new();
+    let mut failed_transactions: Vec<FailedTransaction> = Vec::new();
+    let mut rejections = ParseErrorCounts::default();
+    let mut reconciliation = ReconciliationReport::default();
 
     info!("---> Slot {}", slot);
 
-    match rpc_client.get_block_with_config(slot, get_rpc_client_config()) {
-        Ok(confirmed_block) => {
-            info!(
-                "     get_block request took {:.3?}",
-                slot_start_time.elapsed()
-            );
-            let mut txn_count = 0;
-            let mut unsigned_txn_count = 0;
-
-            // Process only confirmed transactions
-            for txns in confirmed_block.transactions.iter() {
-                // Process inner transactions
-                for inner_txn in txns.iter() {
-                    txn_count += 1;
-
-                    // Exclude any transactions whose meta.err property is populated
-                    if &inner_txn.meta.is_some() == &true {
-                        if inner_txn.meta.clone().unwrap().err.is_some() {
-                            continue;
-                        }
-                    }
+    let confirmed_block = get_block(rpc_client, slot, &config.retry).await.inspect_err(|e| match e {
+        SlotError::Skipped(_) => info!("<--- Slot {}: skipped", slot),
+        SlotError::Failed(dead_letter) => error!(
+            "<--- Slot {}: giving up after {} attempts: {}",
+            slot, dead_letter.attempts, dead_letter.error
+        ),
+    })?;
+
+    info!(
+        "     get_block request took {:.3?}",
+        slot_start_time.elapsed()
+    );
+    let mut txn_count = 0;
 
-                    let mut result = process_transaction(inner_txn);
+    // Process only confirmed transactions
+    for txns in confirmed_block.transactions.iter() {
+        // Process inner transactions, numbering them by their position within the block
+        for (position, inner_txn) in txns.iter().enumerate() {
+            txn_count += 1;
+
+            let with_position = |transaction: UsdcTransaction| UsdcTransaction {
+                slot,
+                position: position as u32,
+                block_time: confirmed_block.block_time,
+                ..transaction
+            };
 
-                    usdc_txns.append(&mut result.0);
-                    unsigned_txn_count += result.1;
+            // Exclude any transactions whose meta.err property is populated, unless their attempted transfers are to
+            // be recorded.  They moved no tokens, so there is nothing to reconcile
+            if let Some(err) = inner_txn.meta.as_ref().and_then(|meta| meta.err.as_ref()) {
+                if config.record_failed_transactions {
+                    if let Some(attempted) = process_transaction(inner_txn, config)
+                        .ok()
+                        .map(|processed| processed.transaction)
+                        .filter(|transaction| !transaction.transfers.is_empty())
+                    {
+                        failed_transactions.push(FailedTransaction::new(
+                            with_position(attempted),
+                            err.clone(),
+                            get_token_error(inner_txn, err, config),
+                        ));
+                    }
                 }
+
+                continue;
             }
 
-            info!(
-                "<--- Slot {}: Processed {} transactions in {:.3?}{}",
-                slot,
-                txn_count,
-                slot_start_time.elapsed(),
-                if unsigned_txn_count == 0 {
-                    "".to_string()
-                } else {
-                    format!(". ({} skipped)", unsigned_txn_count)
-                },
-            );
+            // A transaction that could not be processed at all is still cross-checked, in case it moved any tokens
+            let mut processed = process_transaction(inner_txn, config).unwrap_or_else(|err| {
+                rejections.add(&err);
+                ProcessedTransaction::default()
+            });
+
+            reconciliation.check(slot, inner_txn, &processed, config);
+
+            for supply_change in processed.supply_changes.iter_mut() {
+                supply_change.block_time = confirmed_block.block_time;
+            }
+
+            for account_event in processed.account_events.iter_mut() {
+                account_event.block_time = confirmed_block.block_time;
+            }
+
+            if !processed.transaction.transfers.is_empty() {
+                transactions.push(with_position(processed.transaction));
+            }
+
+            supply_changes.append(&mut processed.supply_changes);
+            account_events.append(&mut processed.account_events);
+            rejections.merge(&processed.rejections);
         }
-        Err(e) => error!("<--- Slot {}: {}", slot, e),
-    };
+    }
 
-    SignedUsdcTransactionsBySlot {
+    resolve_owners(rpc_client, owners, config, transactions.iter_mut()).await;
+    resolve_owners(
+        rpc_client,
+        owners,
+        config,
+        failed_transactions.iter_mut().map(|failed_txn| &mut failed_txn.transaction),
+    )
+    .await;
+
+    info!(
+        "<--- Slot {}: Processed {} transactions in {:.3?}{}",
         slot,
-        txns: usdc_txns,
-    }
+        txn_count,
+        slot_start_time.elapsed(),
+        if rejections.unsigned == 0 {
+            "".to_string()
+        } else {
+            format!(". ({} skipped)", rejections.unsigned)
+        },
+    );
+    debug!("     Slot {} rejections: {}", slot, rejections);
+
+    Ok((
+        SignedUsdcTransactionsBySlot {
+            slot,
+            transactions,
+            supply_changes,
+            account_events,
+            failed_transactions,
+        },
+        rejections,
+        reconciliation,
+    ))
+}
+
+// Several blocks are fetched concurrently, but the results are still yielded in slot order
+pub fn process_slots<'a>(
+    rpc_client: &'a RpcClient,
+    owners: &'a OwnerCache,
+    slots: &'a [u64],
+    config: &'a Config,
+) -> impl Stream<Item = SlotResult> + 'a {
+    stream::iter(slots.iter().copied())
+        .map(move |slot| process_slot_txns(rpc_client, owners, slot, config))
+        .buffered(config.max_concurrent_slots)
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[cfg(test)]
-mod unit_tests;
+pub mod unit_tests;
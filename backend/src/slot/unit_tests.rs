@@ -1,19 +1,34 @@
 use crate::{
-    process_slot_txns,
-    transaction::unit_tests::{get_ui_transaction, get_ui_txn_status_meta},
+    config::{Config, RetryPolicy},
+    owner::OwnerCache,
+    slot::{get_retry_delay, process_slot_txns, process_slots, DeadLetter, DeadLetters, SlotError},
+    solana::TransactionStatus,
+    transaction::unit_tests::{get_ui_transaction, get_ui_txn_status_meta, get_v0_txn, V0_DESTINATION},
+    MAX_DEAD_LETTERS,
 };
 
 use async_trait::async_trait;
-use env_logger;
 use serde_json::{json, Value};
 use solana_client::{
     client_error,
-    rpc_client::{RpcClient, RpcClientConfig},
-    rpc_request::RpcRequest,
+    nonblocking::rpc_client::RpcClient,
+    rpc_client::RpcClientConfig,
+    rpc_custom_error::JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+    rpc_request::{RpcError, RpcRequest, RpcResponseErrorData},
     rpc_sender::{RpcSender, RpcTransportStats},
 };
 use solana_commitment_config::CommitmentConfig;
-use std::collections::HashMap;
+use solana_sdk::transaction::{TransactionError, TransactionVersion};
+use futures::StreamExt;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time::sleep;
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 fn get_mock_response(with_meta_error: bool) -> Value {
@@ -39,24 +54,54 @@ fn get_mock_response(with_meta_error: bool) -> Value {
     })
 }
 
-struct MockRpcSender {
-    responses: HashMap<RpcRequest, Value>,
+pub struct MockRpcSender {
+    pub responses: HashMap<RpcRequest, Value>,
+    // If set, the higher the slot number, the sooner the response arrives
+    pub delay_by_slot: bool,
+    // Fail this many getBlock requests with the given RPC error code before responding normally
+    pub failure_code: i64,
+    pub failures: u32,
+    pub get_block_calls: Arc<AtomicU32>,
+}
+
+impl MockRpcSender {
+    pub fn new(responses: HashMap<RpcRequest, Value>) -> Self {
+        MockRpcSender {
+            responses,
+            delay_by_slot: false,
+            failure_code: 0,
+            failures: 0,
+            get_block_calls: Arc::new(AtomicU32::new(0)),
+        }
+    }
 }
 
 #[async_trait]
 impl RpcSender for MockRpcSender {
-    async fn send(&self, request: RpcRequest, _params: Value) -> client_error::Result<Value> {
+    async fn send(&self, request: RpcRequest, params: Value) -> client_error::Result<Value> {
+        if self.delay_by_slot {
+            let slot = params[0].as_u64().unwrap_or_default();
+            sleep(Duration::from_millis(10 * (10 - slot % 10))).await;
+        }
+
+        if request == RpcRequest::GetBlock && self.get_block_calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(client_error::ClientError::from(
+                client_error::ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                    code: self.failure_code,
+                    message: "MockRpcSender has been configured to fail".to_string(),
+                    data: RpcResponseErrorData::Empty,
+                }),
+            ));
+        }
+
         if let Some(response) = self.responses.get(&request) {
             Ok(response.clone())
         } else {
             Err(client_error::ClientError::from(
-                client_error::ClientErrorKind::Io(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!(
-                        "MockRpcSender has not been configured to respond to request: {:#?}",
-                        request
-                    ),
-                )),
+                client_error::ClientErrorKind::Io(std::io::Error::other(format!(
+                    "MockRpcSender has not been configured to respond to request: {:#?}",
+                    request
+                ))),
             ))
         }
     }
@@ -70,9 +115,9 @@ impl RpcSender for MockRpcSender {
     }
 }
 
-fn create_mock_client(responses: HashMap<RpcRequest, Value>) -> RpcClient {
+pub fn create_mock_client(sender: MockRpcSender) -> RpcClient {
     RpcClient::new_sender(
-        MockRpcSender { responses },
+        sender,
         RpcClientConfig {
             commitment_config: CommitmentConfig::finalized(),
             confirm_transaction_initial_timeout: None,
@@ -80,46 +125,290 @@ fn create_mock_client(responses: HashMap<RpcRequest, Value>) -> RpcClient {
     )
 }
 
+fn get_fast_retry_config() -> Config {
+    Config {
+        retry: RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+        },
+        ..Config::default()
+    }
+}
+
+pub fn get_block_responses() -> HashMap<RpcRequest, Value> {
+    let mut responses = HashMap::new();
+    responses.insert(RpcRequest::GetBlock, get_mock_response(false));
+    responses
+}
+
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-#[test]
-fn test_01_should_process_valid_txn() -> Result<(), String> {
-    env_logger::builder()
+#[tokio::test]
+async fn test_01_should_process_valid_txn() -> Result<(), String> {
+    let _ = env_logger::builder()
+        .is_test(true)
+        .format_timestamp_millis()
+        .try_init();
+
+    let test_slot = 123456789;
+    let mock_client = create_mock_client(MockRpcSender::new(get_block_responses()));
+    let (result, ..) = process_slot_txns(&mock_client, &OwnerCache::default(), test_slot, &Config::default())
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+
+    if result.transactions.is_empty() {
+        Err("Failed to process valid transaction".to_string())
+    } else if result.transactions.iter().any(|transaction| transaction.block_time != Some(1739353792)) {
+        Err("Transactions should carry the time of their block".to_string())
+    } else if result
+        .transactions
+        .iter()
+        .any(|transaction| transaction.slot != test_slot || transaction.status != TransactionStatus::Success)
+    {
+        Err("Transactions should carry their slot and status".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_02_should_skip_txn_with_meta_error() -> Result<(), String> {
+    let _ = env_logger::builder()
         .is_test(true)
         .format_timestamp_millis()
-        .init();
+        .try_init();
 
     let test_slot = 123456789;
     let mut responses = HashMap::new();
-    responses.insert(RpcRequest::GetBlock, get_mock_response(false));
+    responses.insert(RpcRequest::GetBlock, get_mock_response(true));
+
+    let mock_client = create_mock_client(MockRpcSender::new(responses));
+    let (result, ..) = process_slot_txns(&mock_client, &OwnerCache::default(), test_slot, &Config::default())
+        .await
+        .map_err(|e| format!("{:?}", e))?;
+
+    if !result.transactions.is_empty() {
+        Err("Should have skipped processing a txn with status meta error".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_03_should_yield_concurrently_fetched_slots_in_order() -> Result<(), String> {
+    let _ = env_logger::builder()
+        .is_test(true)
+        .format_timestamp_millis()
+        .try_init();
 
-    let mock_client = create_mock_client(responses.clone());
-    let result = process_slot_txns(&mock_client, test_slot);
+    let test_slots: Vec<u64> = (1..=6).collect();
+    let mock_client = create_mock_client(MockRpcSender {
+        delay_by_slot: true,
+        ..MockRpcSender::new(get_block_responses())
+    });
+    let config = Config {
+        max_concurrent_slots: 3,
+        ..Config::default()
+    };
+    let processed_slots: Vec<u64> = process_slots(&mock_client, &OwnerCache::default(), &test_slots, &config)
+        .filter_map(|result| async move { result.ok().map(|(txns_by_slot, ..)| txns_by_slot.slot) })
+        .collect()
+        .await;
 
-    if result.txns.len() > 0 {
+    if processed_slots == test_slots {
         Ok(())
     } else {
-        Err("Failed to process valid transaction".to_string())
+        Err(format!("Slots processed out of order: {:?}", processed_slots))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_04_should_retry_transient_get_block_error() -> Result<(), String> {
+    let sender = MockRpcSender {
+        failure_code: -32004,
+        failures: 2,
+        ..MockRpcSender::new(get_block_responses())
+    };
+    let get_block_calls = Arc::clone(&sender.get_block_calls);
+    let mock_client = create_mock_client(sender);
+
+    match process_slot_txns(&mock_client, &OwnerCache::default(), 123456789, &get_fast_retry_config()).await {
+        Ok(_) if get_block_calls.load(Ordering::SeqCst) == 3 => Ok(()),
+        Ok(_) => Err(format!(
+            "Expected 3 getBlock requests, got {}",
+            get_block_calls.load(Ordering::SeqCst)
+        )),
+        Err(e) => Err(format!("Should have succeeded on the third attempt, got {:?}", e)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_05_should_not_retry_skipped_slot() -> Result<(), String> {
+    let sender = MockRpcSender {
+        failure_code: JSON_RPC_SERVER_ERROR_SLOT_SKIPPED,
+        failures: 1,
+        ..MockRpcSender::new(get_block_responses())
+    };
+    let get_block_calls = Arc::clone(&sender.get_block_calls);
+    let mock_client = create_mock_client(sender);
+
+    match process_slot_txns(&mock_client, &OwnerCache::default(), 123456789, &get_fast_retry_config()).await {
+        Err(SlotError::Skipped(123456789)) if get_block_calls.load(Ordering::SeqCst) == 1 => Ok(()),
+        Err(SlotError::Skipped(_)) => Err("Should not have retried a skipped slot".to_string()),
+        _ => Err("Should have reported a skipped slot".to_string()),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_06_should_give_up_after_max_attempts() -> Result<(), String> {
+    let mock_client = create_mock_client(MockRpcSender {
+        failure_code: -32004,
+        failures: u32::MAX,
+        ..MockRpcSender::new(get_block_responses())
+    });
+
+    match process_slot_txns(&mock_client, &OwnerCache::default(), 123456789, &get_fast_retry_config()).await {
+        Err(SlotError::Failed(dead_letter)) if dead_letter.slot == 123456789 && dead_letter.attempts == 3 => Ok(()),
+        Err(SlotError::Failed(dead_letter)) => Err(format!("Wrong dead letter {:?}", dead_letter)),
+        _ => Err("Should have given up fetching the block".to_string()),
     }
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
-fn test_02_should_skip_txn_with_meta_error() -> Result<(), String> {
-    env_logger::builder()
+fn test_07_should_cap_jittered_exponential_backoff() -> Result<(), String> {
+    let retry = RetryPolicy {
+        max_attempts: 10,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+
+    for (attempt, expected) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (9, 1000)] {
+        let delay = get_retry_delay(&retry, attempt);
+        let expected = Duration::from_millis(expected);
+
+        if delay < expected / 2 || delay > expected {
+            return Err(format!(
+                "Retry delay for attempt {} should be between {:?} and {:?}, got {:?}",
+                attempt,
+                expected / 2,
+                expected,
+                delay
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_08_should_record_failed_txn_when_configured() -> Result<(), String> {
+    let _ = env_logger::builder()
         .is_test(true)
         .format_timestamp_millis()
-        .init();
+        .try_init();
 
     let test_slot = 123456789;
     let mut responses = HashMap::new();
     responses.insert(RpcRequest::GetBlock, get_mock_response(true));
 
-    let mock_client = create_mock_client(responses.clone());
-    let result = process_slot_txns(&mock_client, test_slot);
+    let mock_client = create_mock_client(MockRpcSender::new(responses));
+    let config = Config {
+        record_failed_transactions: true,
+        ..Config::default()
+    };
+    let (result, ..) = process_slot_txns(&mock_client, &OwnerCache::default(), test_slot, &config)
+        .await
+        .map_err(|e| format!("{:?}", e))?;
 
-    if result.txns.len() > 0 {
-        Err("Should have skipped processing a txn with status meta error".to_string())
-    } else {
+    if !result.transactions.is_empty() {
+        return Err("The transfers of a failed transaction should not be listed".to_string());
+    }
+
+    match result.failed_transactions.as_slice() {
+        [failed_txn]
+            if failed_txn.transaction.slot == test_slot
+                && failed_txn.transaction.block_time == Some(1739353792)
+                && failed_txn.transaction.status == TransactionStatus::Failed
+                && failed_txn.error == TransactionError::MissingSignatureForFee
+                && failed_txn.error_message == TransactionError::MissingSignatureForFee.to_string()
+                && failed_txn.token_error.is_none()
+                && !failed_txn.transaction.transfers.is_empty() =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Wrong failed transactions {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[tokio::test]
+async fn test_09_should_process_v0_txn_with_loaded_addresses() -> Result<(), String> {
+    let _ = env_logger::builder()
+        .is_test(true)
+        .format_timestamp_millis()
+        .try_init();
+
+    let test_slot = 123456789;
+    let mut block = get_mock_response(false);
+    let mut responses = HashMap::new();
+
+    // A v0 transaction whose destination is loaded from an address lookup table, which the jsonParsed encoding lists
+    // among the account keys
+    block["transactions"] = json!([get_v0_txn(true)]);
+    responses.insert(RpcRequest::GetBlock, block);
+
+    let mock_client = create_mock_client(MockRpcSender::new(responses));
+    let (result, rejections, reconciliation) =
+        process_slot_txns(&mock_client, &OwnerCache::default(), test_slot, &Config::default())
+            .await
+            .map_err(|e| format!("{:?}", e))?;
+
+    match result.transactions.as_slice() {
+        [transaction]
+            if transaction.slot == test_slot
+                && transaction.version == Some(TransactionVersion::Number(0))
+                && transaction.transfers.len() == 2
+                && transaction.transfers.iter().all(|transfer| transfer.txn.to == V0_DESTINATION)
+                && rejections.unsupported_encoding == 0
+                && reconciliation.agreed == 1 =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Wrong transactions {:?}, with rejections {}", other, rejections)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_10_should_give_up_on_the_oldest_dead_letters_once_full() -> Result<(), String> {
+    let mut dead_letters = DeadLetters::default();
+    let dead_letter = |slot| DeadLetter {
+        slot,
+        attempts: 5,
+        error: "Timed out".to_string(),
+    };
+
+    // Slot 1 failing again replaces its dead letter
+    for slot in [1, 1].into_iter().chain(1..=MAX_DEAD_LETTERS as u64 + 2) {
+        dead_letters.insert(dead_letter(slot));
+    }
+
+    let value = serde_json::to_value(&dead_letters).map_err(|e| e.to_string())?;
+    let slots: Vec<u64> = dead_letters.slots().collect();
+
+    if dead_letters.dropped == 2
+        && slots == (3..=MAX_DEAD_LETTERS as u64 + 2).collect::<Vec<u64>>()
+        && value["dead_letters"][0]["slot"] == 3
+    {
         Ok(())
+    } else {
+        Err(format!("Wrong dead letters: {} dropped, {} kept", dead_letters.dropped, slots.len()))
     }
 }
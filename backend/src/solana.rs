@@ -1,32 +1,367 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
+use solana_transaction_error::TransactionError;
+use solana_transaction::versioned::TransactionVersion;
+use std::sync::Arc;
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-#[derive(Clone, Debug, Deserialize, Serialize)]
+// Serialized in the shape of version 1 of the API, which lists every transfer along with the details of the
+// transaction that performed it
+#[derive(Clone, Debug, Default)]
 pub struct SignedUsdcTransactionsBySlot {
     pub slot: u64,
-    pub txns: Vec<SignedUsdcTransaction>,
+    // The transactions that transferred any of the monitored tokens, in execution order
+    pub transactions: Vec<UsdcTransaction>,
+    // Only reported by /supply, so not part of the transactions API
+    pub supply_changes: Vec<SupplyChange>,
+    // Only reported by /addresses/{address}/events, so not part of the transactions API either
+    pub account_events: Vec<AccountEvent>,
+    // Only kept if RECORD_FAILED_TRANSACTIONS is set, and only reported by /failed_transactions
+    pub failed_transactions: Vec<FailedTransaction>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+// A transfer along with the details of the transaction that performed it, as listed by version 1 of the API
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct SignedUsdcTransaction {
-    pub signatures: Vec<String>,
-    pub txn: UsdcTransaction,
+    pub signatures: Arc<[String]>,
+    pub mint: String,
+    pub symbol: String,
+    pub txn: UsdcTransfer,
+    pub block_time: Option<i64>,
+    pub fee_payer: String,
+    pub transaction_fee: Option<u64>,
+    pub compute_units_consumed: Option<u64>,
+    pub version: Option<TransactionVersion>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct UsdcTransaction {
+impl SignedUsdcTransaction {
+    pub fn new(transaction: &UsdcTransaction, transfer: &TransferRecord) -> Self {
+        SignedUsdcTransaction {
+            signatures: transaction.signatures.clone(),
+            mint: transfer.mint.clone(),
+            symbol: transfer.symbol.clone(),
+            txn: transfer.txn.clone(),
+            block_time: transaction.block_time,
+            fee_payer: transaction.fee_payer.clone(),
+            transaction_fee: transaction.transaction_fee,
+            compute_units_consumed: transaction.compute_units_consumed,
+            version: transaction.version.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UsdcTransfer {
     pub from: String,
     pub to: String,
-    pub amount: f64,
+    // Wallets owning the source and destination token accounts, when known
+    pub from_owner: Option<String>,
+    pub to_owner: Option<String>,
+    // Amount in the token's base units.  Divide by 10^decimals to get the UI amount
+    pub amount: u64,
+    pub decimals: u8,
+    // Exact decimal rendering of amount
+    pub ui_amount: String,
+    // Token-2022 transfer fee withheld from amount, in the token's base units
+    pub fee: Option<u64>,
+    // Index of the top-level instruction that either is, or invoked (via CPI), the transfer instruction
+    pub instruction_index: u8,
+    // 1 for a top-level instruction, 2 or more for an instruction invoked via CPI
+    pub stack_height: u32,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SupplyChangeKind {
+    Mint,
+    Burn,
+}
+
+impl SupplyChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SupplyChangeKind::Mint => "mint",
+            SupplyChangeKind::Burn => "burn",
+        }
+    }
+}
+
+// Tokens issued into (mint), or destroyed from (burn), a token account
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SupplyChange {
+    #[serde(rename = "type")]
+    pub kind: SupplyChangeKind,
+    pub signature: String,
+    pub mint: String,
+    pub symbol: String,
+    // Token account credited by a mint, or debited by a burn
+    pub account: String,
+    // Mint authority of a mint, or the owner (or delegate) of the account debited by a burn
+    pub authority: String,
+    pub amount: u64,
+    pub decimals: u8,
+    pub ui_amount: String,
+    pub instruction_index: u8,
+    pub stack_height: u32,
+    pub block_time: Option<i64>,
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-impl std::fmt::Display for UsdcTransaction {
+// What a single token instruction did to one of the monitored tokens.  Amounts are in the token's base units, and
+// account is always the token account acted upon (or, when setting the authority of a mint, the mint itself)
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UsdcEvent {
+    Transfer(UsdcTransfer),
+    Mint {
+        account: String,
+        authority: String,
+        amount: u64,
+        decimals: u8,
+        ui_amount: String,
+    },
+    Burn {
+        account: String,
+        authority: String,
+        amount: u64,
+        decimals: u8,
+        ui_amount: String,
+    },
+    // The delegate may transfer or burn up to amount from the account
+    Approve {
+        account: String,
+        delegate: String,
+        owner: String,
+        amount: u64,
+        decimals: u8,
+        ui_amount: String,
+    },
+    Revoke {
+        account: String,
+        owner: String,
+    },
+    // Any lamports left in the account are sent to destination
+    CloseAccount {
+        account: String,
+        destination: String,
+        owner: String,
+    },
+    FreezeAccount {
+        account: String,
+        freeze_authority: String,
+    },
+    ThawAccount {
+        account: String,
+        freeze_authority: String,
+    },
+    // A new_authority of None removes the authority altogether
+    SetAuthority {
+        account: String,
+        authority_type: String,
+        new_authority: Option<String>,
+        authority: String,
+    },
+}
+
+impl UsdcEvent {
+    pub fn get_type(&self) -> &'static str {
+        match self {
+            UsdcEvent::Transfer(_) => "transfer",
+            UsdcEvent::Mint { .. } => "mint",
+            UsdcEvent::Burn { .. } => "burn",
+            UsdcEvent::Approve { .. } => "approve",
+            UsdcEvent::Revoke { .. } => "revoke",
+            UsdcEvent::CloseAccount { .. } => "close_account",
+            UsdcEvent::FreezeAccount { .. } => "freeze_account",
+            UsdcEvent::ThawAccount { .. } => "thaw_account",
+            UsdcEvent::SetAuthority { .. } => "set_authority",
+        }
+    }
+
+    // The token account acted upon.  For a transfer, this is the source
+    pub fn get_account(&self) -> &str {
+        match self {
+            UsdcEvent::Transfer(txn) => &txn.from,
+            UsdcEvent::Mint { account, .. }
+            | UsdcEvent::Burn { account, .. }
+            | UsdcEvent::Approve { account, .. }
+            | UsdcEvent::Revoke { account, .. }
+            | UsdcEvent::CloseAccount { account, .. }
+            | UsdcEvent::FreezeAccount { account, .. }
+            | UsdcEvent::ThawAccount { account, .. }
+            | UsdcEvent::SetAuthority { account, .. } => account,
+        }
+    }
+}
+
+// An event other than a transfer, mint or burn, such as a delegation or the closure of an account
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AccountEvent {
+    pub signature: String,
+    pub mint: String,
+    pub symbol: String,
+    #[serde(flatten)]
+    pub event: UsdcEvent,
+    pub instruction_index: u8,
+    pub stack_height: u32,
+    pub block_time: Option<i64>,
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Version 2 of the API describes each transaction once, along with the transfers it performed, rather than repeating
+// the transaction's details in every transfer
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    #[default]
+    Success,
+    Failed,
+}
+
+impl TransactionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionStatus::Success => "success",
+            TransactionStatus::Failed => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct TransferRecord {
+    pub mint: String,
+    pub symbol: String,
+    #[serde(flatten)]
+    pub txn: UsdcTransfer,
+}
+
+// A transaction that transferred any of the monitored tokens.  The transfers are in execution order
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct UsdcTransaction {
+    // First signature, which identifies the transaction
+    pub signature: String,
+    pub signatures: Arc<[String]>,
+    pub slot: u64,
+    // Index of the transaction within its block.  Transactions stored before this was recorded are numbered by the
+    // position of their first transfer within the slot instead
+    #[serde(default)]
+    pub position: u32,
+    // In seconds since the Unix epoch.  Not reported for some old blocks
+    pub block_time: Option<i64>,
+    pub status: TransactionStatus,
+    // First signer of the transaction, who paid its fee
+    pub fee_payer: String,
+    // Fee paid for the transaction, in lamports
+    pub transaction_fee: Option<u64>,
+    pub compute_units_consumed: Option<u64>,
+    // Either "legacy" or 0.  Not reported by nodes that pre-date versioned transactions
+    pub version: Option<TransactionVersion>,
+    pub transfers: Vec<TransferRecord>,
+}
+
+impl UsdcTransaction {
+    // The transfers in the shape of version 1 of the API
+    pub fn to_signed_txns(&self) -> impl Iterator<Item = SignedUsdcTransaction> + '_ {
+        self.transfers.iter().map(|transfer| SignedUsdcTransaction::new(self, transfer))
+    }
+}
+
+// A transaction that failed, and so moved no tokens, but that would have transferred the monitored tokens had it
+// succeeded.  The transfers are those it attempted, and its status is always failed
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct FailedTransaction {
+    #[serde(flatten)]
+    pub transaction: UsdcTransaction,
+    // As reported by the RPC node, e.g. {"InstructionError": [0, {"Custom": 1}]}
+    pub error: TransactionError,
+    // The same error in words, e.g. "Error processing Instruction 0: custom program error: 0x1"
+    pub error_message: String,
+    // Name of the error, e.g. insufficient_funds, when it was raised by one of the token programs
+    pub token_error: Option<String>,
+}
+
+impl FailedTransaction {
+    pub fn new(transaction: UsdcTransaction, error: TransactionError, token_error: Option<String>) -> Self {
+        FailedTransaction {
+            transaction,
+            error_message: error.to_string(),
+            error,
+            token_error,
+        }
+    }
+}
+
+impl SignedUsdcTransactionsBySlot {
+    // Whether the slot holds nothing worth keeping
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+            && self.supply_changes.is_empty()
+            && self.account_events.is_empty()
+            && self.failed_transactions.is_empty()
+    }
+
+    pub fn transfer_count(&self) -> usize {
+        self.transactions.iter().map(|transaction| transaction.transfers.len()).sum()
+    }
+
+    // Every transfer, along with the transaction that performed it, in execution order
+    pub fn transfers(&self) -> impl Iterator<Item = (&UsdcTransaction, &TransferRecord)> {
+        self.transactions
+            .iter()
+            .flat_map(|transaction| transaction.transfers.iter().map(move |transfer| (transaction, transfer)))
+    }
+
+    pub fn to_signed_txns(&self) -> Vec<SignedUsdcTransaction> {
+        self.transactions.iter().flat_map(UsdcTransaction::to_signed_txns).collect()
+    }
+}
+
+impl Serialize for SignedUsdcTransactionsBySlot {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        struct Slot<'a> {
+            slot: u64,
+            txns: Vec<SignedUsdcTransaction>,
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            supply_changes: &'a [SupplyChange],
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            account_events: &'a [AccountEvent],
+            #[serde(skip_serializing_if = "<[_]>::is_empty")]
+            failed_transactions: &'a [FailedTransaction],
+        }
+
+        Slot {
+            slot: self.slot,
+            txns: self.to_signed_txns(),
+            supply_changes: &self.supply_changes,
+            account_events: &self.account_events,
+            failed_transactions: &self.failed_transactions,
+        }
+        .serialize(serializer)
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Render a base unit amount as a decimal string without going through floating point.  Trailing zeros in the
+// fractional part are trimmed, so 1470000 with 6 decimals becomes "1.47" and 5000000 becomes "5"
+pub fn format_ui_amount(amount: u64, decimals: u8) -> String {
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", amount, width = decimals + 1);
+    let (int_part, frac_part) = digits.split_at(digits.len() - decimals);
+    let frac_part = frac_part.trim_end_matches('0');
+
+    if frac_part.is_empty() {
+        int_part.to_string()
+    } else {
+        format!("{}.{}", int_part, frac_part)
+    }
+}
+
+impl std::fmt::Display for TransferRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(
             f,
-            "TX detected: {} sent {} USDC to {}",
-            self.from, self.amount, self.to
+            "TX detected: {} sent {} {} to {}",
+            self.txn.from, self.txn.ui_amount, self.symbol, self.txn.to
         )
     }
 }
@@ -0,0 +1,266 @@
+use crate::{
+    account_event::{get_address_events, AccountEventsQuery, AddressEvents},
+    address::{get_history, sum_flow_totals, AddressHistory},
+    backfill::BackfillProgress,
+    balance::ReconciliationReport,
+    config::Config,
+    database::Database,
+    failed::{find_failed_transactions, FailedTransactions, FailedTransactionsQuery},
+    owner::OwnerCache,
+    parse_error::ParseErrorCounts,
+    query::{TransactionRecordsPage, TransactionsPage, TransactionsQuery},
+    retention::{EvictionSink, JsonLinesSink, TransactionBuffer},
+    slot::{DeadLetter, DeadLetters, SlotError, SlotResult},
+    solana::{SignedUsdcTransactionsBySlot, UsdcTransaction},
+    supply::{sum_supply_changes, SupplyQuery, SupplyReport},
+    EVENT_CHANNEL_CAPACITY,
+};
+
+use log::{debug, error};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use std::sync::Arc;
+use tokio::{
+    sync::{broadcast, Mutex},
+    task,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// State shared between the monitor task, any backfill job and the axum handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub config: Arc<Config>,
+    pub rpc_client: Arc<RpcClient>,
+    pub owners: Arc<OwnerCache>,
+    // When configured, every slot containing transfers (or other events) is also written here, and requests are served
+    // from here
+    pub database: Option<Arc<Database>>,
+    // Without a database, the most recent slots containing transfers (or other events), within the limits of the
+    // retention policy.  With one, this stays empty, as every request is served from the database
+    pub txns: Arc<Mutex<TransactionBuffer>>,
+    pub eviction_sink: Option<Arc<dyn EvictionSink>>,
+    pub parse_errors: Arc<Mutex<ParseErrorCounts>>,
+    pub reconciliation: Arc<Mutex<ReconciliationReport>>,
+    pub dead_letters: Arc<Mutex<DeadLetters>>,
+    pub backfill: Arc<Mutex<Option<BackfillProgress>>>,
+    // Slots containing transfers, as they are detected by the monitor
+    pub events: broadcast::Sender<Arc<SignedUsdcTransactionsBySlot>>,
+}
+
+impl AppState {
+    pub fn new(config: Config, rpc_client: RpcClient, database: Option<Database>) -> Self {
+        AppState {
+            txns: Arc::new(Mutex::new(TransactionBuffer::new(config.retention.clone()))),
+            eviction_sink: config
+                .eviction_path
+                .clone()
+                .map(|path| Arc::new(JsonLinesSink::new(path)) as Arc<dyn EvictionSink>),
+            config: Arc::new(config),
+            rpc_client: Arc::new(rpc_client),
+            owners: Arc::default(),
+            database: database.map(Arc::new),
+            parse_errors: Arc::default(),
+            reconciliation: Arc::default(),
+            dead_letters: Arc::default(),
+            backfill: Arc::default(),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+        }
+    }
+
+    pub fn publish(&self, txns_by_slot: SignedUsdcTransactionsBySlot) {
+        // An error only means that nobody is currently subscribed
+        let _ = self.events.send(Arc::new(txns_by_slot));
+    }
+
+    // Store the outcome of processing one slot and return the slot number.  A slot may be processed more than once
+    // (a backfill overlapping the monitored range, or a resumed backfill), so any earlier result is replaced.  A slot
+    // whose block could not be fetched, or could not be stored, is recorded as a dead letter and returned as the error
+    pub async fn record_slot(
+        &self,
+        result: SlotResult,
+    ) -> Result<u64, DeadLetter> {
+        let slot = match result {
+            Ok((txns_by_slot, rejections, reconciliation)) => {
+                let slot = txns_by_slot.slot;
+
+                self.parse_errors.lock().await.merge(&rejections);
+                self.reconciliation.lock().await.merge(&reconciliation);
+
+                if !txns_by_slot.is_empty() {
+                    if let Err(e) = self.write_slot(txns_by_slot).await {
+                        error!("Unable to store slot {}: {}", slot, e);
+                        return Err(self.add_dead_letter(DeadLetter {
+                            slot,
+                            attempts: 1,
+                            error: e,
+                        })
+                        .await);
+                    }
+                }
+
+                slot
+            }
+            Err(SlotError::Skipped(slot)) => slot,
+            // Record the slot so the gap can be re-fetched later
+            Err(SlotError::Failed(dead_letter)) => return Err(self.add_dead_letter(dead_letter).await),
+        };
+
+        // The block has now been fetched, so the slot is no longer a gap
+        self.dead_letters.lock().await.remove(slot);
+        Ok(slot)
+    }
+
+    async fn add_dead_letter(&self, dead_letter: DeadLetter) -> DeadLetter {
+        self.dead_letters.lock().await.insert(dead_letter.clone());
+        dead_letter
+    }
+
+    async fn write_slot(&self, txns_by_slot: SignedUsdcTransactionsBySlot) -> Result<(), String> {
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| database.write_slot(&txns_by_slot)).await;
+        }
+
+        let evicted = self.txns.lock().await.insert(txns_by_slot);
+
+        self.evict(evicted).await;
+        Ok(())
+    }
+
+    async fn evict(&self, evicted: Vec<SignedUsdcTransactionsBySlot>) {
+        let (Some(sink), Some(last)) = (&self.eviction_sink, evicted.last()) else {
+            return;
+        };
+        let sink = Arc::clone(sink);
+
+        debug!("Evicting {} slots from memory, up to slot {}", evicted.len(), last.slot);
+
+        match task::spawn_blocking(move || sink.evict(&evicted)).await {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => error!("Unable to hand evicted slots to the eviction sink: {}", e),
+            Err(e) => error!("Unable to hand evicted slots to the eviction sink: {}", e),
+        }
+    }
+
+    // One page of the stored slots matching the query
+    pub async fn read_page(&self, query: TransactionsQuery) -> Result<TransactionsPage, String> {
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| database.read_page(&query)).await;
+        }
+
+        Ok(query.paginate(self.txns.lock().await.iter()))
+    }
+
+    // One page of the stored transactions matching the query, as version 2 of the API pages them
+    pub async fn read_transaction_page(&self, query: TransactionsQuery) -> Result<TransactionRecordsPage, String> {
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| database.read_transaction_page(&query)).await;
+        }
+
+        Ok(query.paginate_transactions(self.txns.lock().await.iter()))
+    }
+
+    // The stored transaction with this signature
+    pub async fn find_signature(&self, signature: String) -> Result<Option<UsdcTransaction>, String> {
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| database.find_signature(&signature)).await;
+        }
+
+        Ok(self
+            .txns
+            .lock()
+            .await
+            .iter()
+            .flat_map(|txns_by_slot| txns_by_slot.transactions.iter())
+            .find(|transaction| transaction.signatures.contains(&signature))
+            .cloned())
+    }
+
+    // One page of the stored transfers from or to this address, along with its flow totals
+    pub async fn read_address_history(
+        &self,
+        address: String,
+        query: TransactionsQuery,
+    ) -> Result<AddressHistory, String> {
+        let query = TransactionsQuery {
+            address: Some(address.clone()),
+            ..query
+        };
+
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| {
+                get_history(&address, &query, database.read_page(&query)?, |query| {
+                    database.read_flow_totals(query)
+                })
+            })
+            .await;
+        }
+
+        let txns = self.txns.lock().await;
+
+        get_history(&address, &query, query.paginate(txns.iter_address(&address)), |query| {
+            Ok(sum_flow_totals(&address, query, txns.iter_address(&address)))
+        })
+    }
+
+    // Totals of the stored mints and burns, by mint
+    pub async fn read_supply(&self, query: SupplyQuery) -> Result<SupplyReport, String> {
+        let totals = if let Some(database) = &self.database {
+            let query = query.clone();
+
+            with_database(database, move |database| database.read_supply_totals(&query)).await?
+        } else {
+            sum_supply_changes(&query, self.txns.lock().await.iter())
+        };
+
+        Ok(SupplyReport { query, totals })
+    }
+
+    // The stored events, other than transfers, mints and burns, acting upon this address
+    pub async fn read_address_events(
+        &self,
+        address: String,
+        query: AccountEventsQuery,
+    ) -> Result<AddressEvents, String> {
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| {
+                database
+                    .read_address_events(&address, &query)
+                    .map(|events| AddressEvents::new(&address, &query, events))
+            })
+            .await;
+        }
+
+        let txns = self.txns.lock().await;
+
+        Ok(get_address_events(&address, &query, txns.iter_address(&address)))
+    }
+
+    // The stored failed transactions that attempted to transfer the monitored tokens
+    pub async fn read_failed_transactions(
+        &self,
+        query: FailedTransactionsQuery,
+    ) -> Result<FailedTransactions, String> {
+        if let Some(database) = &self.database {
+            return with_database(database, move |database| {
+                database
+                    .read_failed_transactions(&query)
+                    .map(|transactions| FailedTransactions::new(&query, transactions))
+            })
+            .await;
+        }
+
+        Ok(find_failed_transactions(&query, self.txns.lock().await.iter()))
+    }
+}
+
+// Run a request against the database, which blocks, away from the async runtime
+async fn with_database<T: Send + 'static>(
+    database: &Arc<Database>,
+    request: impl FnOnce(&Database) -> rusqlite::Result<T> + Send + 'static,
+) -> Result<T, String> {
+    let database = Arc::clone(database);
+
+    task::spawn_blocking(move || request(&database))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
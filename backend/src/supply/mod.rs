@@ -0,0 +1,114 @@
+use crate::solana::{SignedUsdcTransactionsBySlot, SupplyChange, SupplyChangeKind};
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Query parameters of /supply.  Slot bounds are inclusive, as are time bounds, which are in seconds since the Unix
+// epoch.  A supply change whose block time is unknown never matches a time bound
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SupplyQuery {
+    pub symbol: Option<String>,
+    pub from_slot: Option<u64>,
+    pub to_slot: Option<u64>,
+    pub from_time: Option<i64>,
+    pub to_time: Option<i64>,
+}
+
+impl SupplyQuery {
+    pub fn matches(&self, slot: u64, supply_change: &SupplyChange) -> bool {
+        self.symbol.as_ref().is_none_or(|symbol| supply_change.symbol.eq_ignore_ascii_case(symbol))
+            && self.from_slot.is_none_or(|from_slot| slot >= from_slot)
+            && self.to_slot.is_none_or(|to_slot| slot <= to_slot)
+            && self.from_time.is_none_or(|from_time| supply_change.block_time.is_some_and(|time| time >= from_time))
+            && self.to_time.is_none_or(|to_time| supply_change.block_time.is_some_and(|time| time <= to_time))
+    }
+}
+
+// Tokens issued and destroyed by the monitored mints and burns of one token, in the token's base units
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SupplyTotals {
+    pub mint: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub mint_count: u64,
+    pub burn_count: u64,
+    pub minted: u128,
+    pub burned: u128,
+    // Net issuance: minted less burned
+    pub net: i128,
+}
+
+impl SupplyTotals {
+    pub fn new(
+        mint: String,
+        symbol: String,
+        decimals: u8,
+        mint_count: u64,
+        burn_count: u64,
+        minted: u128,
+        burned: u128,
+    ) -> Self {
+        SupplyTotals {
+            mint,
+            symbol,
+            decimals,
+            mint_count,
+            burn_count,
+            minted,
+            burned,
+            net: minted as i128 - burned as i128,
+        }
+    }
+}
+
+// The query's bounds, echoed back, along with the totals of every matching supply change in the store, by mint
+#[derive(Clone, Debug, Serialize)]
+pub struct SupplyReport {
+    #[serde(flatten)]
+    pub query: SupplyQuery,
+    pub totals: Vec<SupplyTotals>,
+}
+
+// Sum the supply changes matching the query, by mint
+pub fn sum_supply_changes<'a>(
+    query: &SupplyQuery,
+    slots: impl Iterator<Item = &'a SignedUsdcTransactionsBySlot>,
+) -> Vec<SupplyTotals> {
+    let mut totals: BTreeMap<String, SupplyTotals> = BTreeMap::new();
+
+    for txns_by_slot in slots {
+        for supply_change in txns_by_slot.supply_changes.iter() {
+            if !query.matches(txns_by_slot.slot, supply_change) {
+                continue;
+            }
+
+            let supply = totals.entry(supply_change.mint.clone()).or_insert_with(|| SupplyTotals {
+                mint: supply_change.mint.clone(),
+                symbol: supply_change.symbol.clone(),
+                decimals: supply_change.decimals,
+                ..SupplyTotals::default()
+            });
+            let amount = supply_change.amount as u128;
+
+            match supply_change.kind {
+                SupplyChangeKind::Mint => {
+                    supply.mint_count += 1;
+                    supply.minted += amount;
+                    supply.net += amount as i128;
+                }
+                SupplyChangeKind::Burn => {
+                    supply.burn_count += 1;
+                    supply.burned += amount;
+                    supply.net -= amount as i128;
+                }
+            }
+        }
+    }
+
+    totals.into_values().collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+pub mod unit_tests;
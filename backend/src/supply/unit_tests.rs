@@ -0,0 +1,98 @@
+use crate::{
+    solana::{SignedUsdcTransactionsBySlot, SupplyChange, SupplyChangeKind},
+    supply::{sum_supply_changes, SupplyQuery, SupplyTotals},
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+pub fn get_supply_change(kind: SupplyChangeKind, symbol: &str, amount: u64, block_time: Option<i64>) -> SupplyChange {
+    SupplyChange {
+        kind,
+        signature: format!("Signature{}", amount),
+        mint: format!("{}Mint", symbol),
+        symbol: symbol.to_string(),
+        account: "Treasury".to_string(),
+        authority: "Issuer".to_string(),
+        amount,
+        decimals: 6,
+        ui_amount: amount.to_string(),
+        instruction_index: 0,
+        stack_height: 1,
+        block_time,
+    }
+}
+
+// The issuer mints 100 USDC and 10 USDT, burns 30 USDC, then mints 5 USDC in a block of unknown time
+fn get_slots() -> Vec<SignedUsdcTransactionsBySlot> {
+    [
+        (1, vec![get_supply_change(SupplyChangeKind::Mint, "USDC", 100, Some(1000))]),
+        (
+            2,
+            vec![
+                get_supply_change(SupplyChangeKind::Burn, "USDC", 30, Some(2000)),
+                get_supply_change(SupplyChangeKind::Mint, "USDT", 10, Some(2000)),
+            ],
+        ),
+        (3, vec![get_supply_change(SupplyChangeKind::Mint, "USDC", 5, None)]),
+    ]
+    .into_iter()
+    .map(|(slot, supply_changes)| SignedUsdcTransactionsBySlot {
+        slot,
+        supply_changes,
+        ..Default::default()
+    })
+    .collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_sum_supply_changes_by_mint() -> Result<(), String> {
+    let totals = sum_supply_changes(&SupplyQuery::default(), get_slots().iter());
+    let expected = vec![
+        SupplyTotals::new("USDCMint".to_string(), "USDC".to_string(), 6, 2, 1, 105, 30),
+        SupplyTotals::new("USDTMint".to_string(), "USDT".to_string(), 6, 1, 0, 10, 0),
+    ];
+
+    if totals == expected && totals[0].net == 75 {
+        Ok(())
+    } else {
+        Err(format!("Wrong totals {:?}", totals))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_only_sum_supply_changes_within_bounds() -> Result<(), String> {
+    let slots = get_slots();
+    let nets: Vec<Vec<(String, i128)>> = [
+        SupplyQuery {
+            symbol: Some("usdc".to_string()),
+            from_slot: Some(2),
+            ..SupplyQuery::default()
+        },
+        // Excludes the mint of unknown time
+        SupplyQuery {
+            symbol: Some("USDC".to_string()),
+            from_time: Some(1000),
+            ..SupplyQuery::default()
+        },
+        SupplyQuery {
+            to_time: Some(1999),
+            ..SupplyQuery::default()
+        },
+    ]
+    .iter()
+    .map(|query| {
+        sum_supply_changes(query, slots.iter())
+            .into_iter()
+            .map(|supply| (supply.symbol, supply.net))
+            .collect()
+    })
+    .collect();
+    let usdc = |net| vec![("USDC".to_string(), net)];
+
+    if nets == [usdc(-25), usdc(70), usdc(100)] {
+        Ok(())
+    } else {
+        Err(format!("Wrong net issuance {:?}", nets))
+    }
+}
@@ -0,0 +1,47 @@
+use crate::solana::{SignedUsdcTransactionsBySlot, TransferRecord, UsdcTransaction, UsdcTransfer};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// A transfer of a 6-decimal token, of mint USDCMint when the symbol is USDC.  Tests needing more set the remaining
+// fields on top of it
+pub fn get_transfer(symbol: &str, from: &str, to: &str, amount: u64) -> TransferRecord {
+    TransferRecord {
+        mint: format!("{}Mint", symbol),
+        symbol: symbol.to_string(),
+        txn: UsdcTransfer {
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            decimals: 6,
+            ..UsdcTransfer::default()
+        },
+    }
+}
+
+// A transaction performing that one transfer, signed by a signature named after its amount, e.g. Signature100
+pub fn get_transaction(symbol: &str, from: &str, to: &str, amount: u64) -> UsdcTransaction {
+    let signature = format!("Signature{}", amount);
+
+    UsdcTransaction {
+        signatures: [signature.clone()].into(),
+        signature,
+        transfers: vec![get_transfer(symbol, from, to, amount)],
+        ..UsdcTransaction::default()
+    }
+}
+
+// The transactions are numbered by their position within the slot
+pub fn get_slot(slot: u64, transactions: Vec<UsdcTransaction>) -> SignedUsdcTransactionsBySlot {
+    SignedUsdcTransactionsBySlot {
+        slot,
+        transactions: transactions
+            .into_iter()
+            .enumerate()
+            .map(|(position, transaction)| UsdcTransaction {
+                slot,
+                position: position as u32,
+                ..transaction
+            })
+            .collect(),
+        ..SignedUsdcTransactionsBySlot::default()
+    }
+}
@@ -0,0 +1,85 @@
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, UiLoadedAddresses, UiMessage, UiParsedMessage, UiTransactionStatusMeta,
+    UiTransactionTokenBalance,
+};
+use std::collections::HashMap;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenAccount {
+    pub mint: String,
+    pub decimals: u8,
+    // Wallet that owns the token account.  Nodes only report this from v1.9 onwards
+    pub owner: Option<String>,
+}
+
+// Token accounts touched by a transaction, keyed by token account address
+pub type TokenAccounts = HashMap<String, TokenAccount>;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Every account key of a transaction, in the order in which its instructions and token balances refer to them.  A
+// parsed message already lists the keys that a version 0 transaction loaded from address lookup tables.  Otherwise
+// they are only reported in the status meta, and follow the message's own keys: first the writable, then the readonly
+pub fn get_account_keys(message: &UiMessage, meta: Option<&UiTransactionStatusMeta>) -> Vec<String> {
+    match message {
+        UiMessage::Parsed(UiParsedMessage { account_keys, .. }) => {
+            account_keys.iter().map(|account| account.pubkey.clone()).collect()
+        }
+        UiMessage::Raw(raw) => {
+            let mut account_keys = raw.account_keys.clone();
+
+            if let Some(OptionSerializer::Some(UiLoadedAddresses { writable, readonly })) =
+                meta.map(|meta| &meta.loaded_addresses)
+            {
+                account_keys.extend(writable.iter().cloned());
+                account_keys.extend(readonly.iter().cloned());
+            }
+
+            account_keys
+        }
+    }
+}
+
+fn add_token_balances(
+    token_accounts: &mut TokenAccounts,
+    account_keys: &[String],
+    token_balances: &OptionSerializer<Vec<UiTransactionTokenBalance>>,
+) {
+    if let OptionSerializer::Some(balances) = token_balances {
+        for balance in balances.iter() {
+            if let Some(account) = account_keys.get(balance.account_index as usize) {
+                let owner = match &balance.owner {
+                    OptionSerializer::Some(owner) => Some(owner.clone()),
+                    _ => None,
+                };
+                let token_account = token_accounts
+                    .entry(account.clone())
+                    .or_insert_with(|| TokenAccount {
+                        mint: balance.mint.clone(),
+                        decimals: balance.ui_token_amount.decimals,
+                        owner: None,
+                    });
+
+                token_account.owner = token_account.owner.take().or(owner);
+            }
+        }
+    }
+}
+
+// A plain SPL Token transfer instruction does not identify the mint, so this has to be resolved from the token balances
+// recorded in the transaction's status meta.  Accounts created during the transaction only appear in the post token
+// balances, and closed accounts only in the pre token balances, so both lists must be checked
+pub fn get_token_accounts(account_keys: &[String], meta: Option<&UiTransactionStatusMeta>) -> TokenAccounts {
+    let mut token_accounts = TokenAccounts::new();
+
+    if let Some(meta) = meta {
+        add_token_balances(&mut token_accounts, account_keys, &meta.pre_token_balances);
+        add_token_balances(&mut token_accounts, account_keys, &meta.post_token_balances);
+    }
+
+    token_accounts
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
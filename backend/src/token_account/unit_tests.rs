@@ -0,0 +1,100 @@
+use crate::{
+    token_account::{get_token_accounts, TokenAccount},
+    transaction::unit_tests::get_ui_txn_status_meta,
+    USDC_MINT,
+};
+
+use serde_json::json;
+use solana_transaction_status::{
+    option_serializer::OptionSerializer, UiTransactionStatusMeta, UiTransactionTokenBalance,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+const SOURCE_ACCOUNT: &str = "4yV1DkPtGameUwtM3CzwM23inphbosNbmX4d3MRLaPBc";
+const DESTINATION_ACCOUNT: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+
+fn get_account_keys() -> Vec<String> {
+    vec![SOURCE_ACCOUNT.to_string(), DESTINATION_ACCOUNT.to_string()]
+}
+
+fn get_token_balance(account_index: u8) -> UiTransactionTokenBalance {
+    serde_json::from_value(json!({
+        "accountIndex": account_index,
+        "mint": USDC_MINT,
+        "uiTokenAmount": {
+            "amount": "1470000",
+            "decimals": 6,
+            "uiAmount": 1.47,
+            "uiAmountString": "1.47"
+        },
+        "owner": "E1wid5KyTfkzxWDUmhxhJTKZqVRR4N6kyuTLXB5bPiU",
+        "programId": "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+    }))
+    .unwrap()
+}
+
+fn get_meta_with_token_balances(pre: Vec<u8>, post: Vec<u8>) -> UiTransactionStatusMeta {
+    UiTransactionStatusMeta {
+        pre_token_balances: OptionSerializer::Some(pre.into_iter().map(get_token_balance).collect()),
+        post_token_balances: OptionSerializer::Some(post.into_iter().map(get_token_balance).collect()),
+        ..get_ui_txn_status_meta(false)
+    }
+}
+
+fn check_token_account(account: Option<&TokenAccount>, pubkey: &str) -> Result<(), String> {
+    match account {
+        Some(TokenAccount { mint, decimals: 6, .. }) if mint == USDC_MINT => Ok(()),
+        Some(acc) => Err(format!("Wrong token account details for {}: {:?}", pubkey, acc)),
+        None => Err(format!("Token account {} not found", pubkey)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_find_no_token_accounts_without_meta() -> Result<(), String> {
+    if get_token_accounts(&get_account_keys(), None).is_empty() {
+        Ok(())
+    } else {
+        Err("Should not have found token accounts without a status meta".to_string())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_combine_pre_and_post_token_balances() -> Result<(), String> {
+    let meta = get_meta_with_token_balances(vec![0], vec![1]);
+    let token_accounts = get_token_accounts(&get_account_keys(), Some(&meta));
+
+    check_token_account(token_accounts.get(SOURCE_ACCOUNT), SOURCE_ACCOUNT)?;
+    check_token_account(token_accounts.get(DESTINATION_ACCOUNT), DESTINATION_ACCOUNT)
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_ignore_out_of_range_account_index() -> Result<(), String> {
+    let meta = get_meta_with_token_balances(vec![7], vec![]);
+
+    if get_token_accounts(&get_account_keys(), Some(&meta)).is_empty() {
+        Ok(())
+    } else {
+        Err("Should have ignored a token balance with an unknown account index".to_string())
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_take_owner_from_either_token_balance() -> Result<(), String> {
+    let mut meta = get_meta_with_token_balances(vec![0], vec![0]);
+
+    // Only the post token balance reports the owner
+    if let OptionSerializer::Some(balances) = &mut meta.pre_token_balances {
+        balances[0].owner = OptionSerializer::None;
+    }
+
+    match get_token_accounts(&get_account_keys(), Some(&meta)).get(SOURCE_ACCOUNT) {
+        Some(TokenAccount {
+            owner: Some(owner), ..
+        }) if owner == "E1wid5KyTfkzxWDUmhxhJTKZqVRR4N6kyuTLXB5bPiU" => Ok(()),
+        other => Err(format!("Expected the owner from the post token balance, got {:?}", other)),
+    }
+}
@@ -1,40 +1,206 @@
-use crate::{instruction::process_instruction, solana::SignedUsdcTransaction};
+use crate::{
+    config::{Config, MonitoredMint},
+    instruction::{decode_compiled_instruction, process_instruction},
+    parse_error::{ParseError, ParseErrorCounts},
+    solana::{
+        AccountEvent, SupplyChange, SupplyChangeKind, TransactionStatus, TransferRecord, UsdcEvent, UsdcTransaction,
+        UsdcTransfer,
+    },
+    token_account::{get_account_keys, get_token_accounts},
+};
 
-use log::info;
+use log::{info, warn};
+use solana_pubkey::Pubkey;
 use solana_transaction_status::{
-    EncodedTransaction, EncodedTransactionWithStatusMeta, UiMessage, UiParsedMessage, UiTransaction,
+    option_serializer::OptionSerializer, EncodedTransaction, EncodedTransactionWithStatusMeta, UiInstruction,
+    UiMessage, UiParsedInstruction, UiParsedMessage, UiRawMessage, UiTransaction,
 };
+use std::{borrow::Cow, str::FromStr};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Outer instructions always run at stack height 1.  Nodes that pre-date the stackHeight field do not report a height
+// for inner instructions, so in that case, the best we can say is that the instruction was invoked via CPI
+const OUTER_STACK_HEIGHT: u32 = 1;
+const DEFAULT_INNER_STACK_HEIGHT: u32 = 2;
+
+fn get_stack_height(instruction: &UiInstruction) -> Option<u32> {
+    match instruction {
+        UiInstruction::Compiled(i) => i.stack_height,
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(i)) => i.stack_height,
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(i)) => i.stack_height,
+    }
+}
+
+fn get_transfer_record(
+    (txn, monitored_mint): (UsdcTransfer, &MonitoredMint),
+    instruction_index: u8,
+    stack_height: u32,
+) -> TransferRecord {
+    let transfer = TransferRecord {
+        mint: monitored_mint.mint.clone(),
+        symbol: monitored_mint.symbol.clone(),
+        txn: UsdcTransfer {
+            instruction_index,
+            stack_height,
+            ..txn
+        },
+    };
+
+    info!("{}", transfer);
+
+    transfer
+}
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-pub fn process_transaction(txn: &EncodedTransactionWithStatusMeta) -> (Vec<SignedUsdcTransaction>, u32) {
-    let mut signed_txns: Vec<SignedUsdcTransaction> = Vec::new();
-    let mut unsigned_txn_count: u32 = 0;
-
-    match &txn.transaction {
-        // If present, extract instructions from signed, parsed JSON transaction
-        EncodedTransaction::Json(
-            UiTransaction {
-                message: UiMessage::Parsed(UiParsedMessage { instructions, .. }),
-                signatures,
-                ..
+// What one transaction did to the monitored tokens, along with the instructions that were rejected
+#[derive(Debug, Default)]
+pub struct ProcessedTransaction {
+    // Holds no transfers if the transaction transferred none of the monitored tokens
+    pub transaction: UsdcTransaction,
+    pub supply_changes: Vec<SupplyChange>,
+    pub account_events: Vec<AccountEvent>,
+    pub rejections: ParseErrorCounts,
+}
+
+pub fn process_transaction(
+    txn: &EncodedTransactionWithStatusMeta,
+    config: &Config,
+) -> Result<ProcessedTransaction, ParseError> {
+    // Extract instructions from signed JSON transaction.  These are parsed, unless the transaction was requested in
+    // the json encoding, in which case they are left compiled, and decoded here
+    let (message, signatures) = match &txn.transaction {
+        EncodedTransaction::Json(UiTransaction { message, signatures }) => (message, signatures),
+        _ => return Err(ParseError::UnsupportedEncoding),
+    };
+
+    if signatures.is_empty() {
+        return Err(ParseError::Unsigned);
+    }
+
+    let account_keys = get_account_keys(message, txn.meta.as_ref());
+    let (instructions, pubkeys): (Cow<[UiInstruction]>, Vec<Pubkey>) = match message {
+        UiMessage::Parsed(UiParsedMessage { instructions, .. }) => (Cow::Borrowed(instructions), vec![]),
+        // A key that is not a valid address leaves any instruction referring to it, or to a later key, undecodable
+        UiMessage::Raw(UiRawMessage { instructions, .. }) => (
+            instructions.iter().cloned().map(UiInstruction::Compiled).collect(),
+            account_keys.iter().map_while(|key| Pubkey::from_str(key).ok()).collect(),
+        ),
+    };
+
+    // The slot, position and block time are only known to the caller.  The fee payer is always the first account
+    let mut processed = ProcessedTransaction {
+        transaction: UsdcTransaction {
+            signature: signatures.first().cloned().unwrap_or_default(),
+            signatures: signatures.as_slice().into(),
+            status: match txn.meta.as_ref().and_then(|meta| meta.err.as_ref()) {
+                Some(_) => TransactionStatus::Failed,
+                None => TransactionStatus::Success,
             },
-            ..,
-        ) => instructions.iter().for_each(|i| {
-            if signatures.is_empty() {
-                // info!("Skipping unsigned transaction");
-                unsigned_txn_count += 1;
-            } else if let Some(txn) = process_instruction(i) {
-                info!("{}", txn);
-                signed_txns.push(SignedUsdcTransaction {
-                    signatures: signatures.clone(),
-                    txn,
-                });
+            fee_payer: account_keys.first().cloned().unwrap_or_default(),
+            transaction_fee: txn.meta.as_ref().map(|meta| meta.fee),
+            compute_units_consumed: txn.meta.as_ref().and_then(|meta| match meta.compute_units_consumed {
+                OptionSerializer::Some(compute_units) => Some(compute_units),
+                _ => None,
+            }),
+            version: txn.version.clone(),
+            ..UsdcTransaction::default()
+        },
+        ..ProcessedTransaction::default()
+    };
+    let token_accounts = get_token_accounts(&account_keys, txn.meta.as_ref());
+    let inner_instructions = match &txn.meta {
+        Some(meta) => match &meta.inner_instructions {
+            OptionSerializer::Some(inner) => inner.as_slice(),
+            _ => &[],
+        },
+        None => &[],
+    };
+
+    let mut process = |instruction: &UiInstruction, instruction_index: u8, stack_height: u32| {
+        let decoded = match instruction {
+            UiInstruction::Compiled(compiled) => Some(decode_compiled_instruction(compiled, &pubkeys, config)),
+            _ => None,
+        };
+        let processed_instruction = match decoded {
+            Some(Ok(decoded)) => process_instruction(&decoded, &token_accounts, config),
+            Some(Err(err)) => Err(err),
+            None => process_instruction(instruction, &token_accounts, config),
+        };
+        let (event, monitored_mint) = match processed_instruction {
+            Ok(processed_instruction) => processed_instruction,
+            Err(err) => {
+                // Only a malformed amount indicates a problem with the data itself, so make it visible
+                if let ParseError::BadAmount(_) = err {
+                    warn!("{}: {}", signatures[0], err);
+                }
+
+                processed.rejections.add(&err);
+                return;
+            }
+        };
+        let is_mint = matches!(event, UsdcEvent::Mint { .. });
+
+        // Transfers, and changes to the supply, are reported separately from any other event
+        match event {
+            UsdcEvent::Transfer(usdc_txn) => processed.transaction.transfers.push(get_transfer_record(
+                (usdc_txn, monitored_mint),
+                instruction_index,
+                stack_height,
+            )),
+            UsdcEvent::Mint {
+                account,
+                authority,
+                amount,
+                decimals,
+                ui_amount,
+            }
+            | UsdcEvent::Burn {
+                account,
+                authority,
+                amount,
+                decimals,
+                ui_amount,
+            } => processed.supply_changes.push(SupplyChange {
+                kind: if is_mint { SupplyChangeKind::Mint } else { SupplyChangeKind::Burn },
+                signature: signatures.first().cloned().unwrap_or_default(),
+                mint: monitored_mint.mint.clone(),
+                symbol: monitored_mint.symbol.clone(),
+                account,
+                authority,
+                amount,
+                decimals,
+                ui_amount,
+                instruction_index,
+                stack_height,
+                block_time: None,
+            }),
+            event => processed.account_events.push(AccountEvent {
+                signature: signatures.first().cloned().unwrap_or_default(),
+                mint: monitored_mint.mint.clone(),
+                symbol: monitored_mint.symbol.clone(),
+                event,
+                instruction_index,
+                stack_height,
+                block_time: None,
+            }),
+        }
+    };
+
+    // Walk the instructions in execution order: each top-level instruction is followed by any instructions it invoked
+    // via CPI.  These are grouped by the index of the outer instruction that invoked them
+    for (idx, i) in instructions.iter().enumerate() {
+        let idx = idx as u8;
+
+        process(i, idx, OUTER_STACK_HEIGHT);
+
+        for group in inner_instructions.iter().filter(|group| group.index == idx) {
+            for inner in group.instructions.iter() {
+                process(inner, idx, get_stack_height(inner).unwrap_or(DEFAULT_INNER_STACK_HEIGHT));
             }
-        }),
-        _ => info!("Transaction does not contain a JSON message"),
+        }
     }
 
-    (signed_txns, unsigned_txn_count)
+    Ok(processed)
 }
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
@@ -1,15 +1,28 @@
-use crate::{instruction::test_data::*, transaction::process_transaction};
+use crate::{
+    config::Config,
+    instruction::test_data::*,
+    parse_error::ParseError,
+    solana::{SupplyChangeKind, TransactionStatus},
+    transaction::{process_transaction, ProcessedTransaction},
+    TOKEN_PROGRAM_ID, USDC_MINT,
+};
 
-use solana_sdk::transaction::TransactionError;
+use serde_json::json;
+use solana_sdk::{
+    message::MessageHeader,
+    transaction::{TransactionError, TransactionVersion},
+};
 use solana_transaction_status::{
     option_serializer::OptionSerializer,
     parse_accounts::{ParsedAccount, ParsedAccountSource},
-    EncodedTransaction, EncodedTransactionWithStatusMeta, UiInnerInstructions, UiMessage,
-    UiParsedMessage, UiTransaction, UiTransactionStatusMeta,
+    parse_instruction::ParsedInstruction,
+    EncodedTransaction, EncodedTransactionWithStatusMeta, UiAddressTableLookup, UiCompiledInstruction,
+    UiInnerInstructions, UiInstruction, UiLoadedAddresses, UiMessage, UiParsedInstruction, UiParsedMessage,
+    UiPartiallyDecodedInstruction, UiRawMessage, UiTransaction, UiTransactionStatusMeta, UiTransactionTokenBalance,
 };
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-fn get_signatures() -> Vec<String> {
+pub fn get_signatures() -> Vec<String> {
     vec![
         "32bsAScN7dH7DW3tjP3AnD1RBn8UsDsE4BwVFfGLUKRda4ConrSwbGhAdpUU5p1Jw6k1jvAZD1bHhHdJ6Xvuphjh"
             .to_string(),
@@ -66,6 +79,23 @@ pub fn get_ui_txn_status_meta(with_error: bool) -> UiTransactionStatusMeta {
     }
 }
 
+// Simulate a USDC transfer performed via CPI by the outer instruction at index 0
+fn get_ui_txn_status_meta_with_inner_instructions(stack_height: Option<u32>) -> UiTransactionStatusMeta {
+    let mut inner_instruction = get_parsed_ui_instruction_for_test(1, false);
+
+    if let UiInstruction::Parsed(UiParsedInstruction::Parsed(ref mut parsed)) = inner_instruction {
+        parsed.stack_height = stack_height;
+    }
+
+    UiTransactionStatusMeta {
+        inner_instructions: OptionSerializer::from(Some(vec![UiInnerInstructions {
+            index: 0,
+            instructions: vec![inner_instruction],
+        }])),
+        ..get_ui_txn_status_meta(false)
+    }
+}
+
 pub fn get_ui_transaction(with_signatures: bool) -> UiTransaction {
     UiTransaction {
         signatures: if with_signatures {
@@ -82,16 +112,195 @@ pub fn get_ui_transaction(with_signatures: bool) -> UiTransaction {
     }
 }
 
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// A version 0 transaction in which the fee payer calls an aggregator, which transfers 1 USDC via CPI, then transfers a
+// further 0.25 USDC directly.  The destination token account is loaded from an address lookup table as writable, and
+// the USDC mint as readonly, so the account keys are:
+//   0 fee payer, 1 source, 2 aggregator, 3 token program (static), 4 destination (loaded writable), 5 mint (readonly)
+pub const V0_FEE_PAYER: &str = "E1wid5KyTfkzxWDUmhxhJTKZqVRR4N6kyuTLXB5bPiU";
+pub const V0_SOURCE: &str = "4yV1DkPtGameUwtM3CzwM23inphbosNbmX4d3MRLaPBc";
+pub const V0_DESTINATION: &str = "LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo";
+const V0_DESTINATION_OWNER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+const AGGREGATOR_PROGRAM_ID: &str = "JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4";
+const LOOKUP_TABLE: &str = "2immgwYNHBbyVQKVGCEkgWpi53bLwWNRMB5G2nbgYV17";
+
+fn get_v0_token_balance(account_index: u8, owner: &str, amount: u64) -> UiTransactionTokenBalance {
+    serde_json::from_value(json!({
+        "accountIndex": account_index,
+        "mint": USDC_MINT,
+        "owner": owner,
+        "uiTokenAmount": {
+            "amount": amount.to_string(),
+            "decimals": 6,
+            "uiAmount": null,
+            "uiAmountString": ""
+        }
+    }))
+    .unwrap()
+}
+
+fn get_v0_parsed_instruction(parsed: serde_json::Value, stack_height: Option<u32>) -> UiInstruction {
+    UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
+        program: "spl-token".to_string(),
+        program_id: TOKEN_PROGRAM_ID.to_string(),
+        parsed,
+        stack_height,
+    }))
+}
+
+fn get_v0_compiled_instruction(accounts: &[u8], data: &[u8], stack_height: Option<u32>) -> UiCompiledInstruction {
+    UiCompiledInstruction {
+        program_id_index: 3,
+        accounts: accounts.to_vec(),
+        data: bs58::encode(data).into_string(),
+        stack_height,
+    }
+}
+
+// As returned in the jsonParsed encoding, which lists the loaded keys among the account keys, or else in the json
+// encoding, which leaves every instruction compiled
+pub fn get_v0_txn(parsed: bool) -> EncodedTransactionWithStatusMeta {
+    let static_keys = [V0_FEE_PAYER, V0_SOURCE, AGGREGATOR_PROGRAM_ID, TOKEN_PROGRAM_ID];
+    let address_table_lookups = Some(vec![UiAddressTableLookup {
+        account_key: LOOKUP_TABLE.to_string(),
+        writable_indexes: vec![17],
+        readonly_indexes: vec![3],
+    }]);
+    let (message, inner_instruction) = if parsed {
+        let loaded_keys = [V0_DESTINATION, USDC_MINT];
+        let get_parsed_account = |(index, pubkey): (usize, &&str), source| ParsedAccount {
+            pubkey: pubkey.to_string(),
+            signer: index == 0,
+            source: Some(source),
+            writable: matches!(index, 0 | 1 | 4),
+        };
+        let account_keys = static_keys
+            .iter()
+            .enumerate()
+            .map(|account| get_parsed_account(account, ParsedAccountSource::Transaction))
+            .chain(
+                loaded_keys
+                    .iter()
+                    .enumerate()
+                    .map(|(index, pubkey)| get_parsed_account((index + 4, pubkey), ParsedAccountSource::LookupTable)),
+            )
+            .collect();
+        let aggregator_instruction = UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+            UiPartiallyDecodedInstruction {
+                program_id: AGGREGATOR_PROGRAM_ID.to_string(),
+                accounts: [V0_SOURCE, V0_DESTINATION, USDC_MINT, V0_FEE_PAYER, TOKEN_PROGRAM_ID]
+                    .map(str::to_string)
+                    .to_vec(),
+                data: "".to_string(),
+                stack_height: None,
+            },
+        ));
+        let transfer_instruction = get_v0_parsed_instruction(
+            json!({
+                "type": "transfer",
+                "info": {
+                    "source": V0_SOURCE,
+                    "destination": V0_DESTINATION,
+                    "authority": V0_FEE_PAYER,
+                    "amount": "250000"
+                }
+            }),
+            None,
+        );
+
+        (
+            UiMessage::Parsed(UiParsedMessage {
+                account_keys,
+                recent_blockhash: "".to_string(),
+                instructions: vec![aggregator_instruction, transfer_instruction],
+                address_table_lookups,
+            }),
+            get_v0_parsed_instruction(
+                json!({
+                    "type": "transferChecked",
+                    "info": {
+                        "source": V0_SOURCE,
+                        "mint": USDC_MINT,
+                        "destination": V0_DESTINATION,
+                        "authority": V0_FEE_PAYER,
+                        "tokenAmount": {"amount": "1000000", "decimals": 6, "uiAmount": 1.0, "uiAmountString": "1"}
+                    }
+                }),
+                Some(2),
+            ),
+        )
+    } else {
+        // SPL Token instructions are a one byte tag followed by their arguments: transfer is 3 with the amount, and
+        // transferChecked is 12 with the amount and decimals
+        let transfer_data = [&[3_u8][..], &250000_u64.to_le_bytes()].concat();
+        let transfer_checked_data = [&[12_u8][..], &1000000_u64.to_le_bytes(), &[6]].concat();
+
+        (
+            UiMessage::Raw(UiRawMessage {
+                header: MessageHeader {
+                    num_required_signatures: 1,
+                    num_readonly_signed_accounts: 0,
+                    num_readonly_unsigned_accounts: 2,
+                },
+                account_keys: static_keys.map(str::to_string).to_vec(),
+                recent_blockhash: "".to_string(),
+                instructions: vec![
+                    UiCompiledInstruction {
+                        program_id_index: 2,
+                        accounts: vec![1, 4, 5, 0, 3],
+                        data: "".to_string(),
+                        stack_height: None,
+                    },
+                    get_v0_compiled_instruction(&[1, 4, 0], &transfer_data, None),
+                ],
+                address_table_lookups,
+            }),
+            UiInstruction::Compiled(get_v0_compiled_instruction(&[1, 5, 4, 0], &transfer_checked_data, Some(2))),
+        )
+    };
+
+    EncodedTransactionWithStatusMeta {
+        transaction: EncodedTransaction::Json(UiTransaction {
+            signatures: get_signatures(),
+            message,
+        }),
+        meta: Some(UiTransactionStatusMeta {
+            inner_instructions: OptionSerializer::Some(vec![UiInnerInstructions {
+                index: 0,
+                instructions: vec![inner_instruction],
+            }]),
+            pre_token_balances: OptionSerializer::Some(vec![
+                get_v0_token_balance(1, V0_FEE_PAYER, 5000000),
+                get_v0_token_balance(4, V0_DESTINATION_OWNER, 0),
+            ]),
+            post_token_balances: OptionSerializer::Some(vec![
+                get_v0_token_balance(1, V0_FEE_PAYER, 3750000),
+                get_v0_token_balance(4, V0_DESTINATION_OWNER, 1250000),
+            ]),
+            loaded_addresses: OptionSerializer::Some(UiLoadedAddresses {
+                writable: vec![V0_DESTINATION.to_string()],
+                readonly: vec![USDC_MINT.to_string()],
+            }),
+            ..get_ui_txn_status_meta(false)
+        }),
+        version: Some(TransactionVersion::Number(0)),
+    }
+}
+
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
 fn test_01_should_parse_encoded_txn_without_status_meta() -> Result<(), String> {
-    let (usdc_txns, _) = process_transaction(&EncodedTransactionWithStatusMeta {
-        transaction: EncodedTransaction::Json(get_ui_transaction(true)),
-        meta: None,
-        version: None,
-    });
+    let ProcessedTransaction { transaction, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: None,
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
 
-    if usdc_txns.len() > 0 {
+    if !transaction.transfers.is_empty() {
         Ok(())
     } else {
         Err("Failed to parse encoded transaction".to_string())
@@ -101,13 +310,17 @@ fn test_01_should_parse_encoded_txn_without_status_meta() -> Result<(), String>
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
 fn test_02_should_parse_encoded_txn_with_non_error_status_meta() -> Result<(), String> {
-    let (usdc_txns, _) = process_transaction(&EncodedTransactionWithStatusMeta {
-        transaction: EncodedTransaction::Json(get_ui_transaction(true)),
-        meta: Some(get_ui_txn_status_meta(false)),
-        version: None,
-    });
+    let ProcessedTransaction { transaction, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: Some(get_ui_txn_status_meta(false)),
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
 
-    if usdc_txns.len() > 0 {
+    if !transaction.transfers.is_empty() {
         Ok(())
     } else {
         Err("Failed to parse encoded transaction".to_string())
@@ -117,15 +330,261 @@ fn test_02_should_parse_encoded_txn_with_non_error_status_meta() -> Result<(), S
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
 #[test]
 fn test_03_should_not_parse_unsigned_encoded_txn() -> Result<(), String> {
-    let (usdc_txns, unsigned_txn_count) = process_transaction(&EncodedTransactionWithStatusMeta {
-        transaction: EncodedTransaction::Json(get_ui_transaction(false)),
-        meta: Some(get_ui_txn_status_meta(false)),
-        version: None,
-    });
+    match process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(false)),
+            meta: Some(get_ui_txn_status_meta(false)),
+            version: None,
+        },
+        &Config::default(),
+    ) {
+        Err(ParseError::Unsigned) => Ok(()),
+        _ => Err("Failed to skip unsigned encoded transaction".to_string()),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_parse_inner_instructions() -> Result<(), String> {
+    let ProcessedTransaction { transaction, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: Some(get_ui_txn_status_meta_with_inner_instructions(Some(3))),
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
+
+    match transaction.transfers.as_slice() {
+        [outer, inner] => {
+            if outer.symbol != "USDC" || outer.mint != USDC_MINT {
+                Err(format!("Outer instruction tagged with mint {} ({})", outer.mint, outer.symbol))
+            } else if outer.txn.instruction_index != 0 || outer.txn.stack_height != 1 {
+                Err(format!(
+                    "Outer instruction tagged with index {} at stack height {}",
+                    outer.txn.instruction_index, outer.txn.stack_height
+                ))
+            } else if inner.txn.instruction_index != 0 || inner.txn.stack_height != 3 {
+                Err(format!(
+                    "Inner instruction tagged with index {} at stack height {}",
+                    inner.txn.instruction_index, inner.txn.stack_height
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        _ => Err(format!("Expected 2 USDC transactions, got {}", transaction.transfers.len())),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_05_should_default_stack_height_of_inner_instructions() -> Result<(), String> {
+    let ProcessedTransaction { transaction, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: Some(get_ui_txn_status_meta_with_inner_instructions(None)),
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
+
+    match transaction.transfers.last() {
+        Some(inner) if inner.txn.stack_height == 2 => Ok(()),
+        Some(inner) => Err(format!(
+            "Expected inner instruction stack height to default to 2, got {}",
+            inner.txn.stack_height
+        )),
+        None => Err("Failed to parse inner instruction".to_string()),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_06_should_count_rejected_instructions() -> Result<(), String> {
+    let mut ui_transaction = get_ui_transaction(true);
+
+    if let UiMessage::Parsed(ref mut message) = ui_transaction.message {
+        message.instructions.push(get_parsed_ui_instruction_for_test(4, true));
+        message.instructions.push(get_parsed_ui_instruction_for_test(12, false));
+    }
+
+    let ProcessedTransaction { transaction, rejections, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(ui_transaction),
+            meta: None,
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
+
+    let transfer_count = transaction.transfers.len();
+
+    if transfer_count == 1 && rejections.wrong_program == 1 && rejections.bad_amount == 1 && rejections.total() == 2 {
+        Ok(())
+    } else {
+        Err(format!(
+            "Expected 1 USDC transfer and 2 rejections, got {} USDC transfers and rejections: {}",
+            transfer_count,
+            rejections
+        ))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_07_should_attach_transaction_details() -> Result<(), String> {
+    let ProcessedTransaction { transaction, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: Some(UiTransactionStatusMeta {
+                compute_units_consumed: OptionSerializer::Some(6200),
+                ..get_ui_txn_status_meta_with_inner_instructions(Some(2))
+            }),
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
+
+    // The block time is attached by the caller
+    let details = (
+        transaction.fee_payer.as_str(),
+        transaction.transaction_fee,
+        transaction.compute_units_consumed,
+        transaction.block_time,
+        transaction.status,
+        transaction.transfers.len(),
+    );
+    let expected =
+        ("E1wid5KyTfkzxWDUmhxhJTKZqVRR4N6kyuTLXB5bPiU", Some(12345), Some(6200), None, TransactionStatus::Success, 2);
+
+    if details == expected {
+        Ok(())
+    } else {
+        Err(format!("Wrong transaction details {:?}", details))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_08_should_detect_supply_changes() -> Result<(), String> {
+    let ProcessedTransaction { supply_changes, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: Some(UiTransactionStatusMeta {
+                inner_instructions: OptionSerializer::from(Some(vec![UiInnerInstructions {
+                    index: 0,
+                    instructions: vec![
+                        get_parsed_ui_instruction_for_test(16, false),
+                        get_parsed_ui_instruction_for_test(17, false),
+                    ],
+                }])),
+                ..get_ui_txn_status_meta(false)
+            }),
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
+
+    let got: Vec<_> = supply_changes
+        .iter()
+        .map(|supply_change| (supply_change.kind, supply_change.signature.as_str(), supply_change.amount))
+        .collect();
+    let signature = get_signatures()[0].clone();
+
+    if got == [(SupplyChangeKind::Mint, signature.as_str(), 1470000), (SupplyChangeKind::Burn, &signature, 1470000)] {
+        Ok(())
+    } else {
+        Err(format!("Wrong supply changes {:?}", got))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_09_should_report_account_events_separately() -> Result<(), String> {
+    let ProcessedTransaction { transaction, account_events, .. } = process_transaction(
+        &EncodedTransactionWithStatusMeta {
+            transaction: EncodedTransaction::Json(get_ui_transaction(true)),
+            meta: Some(UiTransactionStatusMeta {
+                inner_instructions: OptionSerializer::from(Some(vec![UiInnerInstructions {
+                    index: 0,
+                    instructions: vec![
+                        get_parsed_ui_instruction_for_test(19, false),
+                        get_parsed_ui_instruction_for_test(22, false),
+                    ],
+                }])),
+                ..get_ui_txn_status_meta(false)
+            }),
+            version: None,
+        },
+        &Config::default(),
+    )
+    .map_err(|err| err.to_string())?;
+
+    let got: Vec<_> = account_events
+        .iter()
+        .map(|account_event| {
+            (account_event.event.get_type(), account_event.signature.as_str(), account_event.stack_height)
+        })
+        .collect();
+    let signature = get_signatures()[0].clone();
+
+    let expected = [("approve", signature.as_str(), 2), ("freeze_account", &signature, 2)];
+
+    if got == expected && transaction.transfers.len() == 1 {
+        Ok(())
+    } else {
+        Err(format!("Wrong account events {:?}, alongside {} transfers", got, transaction.transfers.len()))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_10_should_decode_v0_txn_with_loaded_addresses() -> Result<(), String> {
+    let mut decoded = Vec::new();
+
+    for parsed in [true, false] {
+        let ProcessedTransaction { transaction, rejections, .. } =
+            process_transaction(&get_v0_txn(parsed), &Config::default()).map_err(|err| err.to_string())?;
+        let got: Vec<_> = transaction
+            .transfers
+            .iter()
+            .map(|transfer| {
+                (
+                    transfer.txn.to.as_str(),
+                    transfer.txn.to_owner.as_deref(),
+                    transfer.symbol.as_str(),
+                    transfer.txn.amount,
+                    transfer.txn.instruction_index,
+                    transfer.txn.stack_height,
+                )
+            })
+            .collect();
+        let expected = [
+            (V0_DESTINATION, Some(V0_DESTINATION_OWNER), "USDC", 1000000, 0, 2),
+            (V0_DESTINATION, Some(V0_DESTINATION_OWNER), "USDC", 250000, 1, 1),
+        ];
+
+        if got != expected {
+            return Err(format!("Wrong transfers {:?} (parsed: {})", got, parsed));
+        } else if transaction.version != Some(TransactionVersion::Number(0)) {
+            return Err("Transactions should record their version".to_string());
+        } else if rejections.wrong_program != 1 || rejections.unsupported_encoding != 0 {
+            return Err(format!("Wrong rejections {} (parsed: {})", rejections, parsed));
+        }
+
+        decoded.push(serde_json::to_value(&transaction).map_err(|e| e.to_string())?);
+    }
 
-    if usdc_txns.len() == 0 && unsigned_txn_count == 1 {
+    // Decoding the compiled instructions should give exactly the same transfers as the RPC node's parsing
+    if decoded[0] == decoded[1] {
         Ok(())
     } else {
-        Err("Failed to skip unsigned encoded transaction".to_string())
+        Err(format!("Parsed {} differs from compiled {}", decoded[0], decoded[1]))
     }
 }
@@ -0,0 +1,242 @@
+use crate::{
+    query::TransferFilter,
+    solana::{SignedUsdcTransaction, SignedUsdcTransactionsBySlot, TransferRecord},
+    state::AppState,
+    MAX_WS_SUBSCRIPTIONS, WS_SEND_BUFFER,
+};
+
+use axum::extract::ws::{Message, WebSocket};
+use futures::{SinkExt, StreamExt};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::{
+    select,
+    sync::{broadcast::error::RecvError, mpsc},
+    task,
+};
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// A transfer matches when every populated criterion matches.  Addresses (token accounts) and owners (wallets) match
+// either the source or the destination, and mints match either the mint address or the token symbol
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct SubscriptionFilter {
+    pub addresses: Vec<String>,
+    pub owners: Vec<String>,
+    pub mints: Vec<String>,
+    pub min_amount: Option<u64>,
+    pub max_amount: Option<u64>,
+}
+
+impl SubscriptionFilter {
+    pub fn matches(&self, transfer: &TransferRecord) -> bool {
+        TransferFilter {
+            mints: &self.mints,
+            addresses: &self.addresses,
+            owners: &self.owners,
+            min_amount: self.min_amount,
+            max_amount: self.max_amount,
+            ..TransferFilter::default()
+        }
+        .matches(transfer)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    // Subscribing again with the same ID replaces the filter
+    Subscribe {
+        id: String,
+        #[serde(flatten)]
+        filter: SubscriptionFilter,
+    },
+    Unsubscribe {
+        id: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed {
+        id: String,
+    },
+    Unsubscribed {
+        id: String,
+    },
+    // Sent once per transfer, listing every subscription it matches
+    Transfer {
+        subscriptions: Vec<String>,
+        slot: u64,
+        transaction: Box<SignedUsdcTransaction>,
+    },
+    // Transfers from this many slots were dropped because the client was not keeping up
+    Lagged {
+        missed_slots: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+// Subscriptions of one connection, by ID
+pub type Subscriptions = BTreeMap<String, SubscriptionFilter>;
+
+pub fn handle_client_message(subscriptions: &mut Subscriptions, text: &str) -> ServerMessage {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe { id, filter }) => {
+            if subscriptions.len() >= MAX_WS_SUBSCRIPTIONS && !subscriptions.contains_key(&id) {
+                return ServerMessage::Error {
+                    message: format!("No more than {} subscriptions are allowed", MAX_WS_SUBSCRIPTIONS),
+                };
+            }
+
+            subscriptions.insert(id.clone(), filter);
+            ServerMessage::Subscribed { id }
+        }
+        Ok(ClientMessage::Unsubscribe { id }) => match subscriptions.remove(&id) {
+            Some(_) => ServerMessage::Unsubscribed { id },
+            None => ServerMessage::Error {
+                message: format!("Unknown subscription '{}'", id),
+            },
+        },
+        Err(e) => ServerMessage::Error {
+            message: format!("Invalid message: {}", e),
+        },
+    }
+}
+
+pub fn get_transfer_messages(
+    subscriptions: &Subscriptions,
+    txns_by_slot: &SignedUsdcTransactionsBySlot,
+) -> Vec<ServerMessage> {
+    txns_by_slot
+        .transfers()
+        .filter_map(|(transaction, transfer)| {
+            let matching: Vec<String> = subscriptions
+                .iter()
+                .filter(|(_, filter)| filter.matches(transfer))
+                .map(|(id, _)| id.clone())
+                .collect();
+
+            (!matching.is_empty()).then(|| ServerMessage::Transfer {
+                subscriptions: matching,
+                slot: txns_by_slot.slot,
+                transaction: Box::new(SignedUsdcTransaction::new(transaction, transfer)),
+            })
+        })
+        .collect()
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+// Bounded queue of messages waiting to be written to the socket.  Rather than let a slow client hold up the monitor
+// (or use unbounded memory), whole slots are dropped while the queue is full, and the client is told how many once it
+// has caught up
+pub struct Outbox {
+    sender: mpsc::Sender<ServerMessage>,
+    missed_slots: u64,
+}
+
+impl Outbox {
+    pub fn new(sender: mpsc::Sender<ServerMessage>) -> Self {
+        Outbox {
+            sender,
+            missed_slots: 0,
+        }
+    }
+
+    pub fn add_missed_slots(&mut self, missed_slots: u64) {
+        self.missed_slots += missed_slots;
+    }
+
+    // Only called once there is known to be room, so this can fail only if the socket has just been closed
+    fn queue(&self, message: ServerMessage) {
+        let _ = self.sender.try_send(message);
+    }
+
+    // Queue the transfers of one slot.  Returns false once the socket has been closed
+    pub fn push_slot(&mut self, messages: Vec<ServerMessage>) -> bool {
+        if self.sender.is_closed() {
+            return false;
+        }
+
+        if messages.is_empty() {
+            return true;
+        }
+
+        // There must be room for the whole slot, as well as the notice of any slots missed before it
+        if self.sender.capacity() < messages.len() + usize::from(self.missed_slots > 0) {
+            self.missed_slots += 1;
+            return true;
+        }
+
+        if self.missed_slots > 0 {
+            let missed_slots = std::mem::take(&mut self.missed_slots);
+
+            warn!("WebSocket client missed {} slots", missed_slots);
+            self.queue(ServerMessage::Lagged { missed_slots });
+        }
+
+        for message in messages {
+            self.queue(message);
+        }
+
+        true
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+pub async fn handle_socket(socket: WebSocket, state: AppState) {
+    let (mut socket_sender, mut socket_receiver) = socket.split();
+    let (sender, mut receiver) = mpsc::channel::<ServerMessage>(WS_SEND_BUFFER);
+    let mut outbox = Outbox::new(sender.clone());
+    let mut events = state.events.subscribe();
+    let mut subscriptions = Subscriptions::new();
+
+    let writer = task::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let Ok(text) = serde_json::to_string(&message) else {
+                continue;
+            };
+
+            if socket_sender.send(Message::Text(text.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        select! {
+            message = socket_receiver.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let reply = handle_client_message(&mut subscriptions, text.as_str());
+
+                    // Replies are never dropped: stop reading from the client until there is room for them
+                    if sender.send(reply).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by axum itself
+                Some(Ok(_)) => (),
+            },
+            event = events.recv() => match event {
+                Ok(txns_by_slot) => {
+                    if !outbox.push_slot(get_transfer_messages(&subscriptions, &txns_by_slot)) {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(missed_slots)) => outbox.add_missed_slots(missed_slots),
+                Err(RecvError::Closed) => break,
+            },
+        }
+    }
+
+    writer.abort();
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[cfg(test)]
+mod unit_tests;
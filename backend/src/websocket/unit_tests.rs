@@ -0,0 +1,130 @@
+use crate::{
+    test_data::{get_slot, get_transaction, get_transfer},
+    websocket::{
+        get_transfer_messages, handle_client_message, Outbox, ServerMessage, SubscriptionFilter, Subscriptions,
+    },
+    MAX_WS_SUBSCRIPTIONS,
+};
+
+use tokio::sync::mpsc;
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+fn get_transfer_messages_for(slot: u64, transfer_count: usize) -> Vec<ServerMessage> {
+    let mut subscriptions = Subscriptions::new();
+    let txns_by_slot = get_slot(slot, vec![get_transaction("USDC", "Alice", "Bob", 100); transfer_count]);
+
+    subscriptions.insert("all".to_string(), SubscriptionFilter::default());
+    get_transfer_messages(&subscriptions, &txns_by_slot)
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_01_should_match_every_populated_criterion() -> Result<(), String> {
+    let filter = SubscriptionFilter {
+        addresses: vec!["Bob".to_string()],
+        owners: vec![],
+        mints: vec!["usdc".to_string()],
+        min_amount: Some(100),
+        max_amount: None,
+    };
+    let results = [
+        filter.matches(&get_transfer("USDC", "Alice", "Bob", 100)),
+        filter.matches(&get_transfer("USDC", "Alice", "Carol", 100)),
+        filter.matches(&get_transfer("USDT", "Alice", "Bob", 100)),
+        filter.matches(&get_transfer("USDC", "Alice", "Bob", 99)),
+        SubscriptionFilter {
+            mints: vec!["USDCMint".to_string()],
+            ..SubscriptionFilter::default()
+        }
+        .matches(&get_transfer("USDC", "Alice", "Carol", 1)),
+    ];
+
+    if results == [true, false, false, false, true] {
+        Ok(())
+    } else {
+        Err(format!("Wrong matches {:?}", results))
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_02_should_subscribe_and_unsubscribe() -> Result<(), String> {
+    let mut subscriptions = Subscriptions::new();
+    let replies = [
+        r#"{"type": "subscribe", "id": "bob", "addresses": ["Bob"]}"#,
+        r#"{"type": "subscribe", "id": "big", "min_amount": 1000}"#,
+        r#"{"type": "unsubscribe", "id": "big"}"#,
+        r#"{"type": "unsubscribe", "id": "big"}"#,
+        r#"{"type": "subscribe"}"#,
+    ]
+    .map(|text| {
+        let reply = handle_client_message(&mut subscriptions, text);
+
+        serde_json::to_value(reply).unwrap_or_default()["type"].clone()
+    });
+
+    if replies != ["subscribed", "subscribed", "unsubscribed", "error", "error"] {
+        return Err(format!("Wrong replies {:?}", replies));
+    }
+
+    let txns_by_slot = get_slot(7, vec![get_transaction("USDC", "Alice", "Bob", 100)]);
+
+    match get_transfer_messages(&subscriptions, &txns_by_slot).as_slice() {
+        [ServerMessage::Transfer { subscriptions, slot: 7, .. }] if subscriptions == &["bob"] => Ok(()),
+        other => Err(format!("Expected a transfer for subscription 'bob', got {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_03_should_limit_subscriptions() -> Result<(), String> {
+    let mut subscriptions = Subscriptions::new();
+
+    for id in 0..MAX_WS_SUBSCRIPTIONS {
+        handle_client_message(&mut subscriptions, &format!(r#"{{"type": "subscribe", "id": "{}"}}"#, id));
+    }
+
+    // Replacing an existing subscription is still allowed
+    match (
+        handle_client_message(&mut subscriptions, r#"{"type": "subscribe", "id": "extra"}"#),
+        handle_client_message(&mut subscriptions, r#"{"type": "subscribe", "id": "0", "min_amount": 1}"#),
+    ) {
+        (ServerMessage::Error { .. }, ServerMessage::Subscribed { .. })
+            if subscriptions.len() == MAX_WS_SUBSCRIPTIONS =>
+        {
+            Ok(())
+        }
+        other => Err(format!("Wrong replies {:?}", other)),
+    }
+}
+
+// - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
+#[test]
+fn test_04_should_drop_whole_slots_for_slow_client() -> Result<(), String> {
+    let (sender, mut receiver) = mpsc::channel(4);
+    let mut outbox = Outbox::new(sender);
+
+    // The second slot does not fit alongside the first, and the third does not leave room for the notice
+    for (slot, transfer_count) in [(1, 3), (2, 2), (3, 1)] {
+        outbox.push_slot(get_transfer_messages_for(slot, transfer_count));
+    }
+
+    let mut received: Vec<u64> = Vec::new();
+
+    while let Ok(message) = receiver.try_recv() {
+        if let ServerMessage::Transfer { slot, .. } = message {
+            received.push(slot);
+        }
+    }
+
+    // Once the client has caught up, it is told about the missed slots before receiving any more
+    outbox.push_slot(get_transfer_messages_for(4, 1));
+
+    match receiver.try_recv() {
+        Ok(ServerMessage::Lagged { missed_slots: 2 }) if received == [1, 1, 1] => match receiver.try_recv() {
+            Ok(ServerMessage::Transfer { slot: 4, .. }) => Ok(()),
+            other => Err(format!("Expected a transfer from slot 4, got {:?}", other)),
+        },
+        other => Err(format!("Expected notice of 2 missed slots after {:?}, got {:?}", received, other)),
+    }
+}
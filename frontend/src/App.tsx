@@ -1,8 +1,10 @@
-import { useEffect, useState, Fragment } from "react";
-import { SignedUsdcTransactionsBySlot } from "./Solana";
+import { useEffect, useRef, useState, Fragment } from "react";
+import { SignedUsdcTransactionsBySlot, TransactionsPage } from "./Solana";
 
 const API_URL = "http://localhost:3000/transactions";
 const REFRESH_INTERVAL = 2000;
+const PAGE_LIMIT = 1000;
+const MAX_DISPLAYED_SLOTS = 1000;
 const COL_COUNT = 6;
 
 // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
@@ -13,18 +15,31 @@ function App() {
   const [showSignatures, setShowSignatures] = useState<boolean>(false);
   const [loading, setLoading] = useState<boolean>(true);
   const [error, setError] = useState<string | null>(null);
+  // Slot after which to fetch on the next poll
+  const cursor = useRef<number | null>(null);
 
   // - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
   const fetchTransactions = async () => {
     try {
-      const response = await fetch(API_URL);
-      if (!response.ok) throw new Error("Fetch failed");
+      let newTxns: SignedUsdcTransactionsBySlot[] = [];
+      let hasMore = true;
 
-      const newTxns: SignedUsdcTransactionsBySlot[] = await response.json();
+      // Follow the cursor until every slot processed since the previous poll has been fetched
+      while (hasMore) {
+        const afterSlot = cursor.current === null ? "" : `&after_slot=${cursor.current}`;
+        const response = await fetch(`${API_URL}?limit=${PAGE_LIMIT}${afterSlot}`);
+        if (!response.ok) throw new Error("Fetch failed");
+
+        const page: TransactionsPage = await response.json();
+
+        newTxns = newTxns.concat(page.slots);
+        cursor.current = page.next_cursor;
+        hasMore = page.has_more;
+      }
 
       // Only update if there are actual changes
-      if (JSON.stringify(newTxns) !== JSON.stringify(currentTxns)) {
-        setCurrentTxns(prevTxns => JSON.stringify(prevTxns) !== JSON.stringify(newTxns) ? newTxns : prevTxns);
+      if (newTxns.length > 0) {
+        setCurrentTxns(prevTxns => prevTxns.concat(newTxns).slice(-MAX_DISPLAYED_SLOTS));
       }
 
       setError(null);
@@ -91,15 +106,16 @@ function App() {
             Latest block: {txnsBySlot.slot}
           </td>
         </tr>
-        {txnsBySlot.txns.map((tx) => (
-          <Fragment key={tx.signatures[0]}>
+        {/* A transaction performing several transfers has one entry per transfer, all with the same signatures */}
+        {txnsBySlot.txns.map((tx, index) => (
+          <Fragment key={`${tx.signatures[0]}-${index}`}>
             <tr>
               <td>TX detected:</td>
-              <td>{tx.txn.from}</td>
+              <td title={tx.txn.from}>{tx.txn.from_owner ?? tx.txn.from}</td>
               <td>sent</td>
-              <td style={{textAlign: "right"}}>{tx.txn.amount}</td>
-              <td>USDC to</td>
-              <td>{tx.txn.to}</td>
+              <td style={{textAlign: "right"}}>{tx.txn.ui_amount}</td>
+              <td>{tx.symbol} to</td>
+              <td title={tx.txn.to}>{tx.txn.to_owner ?? tx.txn.to}</td>
             </tr>
             {showSignatures && (
             <tr>
@@ -137,7 +153,7 @@ function App() {
       </div>
       <table className="txn-table">
         <thead>
-          <tr><th colSpan={COL_COUNT}>Solana USDC Transactions</th></tr>
+          <tr><th colSpan={COL_COUNT}>Solana Stablecoin Transactions</th></tr>
         </thead>
         <tbody>
           {renderTableContent()}
@@ -1,3 +1,9 @@
+interface TransactionsPage {
+    slots: SignedUsdcTransactionsBySlot[];
+    next_cursor: number | null;
+    has_more: boolean;
+}
+
 interface SignedUsdcTransactionsBySlot {
     slot: number;
     txns: SignedUsdcTransaction[];
@@ -5,16 +11,33 @@ interface SignedUsdcTransactionsBySlot {
 
 interface SignedUsdcTransaction {
     signatures: string[];
+    mint: string;
+    symbol: string;
     txn: UsdcTransaction;
+    block_time: number | null;
+    fee_payer: string;
+    transaction_fee: number | null;
+    compute_units_consumed: number | null;
+    // "legacy", or the version number
+    version: "legacy" | number | null;
 }
 
 interface UsdcTransaction {
     from: string;
     to: string;
-    amount: number;
+    from_owner: string | null;
+    to_owner: string | null;
+    // The amount in base units is left out, as it may be too large for a number to hold exactly.  ui_amount renders
+    // it exactly
+    decimals: number;
+    ui_amount: string;
+    fee: number | null;
+    instruction_index: number;
+    stack_height: number;
 }
 
 export type {
+  TransactionsPage,
   SignedUsdcTransactionsBySlot,
   SignedUsdcTransaction,
   UsdcTransaction